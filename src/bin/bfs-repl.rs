@@ -0,0 +1,23 @@
+//! Build chapter 2 chains interactively. Type `help` to list the commands.
+
+use blockchain_from_scratch::repl::Session;
+use std::io::{self, Write};
+
+fn main() {
+	let mut session = Session::new();
+	println!("Starting from the genesis block. Type `help` for commands and `quit` to leave.");
+	let mut line = String::new();
+	loop {
+		print!("> ");
+		io::stdout().flush().expect("stdout is writable");
+		line.clear();
+		if io::stdin().read_line(&mut line).unwrap_or(0) == 0 || line.trim() == "quit" {
+			break;
+		}
+		match session.execute(&line) {
+			Ok(output) if output.is_empty() => {},
+			Ok(output) => println!("{output}"),
+			Err(error) => println!("error: {error}"),
+		}
+	}
+}
@@ -0,0 +1,8 @@
+//! Print the test vectors as JSON, for checking an implementation of these exercises in another
+//! language.
+//!
+//! Usage: `cargo run --bin bfs-vectors > vectors.json`
+
+fn main() {
+	print!("{}", blockchain_from_scratch::vectors::generate());
+}
@@ -0,0 +1,303 @@
+//! Grade a copy of this tutorial by running each exercise group's tests in order.
+//!
+//! Every exercise's tests share a prefix, such as `sm_2_` for chapter 1 part 2 or `bc_4_` for
+//! chapter 2 part 4. Modules outside the numbered parts are not exercises, so they are not graded.
+//! Their tests are prefixed with the chapter and the module name instead, such as `bc_export_`.
+//!
+//! The grader builds the tests once, then runs one group at a time and records whether each test
+//! passed, failed, or timed out. Unfinished exercises are often `todo!()`s that fail immediately,
+//! but an infinite loop is an easy mistake when mining or searching, so every test gets a deadline.
+//!
+//! Usage: `cargo run --bin grader -- [--json] [PATH_TO_REPO]`
+//!
+//! By default a human readable summary is printed. With `--json` a single JSON object is printed
+//! instead, so the results of many student repos can be collected by a script.
+
+use std::{
+	io::{BufRead, BufReader},
+	process::{Command, ExitCode, Stdio},
+	sync::mpsc,
+	thread,
+	time::Duration,
+};
+
+/// The exercise groups in the order the tutorial presents them, with the test prefix of each.
+const GROUPS: [(&str, &str); 14] = [
+	("Chapter 1 Part 1: Switches", "sm_1_"),
+	("Chapter 1 Part 2: Laundry Machine", "sm_2_"),
+	("Chapter 1 Part 3: ATM", "sm_3_"),
+	("Chapter 1 Part 4: Accounted Currency", "sm_4_"),
+	("Chapter 1 Part 5: Digital Cash", "sm_5_"),
+	("Chapter 1 Part 7: Commit Reveal", "sm_7_"),
+	("Chapter 2 Part 1: Header Chain", "bc_1_"),
+	("Chapter 2 Part 2: Extrinsics and State", "bc_2_"),
+	("Chapter 2 Part 3: Consensus", "bc_3_"),
+	("Chapter 2 Part 4: Batched Extrinsics", "bc_4_"),
+	("Chapter 2 Part 5: Fork Choice", "bc_5_"),
+	("Chapter 2 Part 6: Rich State", "bc_6_"),
+	("Chapter 3 Part 1: Proof of Work", "cs_1_"),
+	("Chapter 3 Part 3: Proof of Authority", "cs_3_"),
+];
+
+/// How long a single test may run before it is counted as timed out.
+const TEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Outcome {
+	Passed,
+	Failed,
+	Ignored,
+	TimedOut,
+}
+
+impl Outcome {
+	fn as_str(self) -> &'static str {
+		match self {
+			Outcome::Passed => "passed",
+			Outcome::Failed => "failed",
+			Outcome::Ignored => "ignored",
+			Outcome::TimedOut => "timed_out",
+		}
+	}
+}
+
+/// The results of one exercise group.
+struct GroupReport {
+	name: &'static str,
+	prefix: &'static str,
+	tests: Vec<(String, Outcome)>,
+}
+
+impl GroupReport {
+	fn count(&self, outcome: Outcome) -> usize {
+		self.tests.iter().filter(|(_, o)| *o == outcome).count()
+	}
+
+	/// The number of tests that count towards the grade, which is all of them except ignored ones
+	/// such as benchmarks.
+	fn graded(&self) -> usize {
+		self.tests.len() - self.count(Outcome::Ignored)
+	}
+}
+
+/// A `cargo test` command for the library of the repo at `path`, passing `args` to the test
+/// harness.
+fn cargo_test(path: &str, args: &[&str]) -> Command {
+	let mut command = Command::new(env!("CARGO"));
+	command
+		.args(["test", "--quiet", "--lib", "--manifest-path"])
+		.arg(format!("{path}/Cargo.toml"))
+		.arg("--")
+		.args(args);
+	command
+}
+
+/// The full names of the tests whose name starts with the prefix, in the order the harness runs
+/// them.
+fn list_tests(path: &str, prefix: &str) -> Vec<String> {
+	let Ok(output) = cargo_test(path, &[prefix, "--list"]).stderr(Stdio::null()).output() else {
+		return Vec::new();
+	};
+	let mut tests: Vec<String> = String::from_utf8_lossy(&output.stdout)
+		.lines()
+		.filter_map(|line| line.strip_suffix(": test"))
+		.filter(|name| name.rsplit("::").next().is_some_and(|test| test.starts_with(prefix)))
+		.map(String::from)
+		.collect();
+	tests.sort();
+	tests
+}
+
+/// Parse a harness line such as `test c2_blockchain::p1_header_chain::bc_1_genesis ... ok`.
+fn parse_result(line: &str) -> Option<(String, Outcome)> {
+	let (name, result) = line.strip_prefix("test ")?.split_once(" ... ")?;
+	let outcome = match result {
+		"ok" => Outcome::Passed,
+		"FAILED" => Outcome::Failed,
+		_ if result.starts_with("ignored") => Outcome::Ignored,
+		_ => return None,
+	};
+	Some((name.to_string(), outcome))
+}
+
+/// Run exactly the named tests one at a time. Returns the results that were reported before the
+/// run finished or a single test took longer than `TEST_TIMEOUT`, in which case the run is killed.
+fn run_tests(path: &str, tests: &[String]) -> Vec<(String, Outcome)> {
+	// `cargo --quiet` also quiets the harness, so ask for one line per test explicitly.
+	let mut args = vec!["--exact", "--test-threads=1", "--format=pretty"];
+	args.extend(tests.iter().map(String::as_str));
+	let Ok(mut child) =
+		cargo_test(path, &args).stdout(Stdio::piped()).stderr(Stdio::null()).spawn()
+	else {
+		return Vec::new();
+	};
+
+	let stdout = child.stdout.take().expect("stdout was piped");
+	let (sender, receiver) = mpsc::channel();
+	thread::spawn(move || {
+		for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+			if sender.send(line).is_err() {
+				break;
+			}
+		}
+	});
+
+	let mut results = Vec::new();
+	loop {
+		match receiver.recv_timeout(TEST_TIMEOUT) {
+			Ok(line) => results.extend(parse_result(&line)),
+			Err(mpsc::RecvTimeoutError::Disconnected) => break,
+			Err(mpsc::RecvTimeoutError::Timeout) => {
+				let _ = child.kill();
+				break;
+			},
+		}
+	}
+	let _ = child.wait();
+	results
+}
+
+/// Run one group. Whenever a test hangs, it is recorded as timed out and the rest of the group is
+/// run again without it.
+fn run_group(path: &str, name: &'static str, prefix: &'static str) -> GroupReport {
+	let mut tests: Vec<(String, Option<Outcome>)> =
+		list_tests(path, prefix).into_iter().map(|test| (test, None)).collect();
+	loop {
+		let remaining: Vec<String> = tests
+			.iter()
+			.filter(|(_, outcome)| outcome.is_none())
+			.map(|(test, _)| test.clone())
+			.collect();
+		if remaining.is_empty() {
+			break;
+		}
+		let results = run_tests(path, &remaining);
+		for (test, outcome) in tests.iter_mut() {
+			if let Some((_, result)) = results.iter().find(|(name, _)| name == test) {
+				*outcome = Some(*result);
+			}
+		}
+		// The harness runs tests in name order, so the first one without a result is the one that
+		// was running when the run ended.
+		if let Some((_, outcome)) = tests.iter_mut().find(|(_, outcome)| outcome.is_none()) {
+			*outcome = Some(Outcome::TimedOut);
+		}
+	}
+	let tests = tests
+		.into_iter()
+		.map(|(test, outcome)| (test, outcome.unwrap_or(Outcome::TimedOut)))
+		.collect();
+	GroupReport { name, prefix, tests }
+}
+
+/// Quote a string for JSON. Test names are Rust paths, but escape properly anyway.
+fn json_string(s: &str) -> String {
+	let mut out = String::from("\"");
+	for c in s.chars() {
+		match c {
+			'"' => out.push_str("\\\""),
+			'\\' => out.push_str("\\\\"),
+			c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+			c => out.push(c),
+		}
+	}
+	out.push('"');
+	out
+}
+
+fn to_json(path: &str, compiled: bool, reports: &[GroupReport]) -> String {
+	let groups: Vec<String> = reports
+		.iter()
+		.map(|report| {
+			let tests: Vec<String> = report
+				.tests
+				.iter()
+				.map(|(name, outcome)| {
+					format!(
+						"{{\"name\":{},\"outcome\":\"{}\"}}",
+						json_string(name),
+						outcome.as_str()
+					)
+				})
+				.collect();
+			format!(
+				"{{\"name\":{},\"prefix\":{},\"passed\":{},\"total\":{},\"tests\":[{}]}}",
+				json_string(report.name),
+				json_string(report.prefix),
+				report.count(Outcome::Passed),
+				report.graded(),
+				tests.join(",")
+			)
+		})
+		.collect();
+	format!(
+		"{{\"repo\":{},\"compiled\":{compiled},\"groups\":[{}]}}",
+		json_string(path),
+		groups.join(",")
+	)
+}
+
+fn print_summary(reports: &[GroupReport]) {
+	for report in reports {
+		println!(
+			"{:<42} {:>3}/{:<3} passed",
+			report.name,
+			report.count(Outcome::Passed),
+			report.graded()
+		);
+		for (name, outcome) in &report.tests {
+			if matches!(outcome, Outcome::Failed | Outcome::TimedOut) {
+				println!(
+					"    {:<10} {}",
+					outcome.as_str(),
+					name.rsplit("::").next().unwrap_or(name)
+				);
+			}
+		}
+	}
+	let passed: usize = reports.iter().map(|report| report.count(Outcome::Passed)).sum();
+	let total: usize = reports.iter().map(|report| report.graded()).sum();
+	println!("\nTotal: {passed}/{total} tests passed");
+}
+
+fn main() -> ExitCode {
+	let mut json = false;
+	let mut path = String::from(".");
+	for arg in std::env::args().skip(1) {
+		match arg.as_str() {
+			"--json" => json = true,
+			_ => path = arg,
+		}
+	}
+
+	// Build once up front, so a compile error is reported as such rather than as every test
+	// failing.
+	let compiled = Command::new(env!("CARGO"))
+		.args(["test", "--quiet", "--lib", "--no-run", "--manifest-path"])
+		.arg(format!("{path}/Cargo.toml"))
+		.stdout(Stdio::null())
+		.stderr(Stdio::null())
+		.status()
+		.is_ok_and(|status| status.success());
+
+	let reports: Vec<GroupReport> = if compiled {
+		GROUPS.iter().map(|(name, prefix)| run_group(&path, name, prefix)).collect()
+	} else {
+		Vec::new()
+	};
+
+	if json {
+		println!("{}", to_json(&path, compiled, &reports));
+	} else if compiled {
+		print_summary(&reports);
+	} else {
+		println!("The repo at {path} does not compile, so no tests could be run. Run `cargo test`");
+		println!("there to see the errors.");
+	}
+
+	if compiled {
+		ExitCode::SUCCESS
+	} else {
+		ExitCode::FAILURE
+	}
+}
@@ -0,0 +1,192 @@
+//! Laws that every state machine should obey, checked with property tests.
+//!
+//! The unit tests in each part check a handful of hand picked transitions. Property tests instead
+//! generate hundreds of random states and transitions and check that some rule holds for all of
+//! them. When a rule fails, proptest shrinks the input to the smallest example it can find, which
+//! is usually exactly the corner case you forgot.
+//!
+//! To check your own machine, implement `LawfulMachine` for it, describing how to generate states
+//! and transitions and optionally any machine-specific law, then call `state_machine_laws` from a
+//! test. The laws are:
+//! 1. Determinism. The same transition from the same state always gives the same result. Every
+//!    node on a network must agree on the next state, so this is not optional for a blockchain.
+//! 2. Conservation. Whatever the machine promises to preserve, such as the total issuance of a
+//!    currency, is preserved by every transition.
+//! 3. Serialization. States survive being encoded and decoded, if the machine supports it.
+
+use super::StateMachine;
+use core::fmt::Debug;
+use proptest::{
+	prelude::*,
+	test_runner::{Config, TestRunner},
+};
+
+/// A state machine that knows how to generate arbitrary inputs for itself.
+pub trait LawfulMachine:
+	StateMachine<State: Clone + Debug + PartialEq, Transition: Clone + Debug>
+{
+	/// Generate states. These should only be states the machine can actually be in, or the
+	/// conservation law may fail for states that could never occur.
+	fn states() -> BoxedStrategy<Self::State>;
+
+	/// Generate transitions, both valid and invalid ones.
+	fn transitions() -> BoxedStrategy<Self::Transition>;
+
+	/// The machine-specific law relating a state, a transition, and the state it leads to. By
+	/// default there is none.
+	fn conserves(_pre: &Self::State, _t: &Self::Transition, _post: &Self::State) -> bool {
+		true
+	}
+
+	/// Encode and decode a state, or return `None` if the machine has no serialization.
+	fn round_trip(_state: &Self::State) -> Option<Self::State> {
+		None
+	}
+}
+
+/// Check every law on `cases` random inputs, panicking with the smallest failing example if one
+/// does not hold.
+pub fn state_machine_laws<M: LawfulMachine>(cases: u32) {
+	let mut runner = TestRunner::new(Config::with_cases(cases));
+	let result = runner.run(&(M::states(), M::transitions()), |(state, transition)| {
+		let post = M::next_state(&state, &transition);
+		prop_assert_eq!(&post, &M::next_state(&state, &transition), "not deterministic");
+		prop_assert!(M::conserves(&state, &transition, &post), "conservation law violated");
+		for state in [&state, &post] {
+			if let Some(decoded) = M::round_trip(state) {
+				prop_assert_eq!(&decoded, state, "serialization round trip changed the state");
+			}
+		}
+		Ok(())
+	});
+	if let Err(error) = result {
+		panic!("{} broke a law: {error}", M::human_name());
+	}
+}
+
+/// A `round_trip` for any machine whose states support serde, going through JSON.
+#[cfg(feature = "serde")]
+pub fn json_round_trip<T: serde::Serialize + serde::de::DeserializeOwned>(state: &T) -> Option<T> {
+	serde_json::from_str(&serde_json::to_string(state).ok()?).ok()
+}
+
+// The machines from this chapter, as examples of implementing the trait.
+
+#[cfg(feature = "std")]
+use super::p4_accounted_currency::{
+	issuance_invariant_holds, AccountingTransaction, IssuanceTrackedCurrency, TrackedBalances,
+};
+use super::{p1_switches::LightSwitch, User};
+
+fn users() -> impl Strategy<Value = User> {
+	prop_oneof![Just(User::Alice), Just(User::Bob), Just(User::Charlie)]
+}
+
+impl LawfulMachine for LightSwitch {
+	fn states() -> BoxedStrategy<bool> {
+		any::<bool>().boxed()
+	}
+
+	fn transitions() -> BoxedStrategy<()> {
+		Just(()).boxed()
+	}
+
+	fn conserves(pre: &bool, _t: &(), post: &bool) -> bool {
+		pre != post
+	}
+}
+
+#[cfg(feature = "std")]
+impl LawfulMachine for IssuanceTrackedCurrency {
+	fn states() -> BoxedStrategy<TrackedBalances> {
+		// Balances are kept well below `u64::MAX` so that minting cannot overflow, and zero
+		// balances are left out because accounts are removed when they empty.
+		proptest::collection::hash_map(users(), 1..1_000_000u64, 0..=3)
+			.prop_map(TrackedBalances::from)
+			.boxed()
+	}
+
+	fn transitions() -> BoxedStrategy<AccountingTransaction> {
+		let amount = 0..2_000_000u64;
+		prop_oneof![
+			(users(), amount.clone())
+				.prop_map(|(minter, amount)| AccountingTransaction::Mint { minter, amount }),
+			(users(), amount.clone())
+				.prop_map(|(burner, amount)| AccountingTransaction::Burn { burner, amount }),
+			(users(), users(), amount).prop_map(|(sender, receiver, amount)| {
+				AccountingTransaction::Transfer { sender, receiver, amount }
+			}),
+		]
+		.boxed()
+	}
+
+	fn conserves(
+		_pre: &TrackedBalances,
+		_t: &AccountingTransaction,
+		post: &TrackedBalances,
+	) -> bool {
+		issuance_invariant_holds(post)
+	}
+
+	#[cfg(feature = "serde")]
+	fn round_trip(state: &TrackedBalances) -> Option<TrackedBalances> {
+		json_round_trip(state)
+	}
+}
+
+#[test]
+fn sm_laws_light_switch() {
+	state_machine_laws::<LightSwitch>(64);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn sm_laws_issuance_tracked_currency() {
+	state_machine_laws::<IssuanceTrackedCurrency>(256);
+}
+
+/// A currency with a bug: transfers to yourself print money.
+#[cfg(feature = "std")]
+struct LeakyCurrency;
+
+#[cfg(feature = "std")]
+impl StateMachine for LeakyCurrency {
+	type State = TrackedBalances;
+	type Transition = AccountingTransaction;
+
+	fn next_state(state: &TrackedBalances, t: &AccountingTransaction) -> TrackedBalances {
+		let mut next = IssuanceTrackedCurrency::next_state(state, t);
+		if let AccountingTransaction::Transfer { sender, receiver, amount } = t {
+			if sender == receiver && next.balances.contains_key(sender) {
+				*next.balances.get_mut(sender).unwrap() += amount;
+			}
+		}
+		next
+	}
+}
+
+#[cfg(feature = "std")]
+impl LawfulMachine for LeakyCurrency {
+	fn states() -> BoxedStrategy<TrackedBalances> {
+		IssuanceTrackedCurrency::states()
+	}
+
+	fn transitions() -> BoxedStrategy<AccountingTransaction> {
+		IssuanceTrackedCurrency::transitions()
+	}
+
+	fn conserves(
+		_pre: &TrackedBalances,
+		_t: &AccountingTransaction,
+		post: &TrackedBalances,
+	) -> bool {
+		issuance_invariant_holds(post)
+	}
+}
+
+#[cfg(feature = "std")]
+#[test]
+#[should_panic(expected = "conservation law violated")]
+fn sm_laws_catch_a_leaky_currency() {
+	state_machine_laws::<LeakyCurrency>(256);
+}
@@ -2,12 +2,22 @@
 //! We begin with a few simple examples, and then proceed to build bigger and more complex state
 //! machines all implementing the same simple interface.
 
-mod p1_switches;
+#[cfg(test)]
+mod laws;
+pub(crate) mod p1_switches;
 mod p2_laundry_machine;
 mod p3_atm;
+#[cfg(feature = "std")]
 mod p4_accounted_currency;
+#[cfg(feature = "std")]
 mod p5_digital_cash;
 mod p6_open_ended;
+#[cfg(feature = "std")]
+mod p7_commit_reveal;
+#[cfg(feature = "std")]
+mod profiler;
+
+use alloc::string::String;
 
 /// A state machine - Generic over the transition type
 pub trait StateMachine {
@@ -30,6 +40,7 @@ pub trait StateMachine {
 
 /// A set of play users for experimenting with the multi-user state machines
 #[derive(Hash, Eq, PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum User {
 	Alice,
 	Bob,
@@ -6,6 +6,7 @@ use super::StateMachine;
 
 /// This state machine models a single light switch.
 /// The internal state, a bool, represents whether the switch is on or not.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct LightSwitch;
 
 /// We model this simple system as a state machine with a single transition - toggling the switch
@@ -25,12 +26,14 @@ pub struct WeirdSwitchMachine;
 
 /// The state is now two switches instead of one so we use a struct.
 #[derive(PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TwoSwitches {
 	first_switch: bool,
 	second_switch: bool,
 }
 
 /// Now there are two switches so we need a proper type for the transition.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Toggle {
 	FirstSwitch,
 	SecondSwitch,
@@ -10,6 +10,7 @@ pub struct ClothesMachine;
 
 /// Models a piece of clothing throughout its lifecycle.
 #[derive(PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ClothesState {
 	/// Clean clothes ready to be worn. With some given life left.
 	Clean(u64),
@@ -24,6 +25,7 @@ pub enum ClothesState {
 }
 
 /// Something you can do with clothes
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ClothesAction {
 	/// Wearing clothes decreases their life by 1 and makes them dirty.
 	Wear,
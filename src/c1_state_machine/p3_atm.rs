@@ -3,9 +3,11 @@
 //! entered the wrong pin.
 
 use super::StateMachine;
+use alloc::vec::Vec;
 
 /// The keys on the ATM keypad
 #[derive(Hash, Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Key {
 	One,
 	Two,
@@ -15,6 +17,7 @@ pub enum Key {
 }
 
 /// Something you can do to the ATM
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Action {
 	/// Swipe your card at the ATM. The attached value is the hash of the pin
 	/// that should be keyed in on the keypad next.
@@ -25,6 +28,7 @@ pub enum Action {
 
 /// The various states of authentication possible with the ATM
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 enum Auth {
 	/// No session has begun yet. Waiting for the user to swipe their card
 	Waiting,
@@ -43,6 +47,7 @@ enum Auth {
 /// the ATM waits for you to key in an amount of money to withdraw. Withdraws
 /// are bounded only by the cash in the machine (there is no account balance).
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Atm {
 	/// How much money is in the ATM
 	cash_inside: u64,
@@ -24,6 +24,8 @@ pub struct AccountedCurrency;
 type Balances = HashMap<User, u64>;
 
 /// The state transitions that users can make in an accounted currency system
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AccountingTransaction {
 	/// Create some new money for the given minter in the given amount
 	Mint { minter: User, amount: u64 },
@@ -95,6 +97,66 @@ impl StateMachine for AccountedCurrency {
 	}
 }
 
+/// The same currency system, but with the total issuance tracked explicitly alongside the
+/// balances rather than recomputed by summing every account.
+///
+/// Real chains keep this number in state because it is needed constantly (inflation, fee
+/// burning, governance thresholds) and because comparing it against the balances is a cheap
+/// way to catch bugs that create or destroy money by accident.
+pub struct IssuanceTrackedCurrency;
+
+/// Balances together with the total amount of currency in existence.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TrackedBalances {
+	pub balances: Balances,
+	pub total_issuance: u64,
+}
+
+impl TrackedBalances {
+	pub fn new() -> Self {
+		TrackedBalances { balances: Balances::new(), total_issuance: 0 }
+	}
+}
+
+impl From<Balances> for TrackedBalances {
+	fn from(balances: Balances) -> Self {
+		let total_issuance = balances.values().sum();
+		TrackedBalances { balances, total_issuance }
+	}
+}
+
+/// Check that the tracked total issuance matches the sum of all balances.
+///
+/// There are no reserved balances in this machine, so the sum of the free balances is the
+/// whole story.
+pub fn issuance_invariant_holds(state: &TrackedBalances) -> bool {
+	state.balances.values().sum::<u64>() == state.total_issuance
+}
+
+impl StateMachine for IssuanceTrackedCurrency {
+	type State = TrackedBalances;
+	type Transition = AccountingTransaction;
+
+	fn next_state(starting_state: &TrackedBalances, t: &AccountingTransaction) -> TrackedBalances {
+		let balances = AccountedCurrency::next_state(&starting_state.balances, t);
+		let total_issuance = match t {
+			// Minting creates exactly the minted amount.
+			AccountingTransaction::Mint { amount, .. } => starting_state.total_issuance + amount,
+			// Burning destroys at most what the burner actually had.
+			AccountingTransaction::Burn { burner, amount } => {
+				let balance = starting_state.balances.get(burner).copied().unwrap_or(0);
+				starting_state.total_issuance - balance.min(*amount)
+			},
+			// Transfers only move money around.
+			AccountingTransaction::Transfer { .. } => starting_state.total_issuance,
+		};
+		let end = TrackedBalances { balances, total_issuance };
+		debug_assert!(issuance_invariant_holds(&end), "total issuance drifted from balances");
+		end
+	}
+}
+
 #[test]
 fn sm_4_mint_creates_account() {
 	let start = HashMap::new();
@@ -315,3 +377,54 @@ fn sm_4_transfer() {
 
 	assert_eq!(end, expected);
 }
+
+#[test]
+fn sm_4_issuance_from_balances() {
+	let state = TrackedBalances::from(HashMap::from([(User::Alice, 100), (User::Bob, 50)]));
+
+	assert_eq!(state.total_issuance, 150);
+	assert!(issuance_invariant_holds(&state));
+}
+
+#[test]
+fn sm_4_mint_increases_issuance() {
+	let start = TrackedBalances::new();
+	let end = IssuanceTrackedCurrency::next_state(
+		&start,
+		&AccountingTransaction::Mint { minter: User::Alice, amount: 100 },
+	);
+
+	assert_eq!(end.total_issuance, 100);
+}
+
+#[test]
+fn sm_4_burn_more_than_balance_only_burns_balance() {
+	let start = TrackedBalances::from(HashMap::from([(User::Alice, 100), (User::Bob, 50)]));
+	let end = IssuanceTrackedCurrency::next_state(
+		&start,
+		&AccountingTransaction::Burn { burner: User::Bob, amount: 80 },
+	);
+
+	assert_eq!(end.total_issuance, 100);
+	assert!(issuance_invariant_holds(&end));
+}
+
+#[test]
+fn sm_4_transfer_preserves_issuance() {
+	let start = TrackedBalances::from(HashMap::from([(User::Alice, 100), (User::Bob, 50)]));
+	let end = IssuanceTrackedCurrency::next_state(
+		&start,
+		&AccountingTransaction::Transfer { sender: User::Bob, receiver: User::Charlie, amount: 50 },
+	);
+
+	assert_eq!(end.total_issuance, 150);
+	assert!(issuance_invariant_holds(&end));
+}
+
+#[test]
+fn sm_4_drifted_issuance_is_detected() {
+	let mut state = TrackedBalances::from(HashMap::from([(User::Alice, 100)]));
+	state.total_issuance = 99;
+
+	assert!(!issuance_invariant_holds(&state));
+}
@@ -14,6 +14,7 @@ pub struct DigitalCashSystem;
 /// it and an amount that it is worth. It also has serial number to ensure that each bill
 /// is unique.
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bill {
 	owner: User,
 	amount: u64,
@@ -23,6 +24,7 @@ pub struct Bill {
 /// The State of a digital cash system. Primarily just the set of currently circulating bills.,
 /// but also a counter for the next serial number.
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct State {
 	/// The set of currently circulating bills
 	bills: HashSet<Bill>,
@@ -71,6 +73,7 @@ impl<const N: usize> From<[Bill; N]> for State {
 }
 
 /// The state transitions that users can make in a digital cash system
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CashTransaction {
 	/// Mint a single new bill owned by the minter
 	Mint { minter: User, amount: u64 },
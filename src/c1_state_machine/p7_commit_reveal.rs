@@ -0,0 +1,259 @@
+//! Blockchains are deterministic, which makes randomness surprisingly hard to come by on-chain.
+//! Anything the chain can compute, the block author can compute too, and the author gets to decide
+//! what goes into the block. Using something like the parent block hash as a random seed therefore
+//! lets the author re-roll the dice by simply trying different blocks.
+//!
+//! A classic improvement is a two phase commit-reveal protocol. In epoch N each participant commits
+//! to a secret by publishing only its hash. In epoch N+1 they reveal the secret, and everyone checks
+//! it against the commitment. The revealed secrets are combined to seed the randomness. Because the
+//! commitments are fixed before anyone reveals, nobody can pick their secret based on the others.
+//!
+//! The commitment hashes the participant together with the secret. Otherwise a participant could
+//! copy someone else's commitment without knowing the secret behind it, then repeat that secret
+//! once it is revealed. Secrets are combined with XOR, so the copy would cancel the original out.
+//!
+//! The protocol still has a well known weakness: the last participant to reveal already knows
+//! everybody else's secret, and may choose _not_ to reveal if they dislike the result. That is why
+//! non-revealers are punished. Punishment makes withholding expensive, but never impossible, which
+//! is one reason real chains combine commit-reveal with VRFs or move to threshold schemes.
+
+use super::{StateMachine, User};
+use crate::hash;
+use std::collections::HashMap;
+
+/// This state machine models a commit-reveal randomness beacon.
+pub struct CommitReveal;
+
+/// The user's commitment to a secret. Participants publish this in the commit phase.
+pub fn commitment(user: User, secret: u64) -> u64 {
+	hash(&(user, secret))
+}
+
+/// The state of the randomness beacon.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BeaconState {
+	/// The current epoch number.
+	pub epoch: u64,
+	/// Commitments made during the current epoch. They will be revealed next epoch.
+	pub commitments: HashMap<User, u64>,
+	/// Commitments made during the previous epoch that have not been revealed yet.
+	pub awaiting_reveal: HashMap<User, u64>,
+	/// The combination of all secrets revealed so far this epoch.
+	pub accumulator: u64,
+	/// The randomness produced at the end of the previous epoch.
+	pub randomness: u64,
+	/// How many times each user has failed to reveal a committed secret.
+	pub penalties: HashMap<User, u64>,
+}
+
+/// The actions participants (and the chain itself) can take.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BeaconAction {
+	/// Commit to a secret for the current epoch by publishing its hash. Each user may only commit
+	/// once per epoch.
+	Commit { user: User, commitment: u64 },
+	/// Reveal the secret committed to during the previous epoch.
+	Reveal { user: User, secret: u64 },
+	/// Close the current epoch. The revealed secrets become the new randomness, anyone who failed
+	/// to reveal is penalized, and this epoch's commitments move into the reveal phase.
+	EndEpoch,
+}
+
+impl StateMachine for CommitReveal {
+	type State = BeaconState;
+	type Transition = BeaconAction;
+
+	fn next_state(starting_state: &BeaconState, t: &BeaconAction) -> BeaconState {
+		let mut state = starting_state.clone();
+		match t {
+			BeaconAction::Commit { user, commitment } => {
+				state.commitments.entry(*user).or_insert(*commitment);
+			},
+			BeaconAction::Reveal { user, secret } => {
+				if state.awaiting_reveal.get(user) == Some(&commitment(*user, *secret)) {
+					state.awaiting_reveal.remove(user);
+					// XOR makes the result independent of the order of reveals.
+					state.accumulator ^= secret;
+				}
+			},
+			BeaconAction::EndEpoch => {
+				for user in state.awaiting_reveal.keys() {
+					*state.penalties.entry(*user).or_insert(0) += 1;
+				}
+				state.randomness = hash(&(state.epoch, state.accumulator));
+				state.awaiting_reveal = std::mem::take(&mut state.commitments);
+				state.accumulator = 0;
+				state.epoch += 1;
+			},
+		}
+		state
+	}
+
+	fn human_name() -> String {
+		"Commit-Reveal Randomness Beacon".into()
+	}
+}
+
+/// Run a sequence of actions from the given starting state.
+#[cfg(test)]
+fn run(start: &BeaconState, actions: &[BeaconAction]) -> BeaconState {
+	actions
+		.iter()
+		.fold(start.clone(), |state, action| CommitReveal::next_state(&state, action))
+}
+
+#[test]
+fn sm_7_commit_is_recorded() {
+	let end = CommitReveal::next_state(
+		&BeaconState::default(),
+		&BeaconAction::Commit { user: User::Alice, commitment: commitment(User::Alice, 42) },
+	);
+
+	assert_eq!(end.commitments, HashMap::from([(User::Alice, commitment(User::Alice, 42))]));
+}
+
+#[test]
+fn sm_7_cannot_change_commitment() {
+	let end = run(
+		&BeaconState::default(),
+		&[
+			BeaconAction::Commit { user: User::Alice, commitment: commitment(User::Alice, 42) },
+			BeaconAction::Commit { user: User::Alice, commitment: commitment(User::Alice, 43) },
+		],
+	);
+
+	assert_eq!(end.commitments, HashMap::from([(User::Alice, commitment(User::Alice, 42))]));
+}
+
+#[test]
+fn sm_7_cannot_reveal_in_same_epoch() {
+	let end = run(
+		&BeaconState::default(),
+		&[
+			BeaconAction::Commit { user: User::Alice, commitment: commitment(User::Alice, 42) },
+			BeaconAction::Reveal { user: User::Alice, secret: 42 },
+		],
+	);
+
+	assert_eq!(end.accumulator, 0);
+}
+
+#[test]
+fn sm_7_wrong_secret_is_ignored() {
+	let end = run(
+		&BeaconState::default(),
+		&[
+			BeaconAction::Commit { user: User::Alice, commitment: commitment(User::Alice, 42) },
+			BeaconAction::EndEpoch,
+			BeaconAction::Reveal { user: User::Alice, secret: 41 },
+		],
+	);
+
+	assert_eq!(end.accumulator, 0);
+	assert!(end.awaiting_reveal.contains_key(&User::Alice));
+}
+
+#[test]
+fn sm_7_reveals_seed_randomness() {
+	let end = run(
+		&BeaconState::default(),
+		&[
+			BeaconAction::Commit { user: User::Alice, commitment: commitment(User::Alice, 42) },
+			BeaconAction::Commit { user: User::Bob, commitment: commitment(User::Bob, 7) },
+			BeaconAction::EndEpoch,
+			BeaconAction::Reveal { user: User::Alice, secret: 42 },
+			BeaconAction::Reveal { user: User::Bob, secret: 7 },
+			BeaconAction::EndEpoch,
+		],
+	);
+
+	assert_eq!(end.epoch, 2);
+	assert_eq!(end.randomness, hash(&(1u64, 42u64 ^ 7)));
+	assert!(end.penalties.is_empty());
+}
+
+#[test]
+fn sm_7_reveal_order_does_not_matter() {
+	let start = run(
+		&BeaconState::default(),
+		&[
+			BeaconAction::Commit { user: User::Alice, commitment: commitment(User::Alice, 42) },
+			BeaconAction::Commit { user: User::Bob, commitment: commitment(User::Bob, 7) },
+			BeaconAction::EndEpoch,
+		],
+	);
+	let first = run(
+		&start,
+		&[
+			BeaconAction::Reveal { user: User::Alice, secret: 42 },
+			BeaconAction::Reveal { user: User::Bob, secret: 7 },
+		],
+	);
+	let second = run(
+		&start,
+		&[
+			BeaconAction::Reveal { user: User::Bob, secret: 7 },
+			BeaconAction::Reveal { user: User::Alice, secret: 42 },
+		],
+	);
+
+	assert_eq!(first, second);
+}
+
+#[test]
+fn sm_7_non_revealer_is_penalized() {
+	let end = run(
+		&BeaconState::default(),
+		&[
+			BeaconAction::Commit { user: User::Alice, commitment: commitment(User::Alice, 42) },
+			BeaconAction::Commit { user: User::Bob, commitment: commitment(User::Bob, 7) },
+			BeaconAction::EndEpoch,
+			BeaconAction::Reveal { user: User::Alice, secret: 42 },
+			BeaconAction::EndEpoch,
+		],
+	);
+
+	assert_eq!(end.penalties, HashMap::from([(User::Bob, 1)]));
+}
+
+#[test]
+fn sm_7_last_revealer_can_choose_between_two_outcomes() {
+	// This is the weakness described at the top of the module. Bob reveals last, so Bob can
+	// compute both possible outcomes and pick the preferred one, at the cost of a penalty.
+	let start = run(
+		&BeaconState::default(),
+		&[
+			BeaconAction::Commit { user: User::Alice, commitment: commitment(User::Alice, 42) },
+			BeaconAction::Commit { user: User::Bob, commitment: commitment(User::Bob, 7) },
+			BeaconAction::EndEpoch,
+			BeaconAction::Reveal { user: User::Alice, secret: 42 },
+		],
+	);
+	let revealed =
+		run(&start, &[BeaconAction::Reveal { user: User::Bob, secret: 7 }, BeaconAction::EndEpoch]);
+	let withheld = run(&start, &[BeaconAction::EndEpoch]);
+
+	assert_ne!(revealed.randomness, withheld.randomness);
+}
+
+#[test]
+fn sm_7_cannot_copy_another_commitment() {
+	// Bob copies Alice's commitment without knowing the secret behind it, and repeats the secret
+	// once Alice reveals it. The commitment is bound to Alice, so Bob's reveal does not match it.
+	let end = run(
+		&BeaconState::default(),
+		&[
+			BeaconAction::Commit { user: User::Alice, commitment: commitment(User::Alice, 42) },
+			BeaconAction::Commit { user: User::Bob, commitment: commitment(User::Alice, 42) },
+			BeaconAction::EndEpoch,
+			BeaconAction::Reveal { user: User::Alice, secret: 42 },
+			BeaconAction::Reveal { user: User::Bob, secret: 42 },
+			BeaconAction::EndEpoch,
+		],
+	);
+
+	assert_eq!(end.randomness, hash(&(1u64, 42u64)));
+	assert_eq!(end.penalties, HashMap::from([(User::Bob, 1)]));
+}
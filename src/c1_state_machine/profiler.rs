@@ -0,0 +1,174 @@
+//! Measure what transitions cost, to choose weights from data rather than by guessing.
+//!
+//! A real chain cannot let users submit transitions for free, or anyone could fill every block
+//! with expensive work. Each kind of transition is given a weight that says how much of a block's
+//! capacity it uses, and fees are charged by weight. Weights are usually derived by benchmarking:
+//! run many transitions, measure how long each kind takes and how much storage it adds, and pick
+//! a weight that covers the worst realistic case.
+//!
+//! This module does that for any state machine that says how to group its transitions and how big
+//! its state is. The suggestions follow Substrate's convention of measuring execution time in
+//! picoseconds, and report storage growth separately, because storage is paid for once per byte
+//! rather than once per execution.
+
+use super::{
+	p4_accounted_currency::{AccountedCurrency, AccountingTransaction},
+	StateMachine, User,
+};
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::fmt::Write;
+use std::{collections::HashMap, time::Instant};
+
+/// A state machine that can be profiled.
+pub trait Profile: StateMachine {
+	/// A short name for the kind of the transition, such as `"transfer"`. Transitions of the same
+	/// kind share a weight.
+	fn kind(t: &Self::Transition) -> &'static str;
+
+	/// How many bytes the state takes to store.
+	fn state_size(state: &Self::State) -> usize;
+}
+
+/// The measurements for one kind of transition.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KindProfile {
+	pub kind: &'static str,
+	/// How many transitions of this kind were in the corpus.
+	pub count: usize,
+	pub mean_nanos: f64,
+	/// The time 95% of these transitions stayed within.
+	pub p95_nanos: u64,
+	/// The average change in state size, which is negative if the kind tends to free storage.
+	pub mean_growth: f64,
+	/// The largest increase in state size any single transition of this kind caused.
+	pub max_growth: usize,
+}
+
+impl KindProfile {
+	/// The execution weight to charge, in picoseconds. It covers the 95th percentile time with a
+	/// 20% safety margin, because the benchmark machine is rarely the slowest one on the network.
+	pub fn suggested_weight(&self) -> u64 {
+		self.p95_nanos.max(1) * 1_000 * 6 / 5
+	}
+
+	/// The storage to charge for, in bytes, which is the worst growth seen.
+	pub fn suggested_storage(&self) -> usize {
+		self.max_growth
+	}
+}
+
+/// Run every transition of the corpus in order, starting from `initial`, and profile them grouped
+/// by kind. Each transition is timed `repetitions` times from the same pre-state and the fastest
+/// time kept, which filters out interruptions from the rest of the system.
+///
+/// The kinds are returned in alphabetical order.
+pub fn profile<M: Profile>(
+	initial: &M::State,
+	corpus: &[M::Transition],
+	repetitions: u32,
+) -> Vec<KindProfile> {
+	let mut samples: BTreeMap<&'static str, Vec<(u64, i64)>> = BTreeMap::new();
+	// `None` until the first transition has run, so that `initial` does not need to be cloned.
+	let mut state: Option<M::State> = None;
+	for t in corpus {
+		let pre = state.take();
+		let pre = pre.as_ref().unwrap_or(initial);
+		let mut fastest = u64::MAX;
+		let mut post = None;
+		for _ in 0..repetitions.max(1) {
+			let started = Instant::now();
+			let next = core::hint::black_box(M::next_state(core::hint::black_box(pre), t));
+			fastest = fastest.min(started.elapsed().as_nanos() as u64);
+			post = Some(next);
+		}
+		let post = post.expect("every transition runs at least once");
+		let growth = M::state_size(&post) as i64 - M::state_size(pre) as i64;
+		samples.entry(M::kind(t)).or_default().push((fastest, growth));
+		state = Some(post);
+	}
+
+	samples
+		.into_iter()
+		.map(|(kind, samples)| {
+			let mut times: Vec<u64> = samples.iter().map(|(time, _)| *time).collect();
+			times.sort();
+			let count = samples.len();
+			KindProfile {
+				kind,
+				count,
+				mean_nanos: times.iter().sum::<u64>() as f64 / count as f64,
+				p95_nanos: times[(count * 95).div_ceil(100) - 1],
+				mean_growth: samples.iter().map(|(_, growth)| *growth).sum::<i64>() as f64
+					/ count as f64,
+				max_growth: samples
+					.iter()
+					.map(|(_, growth)| (*growth).max(0) as usize)
+					.max()
+					.unwrap_or(0),
+			}
+		})
+		.collect()
+}
+
+/// The profiles as a table, one line per kind.
+pub fn report(profiles: &[KindProfile]) -> String {
+	let mut out =
+		String::from("kind         count  mean ns   p95 ns   growth B  weight ps  storage B\n");
+	for p in profiles {
+		writeln!(
+			out,
+			"{:<12} {:>5} {:>8.0} {:>8} {:>10.1} {:>10} {:>10}",
+			p.kind,
+			p.count,
+			p.mean_nanos,
+			p.p95_nanos,
+			p.mean_growth,
+			p.suggested_weight(),
+			p.suggested_storage()
+		)
+		.expect("writing to a String cannot fail");
+	}
+	out
+}
+
+impl Profile for AccountedCurrency {
+	fn kind(t: &AccountingTransaction) -> &'static str {
+		match t {
+			AccountingTransaction::Mint { .. } => "mint",
+			AccountingTransaction::Burn { .. } => "burn",
+			AccountingTransaction::Transfer { .. } => "transfer",
+		}
+	}
+
+	/// Every account stores a user and a balance.
+	fn state_size(state: &HashMap<User, u64>) -> usize {
+		state.len() * (core::mem::size_of::<User>() + core::mem::size_of::<u64>())
+	}
+}
+
+#[test]
+fn sm_profile_accounted_currency() {
+	use AccountingTransaction::*;
+	let corpus = [
+		Mint { minter: User::Alice, amount: 100 },
+		Mint { minter: User::Bob, amount: 50 },
+		Transfer { sender: User::Alice, receiver: User::Bob, amount: 10 },
+		Transfer { sender: User::Bob, receiver: User::Charlie, amount: 60 },
+		Burn { burner: User::Alice, amount: 90 },
+		Mint { minter: User::Alice, amount: 1 },
+	];
+	let profiles = profile::<AccountedCurrency>(&HashMap::new(), &corpus, 5);
+	let kinds: Vec<&str> = profiles.iter().map(|p| p.kind).collect();
+	let account = core::mem::size_of::<User>() + core::mem::size_of::<u64>();
+
+	assert_eq!(kinds, ["burn", "mint", "transfer"]);
+	assert_eq!(profiles.iter().map(|p| p.count).sum::<usize>(), corpus.len());
+	// Every mint here creates an account and the burn removes Alice's. The second transfer empties
+	// Bob's account but creates Charlie's, so neither transfer changes the size.
+	assert_eq!(profiles[1].max_growth, account);
+	assert_eq!(profiles[1].mean_growth, account as f64);
+	assert_eq!(profiles[0].mean_growth, -(account as f64));
+	assert_eq!(profiles[2].mean_growth, 0.0);
+	assert!(profiles.iter().all(|p| p.suggested_weight() >= 1_000));
+	assert_eq!(report(&profiles).lines().count(), 4);
+}
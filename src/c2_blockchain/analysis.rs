@@ -0,0 +1,176 @@
+//! Statistics about the forks in an exported block tree.
+//!
+//! The rows from `export` can describe more than a single chain. Export every block a node has
+//! seen, forks included, and this module answers the questions an assignment might ask about
+//! them. Which chain won? What fraction of the mining effort was wasted on blocks that lost? And
+//! how deep were the losing branches? A node that followed a losing branch of length `d` had to
+//! reorg `d` blocks when it switched, so the branch lengths are the reorg depths.
+//!
+//! The blocks of this chapter have no timestamps or authors, so block times and author fairness
+//! cannot be measured yet.
+
+use super::export::BlockRow;
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::fmt::Write;
+
+type Hash = u64;
+
+/// What `analyze` found.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ForkStatistics {
+	/// Every block in the export.
+	pub blocks: usize,
+	/// The hashes of the blocks on the chain with the most work, from its root to its tip.
+	pub canonical: Vec<Hash>,
+	/// Blocks that are not on the canonical chain.
+	pub stale_blocks: usize,
+	/// Blocks with more than one child.
+	pub fork_points: usize,
+	/// For each length, how many branches of that length leave the canonical chain.
+	pub branch_lengths: BTreeMap<u64, usize>,
+}
+
+impl ForkStatistics {
+	/// The fraction of all blocks that are stale.
+	pub fn stale_rate(&self) -> f64 {
+		self.stale_blocks as f64 / self.blocks.max(1) as f64
+	}
+
+	/// The deepest reorg a node following a losing branch would have had to make.
+	pub fn deepest_reorg(&self) -> u64 {
+		self.branch_lengths.keys().last().copied().unwrap_or(0)
+	}
+
+	/// The branch length distribution as CSV, one `length,count` line per length.
+	pub fn branch_lengths_csv(&self) -> String {
+		let mut out = String::from("length,count\n");
+		for (length, count) in &self.branch_lengths {
+			writeln!(out, "{length},{count}").expect("writing to a String cannot fail");
+		}
+		out
+	}
+}
+
+/// Link the rows into a tree through their parent hashes and measure its forks. The canonical
+/// chain is the one with the most work, as the heaviest chain rule would choose. Ties go to the
+/// block that comes first in `rows`.
+pub fn analyze(rows: &[BlockRow]) -> ForkStatistics {
+	let index: BTreeMap<Hash, usize> =
+		rows.iter().enumerate().map(|(i, row)| (row.hash, i)).collect();
+	let mut children = vec![Vec::new(); rows.len()];
+	let mut roots = Vec::new();
+	for (i, row) in rows.iter().enumerate() {
+		match index.get(&row.parent) {
+			Some(parent) if *parent != i => children[*parent].push(i),
+			_ => roots.push(i),
+		}
+	}
+
+	// Children are always higher than their parents, so visiting the highest blocks first
+	// computes every subtree before the block it hangs off.
+	let mut order: Vec<usize> = (0..rows.len()).collect();
+	order.sort_by_key(|i| core::cmp::Reverse(rows[*i].height));
+	let mut best_work = vec![0u128; rows.len()];
+	let mut longest = vec![0u64; rows.len()];
+	for &i in &order {
+		best_work[i] =
+			rows[i].work as u128 + children[i].iter().map(|c| best_work[*c]).max().unwrap_or(0);
+		longest[i] = 1 + children[i].iter().map(|c| longest[*c]).max().unwrap_or(0);
+	}
+
+	// Pick the first candidate with the most work. `max_by_key` would pick the last.
+	let heaviest = |candidates: &[usize]| {
+		candidates.iter().copied().fold(None, |best: Option<usize>, i| match best {
+			Some(b) if best_work[b] >= best_work[i] => Some(b),
+			_ => Some(i),
+		})
+	};
+	let mut canonical = Vec::new();
+	let mut branch_lengths = BTreeMap::new();
+	let mut next = heaviest(&roots);
+	while let Some(i) = next {
+		canonical.push(rows[i].hash);
+		next = heaviest(&children[i]);
+		for child in children[i].iter().filter(|c| Some(**c) != next) {
+			*branch_lengths.entry(longest[*child]).or_insert(0) += 1;
+		}
+	}
+
+	ForkStatistics {
+		blocks: rows.len(),
+		stale_blocks: rows.len() - canonical.len(),
+		fork_points: children.iter().filter(|c| c.len() > 1).count(),
+		canonical,
+		branch_lengths,
+	}
+}
+
+#[cfg(test)]
+use super::{
+	export::rows,
+	p4_batched_extrinsics::{Block, THRESHOLD},
+	p5_fork_choice::mine_extra_hard,
+};
+
+/// A child of the block, mined four times harder than usual. Its work is at least three quarters
+/// of the most any block can have, so the main chain wins whatever the branches' hashes are.
+#[cfg(test)]
+fn main_child(parent: &Block, extrinsics: Vec<u64>) -> Block {
+	let mut block = parent.child(extrinsics);
+	mine_extra_hard(&mut block.header, THRESHOLD / 4);
+	block
+}
+
+/// A main chain of four blocks after genesis, with a two block branch off block 1 and a one block
+/// branch off block 3.
+#[cfg(test)]
+fn build_tree() -> Vec<Block> {
+	let b0 = Block::genesis();
+	let b1 = b0.child(vec![1]);
+	let b2 = main_child(&b1, vec![2]);
+	let b3 = main_child(&b2, vec![3]);
+	let b4 = main_child(&b3, vec![4]);
+	let x2 = b1.child(vec![20]);
+	let x3 = x2.child(vec![30]);
+	let y4 = b3.child(vec![40]);
+	vec![b0, b1, b2, x2, b3, x3, b4, y4]
+}
+
+#[test]
+fn bc_analysis_finds_forks_and_branches() {
+	let mut blocks = build_tree();
+	// Without a block after b4, the one block branch y4 could still have more work.
+	let b5 = main_child(&blocks[6], vec![5]);
+	blocks.push(b5);
+	let stats = analyze(&rows(&blocks));
+
+	assert_eq!(stats.blocks, 9);
+	assert_eq!(stats.canonical.len(), 6);
+	assert_eq!(stats.stale_blocks, 3);
+	assert_eq!(stats.fork_points, 2);
+	assert_eq!(stats.branch_lengths, BTreeMap::from([(1, 1), (2, 1)]));
+	assert_eq!(stats.deepest_reorg(), 2);
+	assert!((stats.stale_rate() - 3.0 / 9.0).abs() < 1e-9);
+}
+
+#[test]
+fn bc_analysis_single_chain_has_no_forks() {
+	let blocks = build_tree();
+	let chain = [&blocks[0], &blocks[1], &blocks[2], &blocks[4], &blocks[6]].map(Clone::clone);
+	let stats = analyze(&rows(&chain));
+
+	assert_eq!(stats.stale_blocks, 0);
+	assert_eq!(stats.fork_points, 0);
+	assert!(stats.branch_lengths.is_empty());
+	assert_eq!(stats.deepest_reorg(), 0);
+}
+
+#[test]
+fn bc_analysis_csv() {
+	let mut blocks = build_tree();
+	let b5 = main_child(&blocks[6], vec![5]);
+	blocks.push(b5);
+	let csv = analyze(&rows(&blocks)).branch_lengths_csv();
+
+	assert_eq!(csv, "length,count\n1,1\n2,1\n");
+}
@@ -0,0 +1,73 @@
+//! Pick mining difficulty by time rather than by number.
+//!
+//! A threshold such as `u64::MAX / 5_000_000` means five million hashes on average, which is a
+//! second on one laptop and ten on another, and ten times more again in a debug build. Tests and
+//! benchmarks that mine should instead say how long they are willing to wait. This module measures
+//! how fast this machine hashes headers, once per process, and turns a time budget into the
+//! threshold that takes about that long to meet with a single thread.
+
+use super::p4_batched_extrinsics::Header;
+use crate::hash;
+use std::{
+	sync::OnceLock,
+	time::{Duration, Instant},
+};
+
+/// How long to spend measuring the hash rate.
+const MEASUREMENT: Duration = Duration::from_millis(50);
+
+/// The number of header hashes this machine computes per second on one thread, measured the first
+/// time it is asked for.
+pub(crate) fn hash_rate() -> u64 {
+	static RATE: OnceLock<u64> = OnceLock::new();
+	*RATE.get_or_init(|| {
+		let mut header = Header::genesis().child(0, 0);
+		let started = Instant::now();
+		let mut hashes: u64 = 0;
+		// Check the clock only every so often, so that reading it does not dominate the timing.
+		while started.elapsed() < MEASUREMENT {
+			for _ in 0..1_000 {
+				header.consensus_digest += 1;
+				core::hint::black_box(hash(&header));
+			}
+			hashes += 1_000;
+		}
+		(hashes as f64 / started.elapsed().as_secs_f64()) as u64
+	})
+}
+
+/// The threshold at which mining a header with one thread takes about `budget` on average.
+///
+/// Each attempt succeeds with probability `threshold / u64::MAX`, so mining takes
+/// `u64::MAX / threshold` attempts on average. This is an average: any single block may take much
+/// longer or shorter.
+pub(crate) fn threshold_for(budget: Duration) -> u64 {
+	let attempts = (hash_rate() as f64 * budget.as_secs_f64()).max(1.0);
+	(u64::MAX as f64 / attempts) as u64
+}
+
+#[test]
+fn bc_calibration_measures_a_hash_rate() {
+	assert!(hash_rate() > 0);
+	assert_eq!(hash_rate(), hash_rate());
+}
+
+#[test]
+fn bc_calibration_longer_budgets_are_harder() {
+	let easy = threshold_for(Duration::from_millis(1));
+	let hard = threshold_for(Duration::from_secs(1));
+
+	assert!(hard < easy);
+	assert_eq!(threshold_for(Duration::ZERO), u64::MAX);
+}
+
+#[test]
+fn bc_calibration_threshold_can_be_met() {
+	let threshold = threshold_for(Duration::from_millis(20));
+	let mut header = Header::genesis().child(1, 1);
+	while hash(&header) > threshold {
+		header.consensus_digest += 1;
+	}
+
+	assert!(hash(&header) <= threshold);
+}
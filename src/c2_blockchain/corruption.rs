@@ -0,0 +1,218 @@
+//! Break a valid chain on purpose.
+//!
+//! A good way to check that you understand block validation is to be handed a chain and asked
+//! which block is invalid. This module builds such exercises. It takes a valid chain of batched
+//! extrinsic blocks from part 4 and produces variants where exactly one block has been tampered
+//! with, together with an answer key saying which block it was and what was done to it.
+//!
+//! The corruptions are meant to be subtle. Every corrupted block is re-mined so that its proof of
+//! work still looks fine (except when the seal itself is the problem), and every block after it is
+//! rebuilt on top of it, so the chain only breaks in one place.
+
+use super::p4_batched_extrinsics::{Block, Header, THRESHOLD};
+use crate::{crypto::merkle::merkle_root, hash, rng::DeterministicRng};
+use alloc::{string::String, vec::Vec};
+use core::fmt::Write;
+
+/// The ways a block can be broken.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Corruption {
+	/// The parent hash has one bit flipped.
+	WrongParent,
+	/// The height is one more than it should be.
+	HeightOffByOne,
+	/// An extrinsic in the body was changed and the extrinsics root recomputed to match, but the
+	/// state was not.
+	TamperedBody,
+	/// The seal was copied from another block instead of being mined for this one.
+	ReusedSeal,
+}
+
+impl Corruption {
+	/// Every kind of corruption.
+	pub const ALL: [Corruption; 4] = [
+		Corruption::WrongParent,
+		Corruption::HeightOffByOne,
+		Corruption::TamperedBody,
+		Corruption::ReusedSeal,
+	];
+
+	/// A short explanation for the answer key.
+	pub fn describe(self) -> &'static str {
+		match self {
+			Corruption::WrongParent => "the parent hash does not match the previous block",
+			Corruption::HeightOffByOne => "the height is off by one",
+			Corruption::TamperedBody => "the body was changed, so executing it gives another state",
+			Corruption::ReusedSeal => {
+				"the seal was reused from another block and is not valid work"
+			},
+		}
+	}
+}
+
+/// One "find the invalid block" exercise.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Exercise {
+	/// The chain to hand out, starting with genesis.
+	pub chain: Vec<Block>,
+	/// The answer: the index in `chain` of the only invalid block.
+	pub invalid_block: usize,
+	/// The answer: what is wrong with it.
+	pub corruption: Corruption,
+}
+
+/// Search for a consensus digest that meets the threshold, starting from zero.
+fn reseal(header: &mut Header) {
+	header.consensus_digest = 0;
+	while hash(header) > THRESHOLD {
+		header.consensus_digest += 1;
+	}
+}
+
+/// Return a copy of the chain with the block at `index` corrupted, or `None` if the chain is too
+/// short, `index` is the genesis block, or the chain offers no seal that would be invalid here.
+///
+/// `chain` must start with genesis.
+pub fn corrupt(chain: &[Block], index: usize, corruption: Corruption) -> Option<Vec<Block>> {
+	if index == 0 || index >= chain.len() {
+		return None;
+	}
+	let mut chain = chain.to_vec();
+	let block = &mut chain[index];
+	match corruption {
+		Corruption::WrongParent => {
+			block.header.parent ^= 1;
+			reseal(&mut block.header);
+		},
+		Corruption::HeightOffByOne => {
+			block.header.height += 1;
+			reseal(&mut block.header);
+		},
+		Corruption::TamperedBody => {
+			match block.body.first_mut() {
+				Some(extrinsic) => *extrinsic = extrinsic.wrapping_add(1),
+				None => block.body.push(1),
+			}
+			block.header.extrinsics_root = merkle_root(&block.body);
+			reseal(&mut block.header);
+		},
+		Corruption::ReusedSeal => {
+			let mut header = block.header.clone();
+			let donor = chain.iter().map(|donor| donor.header.consensus_digest).find(|seal| {
+				header.consensus_digest = *seal;
+				hash(&header) > THRESHOLD
+			})?;
+			chain[index].header.consensus_digest = donor;
+		},
+	}
+
+	// Rebuild everything after the corrupted block on top of it, keeping the same contents.
+	for i in index + 1..chain.len() {
+		let header = &chain[i].header;
+		chain[i].header = chain[i - 1].header.child(header.extrinsics_root, header.state);
+	}
+	Some(chain)
+}
+
+/// Generate one exercise for each of the given corruptions. Which block is corrupted is picked
+/// from the seed, so the same seed always gives the same exercises.
+///
+/// Corruptions that cannot be applied to this chain (see `corrupt`) are skipped.
+pub fn generate(chain: &[Block], corruptions: &[Corruption], seed: u64) -> Vec<Exercise> {
+	if chain.len() < 2 {
+		return Vec::new();
+	}
+	let mut rng = DeterministicRng::new(seed);
+	corruptions
+		.iter()
+		.filter_map(|corruption| {
+			let invalid_block = 1 + rng.below(chain.len() as u64 - 1) as usize;
+			let chain = corrupt(chain, invalid_block, *corruption)?;
+			Some(Exercise { chain, invalid_block, corruption: *corruption })
+		})
+		.collect()
+}
+
+/// The answer key for a set of exercises, one line each, numbered from one.
+pub fn answer_key(exercises: &[Exercise]) -> String {
+	let mut key = String::new();
+	for (i, exercise) in exercises.iter().enumerate() {
+		let height = exercise.chain[exercise.invalid_block].header.height;
+		writeln!(key, "{}. block #{height}: {}", i + 1, exercise.corruption.describe())
+			.expect("writing to a String cannot fail");
+	}
+	key
+}
+
+/// The index of the first block that is not valid, checking the links, the state, and the work.
+#[cfg(test)]
+fn first_invalid_block(chain: &[Block]) -> Option<usize> {
+	(1..chain.len()).find(|i| {
+		let (parent, child) = (&chain[i - 1], &chain[*i]);
+		let state = child.body.iter().fold(parent.header.state, |state, ext| state + ext);
+		child.header.parent != hash(&parent.header)
+			|| child.header.height != parent.header.height + 1
+			|| child.header.extrinsics_root != merkle_root(&child.body)
+			|| child.header.state != state
+			|| hash(&child.header) > THRESHOLD
+	})
+}
+
+#[cfg(test)]
+fn build_chain() -> Vec<Block> {
+	let mut chain = vec![Block::genesis()];
+	for extrinsics in [vec![1, 2], vec![], vec![3], vec![4, 5, 6], vec![7]] {
+		let child = chain.last().unwrap().child(extrinsics);
+		chain.push(child);
+	}
+	chain
+}
+
+#[test]
+fn bc_corrupt_each_kind_breaks_exactly_one_block() {
+	let chain = build_chain();
+	assert_eq!(first_invalid_block(&chain), None);
+
+	for corruption in Corruption::ALL {
+		for index in 1..chain.len() {
+			let corrupted = corrupt(&chain, index, corruption).unwrap();
+			assert_eq!(corrupted.len(), chain.len());
+			assert_eq!(first_invalid_block(&corrupted), Some(index), "{corruption:?} at {index}");
+			assert_eq!(first_invalid_block(&corrupted[index..]), None, "{corruption:?} at {index}");
+		}
+	}
+}
+
+#[test]
+fn bc_corrupt_tampered_body_passes_header_checks() {
+	let chain = build_chain();
+	let corrupted = corrupt(&chain, 2, Corruption::TamperedBody).unwrap();
+
+	assert_eq!(corrupted[2].body, vec![1]);
+	assert_eq!(corrupted[2].header.extrinsics_root, merkle_root(&corrupted[2].body));
+	assert_eq!(corrupted[2].header.state, chain[2].header.state);
+	assert!(!chain[0].verify_sub_chain(&corrupted[1..]));
+}
+
+#[test]
+fn bc_corrupt_rejects_genesis_and_out_of_range() {
+	let chain = build_chain();
+
+	assert_eq!(corrupt(&chain, 0, Corruption::WrongParent), None);
+	assert_eq!(corrupt(&chain, chain.len(), Corruption::WrongParent), None);
+	assert!(generate(&chain[..1], &Corruption::ALL, 0).is_empty());
+}
+
+#[test]
+fn bc_corrupt_generate_is_reproducible() {
+	let chain = build_chain();
+	let exercises = generate(&chain, &Corruption::ALL, 42);
+
+	assert_eq!(exercises.len(), 4);
+	assert_eq!(exercises, generate(&chain, &Corruption::ALL, 42));
+	for exercise in &exercises {
+		assert_eq!(first_invalid_block(&exercise.chain), Some(exercise.invalid_block));
+	}
+	assert_eq!(answer_key(&exercises).lines().count(), 4);
+	assert!(answer_key(&exercises).starts_with("1. block #"));
+}
@@ -0,0 +1,203 @@
+//! Double spends, and why merchants wait for confirmations.
+//!
+//! An attacker pays a merchant in a block on the public chain, while secretly mining a competing
+//! branch from the block before it, where the same coins go back to the attacker. The merchant
+//! waits until the payment is `depth` blocks deep before handing over the goods. The attacker then
+//! keeps mining, and if its branch ever becomes longer than the public one, it publishes it. Every
+//! node switches to the longer branch, the payment disappears, and the reorg was `depth` blocks or
+//! more deep.
+//!
+//! With less than half of the hash power the attacker falls behind on average, so the chance that
+//! it ever catches up shrinks exponentially with the depth. That is where the familiar advice to
+//! wait for six confirmations comes from. With more than half, the attacker always wins eventually,
+//! which is the 51% attack.
+//!
+//! This module measures the success probability by running the race many times with seeded
+//! randomness, using real part 4 headers and the part 5 longest chain rule, and compares it with
+//! the exact probability. From the exact probability it also estimates how likely each block of a
+//! chain is to be reverted, and how many confirmations make a payment safe enough.
+//!
+//! The analysis counts blocks, so it applies to the heaviest chain rule as well as the longest, as
+//! long as the difficulty stays the same during the attack.
+
+use super::{
+	p4_batched_extrinsics::Header,
+	p5_fork_choice::{ForkChoice, LongestChainRule},
+};
+use crate::rng::DeterministicRng;
+use alloc::vec::Vec;
+
+/// Once the attacker is this many blocks behind, it gives up. Catching up from here is so unlikely
+/// for any attacker with less than half the hash power that it makes no measurable difference.
+const GIVE_UP_DEFICIT: usize = 20;
+
+/// Run one attack, returning whether the attacker's branch replaced the payment.
+///
+/// `attacker_share` is the attacker's fraction of the hash power, and `depth` the number of blocks
+/// the merchant waits for, counting the one with the payment.
+pub fn attack_succeeds(attacker_share: f64, depth: u32, rng: &mut DeterministicRng) -> bool {
+	// With all of the hash power the honest branch never grows, so the race below would never
+	// end. The attacker's branch is the longest from its first block on.
+	if attacker_share >= 1.0 {
+		return true;
+	}
+	let fork_point = Header::genesis();
+	// The payment goes in the first block on the honest branch, and the conflicting transaction
+	// in the first block on the attacker's branch. Which is which is recorded in the extrinsics
+	// root. The attacker starts mining as soon as it sends the payment.
+	let mut honest: Vec<Header> = Vec::new();
+	let mut attacker: Vec<Header> = Vec::new();
+
+	// Honest miners find each block unless the attacker does. Both keep going until the merchant
+	// is satisfied, and then until the attacker is either ahead or hopelessly behind.
+	loop {
+		if rng.chance(attacker_share) {
+			let parent = attacker.last().unwrap_or(&fork_point);
+			attacker.push(parent.child(1, 0));
+		} else {
+			let parent = honest.last().unwrap_or(&fork_point);
+			honest.push(parent.child(0, 0));
+		}
+
+		if honest.len() < depth as usize {
+			continue;
+		}
+		if !LongestChainRule::first_chain_is_better(&honest, &attacker) {
+			return true;
+		}
+		if honest.len() >= attacker.len() + GIVE_UP_DEFICIT {
+			return false;
+		}
+	}
+}
+
+/// The fraction of `runs` seeded attacks that succeed.
+pub fn success_rate(attacker_share: f64, depth: u32, runs: u32, seed: u64) -> f64 {
+	let root = DeterministicRng::new(seed);
+	let successes = (0..runs)
+		.filter(|run| attack_succeeds(attacker_share, depth, &mut root.fork(&format!("run {run}"))))
+		.count();
+	successes as f64 / runs.max(1) as f64
+}
+
+/// Measured success rates for every combination of attacker share and depth. Row `i` is for
+/// `attacker_shares[i]`, and column `j` for `depths[j]`.
+pub fn success_table(
+	attacker_shares: &[f64],
+	depths: &[u32],
+	runs: u32,
+	seed: u64,
+) -> Vec<Vec<f64>> {
+	attacker_shares
+		.iter()
+		.map(|share| depths.iter().map(|depth| success_rate(*share, *depth, runs, seed)).collect())
+		.collect()
+}
+
+/// The exact probability that the attack succeeds, ignoring the give up rule.
+///
+/// While the honest miners find `depth` blocks, the attacker finds `k` with the negative binomial
+/// probability `C(k + depth - 1, k) p^depth q^k`. From there it must gain `depth - k + 1` blocks
+/// on the honest miners, which by the gambler's ruin happens with probability
+/// `(q / p)^(depth - k + 1)`, or certainly if `q >= p`.
+pub fn exact_success_probability(attacker_share: f64, depth: u32) -> f64 {
+	let (q, p) = (attacker_share, 1.0 - attacker_share);
+	if q >= p {
+		return 1.0;
+	}
+	let power = |base: f64, exponent: u32| (0..exponent).fold(1.0, |acc, _| acc * base);
+	// The probability of failing: the attacker has at most `depth` blocks when the merchant is
+	// satisfied, and then never gets ahead.
+	let mut failure = 0.0;
+	// `C(k + depth - 1, k) p^depth q^k`, starting at k = 0. Each term is built from the last one,
+	// because for deep blocks the binomial coefficient alone overflows.
+	let mut reached = power(p, depth);
+	for k in 0..=depth {
+		if k > 0 {
+			reached *= (k + depth - 1) as f64 / k as f64 * q;
+		}
+		failure += reached * (1.0 - power(q / p, depth - k + 1));
+	}
+	1.0 - failure
+}
+
+/// The deepest block `confirmations_needed` considers. Beyond it, `p^depth` gets too close to the
+/// smallest `f64` for the exact probability to be trusted.
+pub const MAX_CONFIRMATIONS: u32 = 1_000;
+
+/// The probability that an attacker with `attacker_share` of the hash power reverts each block of
+/// the chain, from the first block to the tip. A block's depth counts itself and every block after
+/// it, so the tip has one confirmation and is the most likely to be reverted.
+pub fn reversal_probabilities(chain: &[Header], attacker_share: f64) -> Vec<f64> {
+	(1..=chain.len() as u32)
+		.rev()
+		.map(|depth| exact_success_probability(attacker_share, depth))
+		.collect()
+}
+
+/// The fewest confirmations after which an attacker with `attacker_share` of the hash power reverts
+/// a block with probability at most `risk`. Returns `None` if no depth up to `MAX_CONFIRMATIONS`
+/// is enough, which is always the case for an attacker with half of the hash power or more.
+pub fn confirmations_needed(attacker_share: f64, risk: f64) -> Option<u32> {
+	(1..=MAX_CONFIRMATIONS).find(|depth| exact_success_probability(attacker_share, *depth) <= risk)
+}
+
+#[test]
+fn bc_double_spend_is_reproducible() {
+	assert_eq!(success_rate(0.3, 2, 50, 4), success_rate(0.3, 2, 50, 4));
+}
+
+#[test]
+fn bc_double_spend_matches_the_exact_probability() {
+	for (share, depth) in [(0.1, 1), (0.3, 2), (0.3, 4)] {
+		let measured = success_rate(share, depth, 300, 5);
+		let exact = exact_success_probability(share, depth);
+		assert!((measured - exact).abs() < 0.07, "{share} at {depth}: {measured} vs {exact}");
+	}
+}
+
+#[test]
+fn bc_double_spend_deeper_is_safer() {
+	let table = success_table(&[0.1, 0.3], &[1, 3, 6], 200, 6);
+
+	for row in &table {
+		assert!(row[0] >= row[1] && row[1] >= row[2], "{row:?}");
+	}
+	assert!(table[0][2] < 0.01);
+	assert!(table[1][0] > table[0][0]);
+}
+
+#[test]
+fn bc_double_spend_majority_always_wins() {
+	assert_eq!(success_rate(0.6, 6, 50, 7), 1.0);
+	assert_eq!(success_rate(1.0, 6, 50, 7), 1.0);
+	assert_eq!(exact_success_probability(0.5, 6), 1.0);
+	assert_eq!(exact_success_probability(1.0, 6), 1.0);
+}
+
+#[test]
+fn bc_double_spend_confirmations_needed() {
+	assert_eq!(confirmations_needed(0.1, 0.001), Some(4));
+	assert_eq!(confirmations_needed(0.3, 0.001), Some(29));
+	assert_eq!(confirmations_needed(0.0, 0.0), Some(1));
+	assert_eq!(confirmations_needed(0.5, 0.5), None);
+	// Close to half of the hash power, hundreds of blocks are needed. The exact probability has to
+	// stay finite that deep.
+	assert_eq!(confirmations_needed(0.45, 0.001), Some(530));
+	assert!(exact_success_probability(0.45, MAX_CONFIRMATIONS).is_finite());
+}
+
+#[test]
+fn bc_double_spend_reversal_by_depth() {
+	let mut chain = vec![Header::genesis()];
+	for i in 0..6 {
+		let next = chain[i].child(0, 0);
+		chain.push(next);
+	}
+	let probabilities = reversal_probabilities(&chain, 0.1);
+
+	assert_eq!(probabilities.len(), 7);
+	assert_eq!(probabilities[6], exact_success_probability(0.1, 1));
+	assert!(probabilities.windows(2).all(|pair| pair[0] < pair[1]));
+	assert!(probabilities[0] < 0.001);
+}
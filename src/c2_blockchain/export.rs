@@ -0,0 +1,181 @@
+//! Export chains for analysis outside of Rust.
+//!
+//! Questions like "how long are the forks we see?" or "how is the work spread across blocks?" are
+//! much easier to answer in a spreadsheet or a notebook than in a unit test. This module turns a
+//! chain of blocks into one row per block, and writes the rows as CSV or as JSON lines (one JSON
+//! object per line), both of which load directly into pandas and friends.
+//!
+//! The rows only contain what the blocks of this chapter actually record. There is no author,
+//! timestamp, or fee in these blocks yet, so there are no columns for them either.
+//!
+//! Hashes are written in hex, as JSON strings. Many JSON parsers, including the one in pandas, read
+//! numbers as doubles, which would round a 64 bit hash to a different one. The work is a number
+//! too large for a double as well, but rounding it only changes the last digits of a sum.
+
+use super::p4_batched_extrinsics::{Block, THRESHOLD};
+use crate::{hash, hex::HexHash};
+use alloc::{string::String, vec::Vec};
+use core::fmt::Write;
+
+type Hash = u64;
+
+/// The columns, in the order they are written.
+const COLUMNS: [&str; 6] = ["height", "hash", "parent", "work", "extrinsic_count", "state"];
+
+/// Everything we export about a single block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockRow {
+	pub height: u64,
+	pub hash: Hash,
+	pub parent: Hash,
+	/// The work the block contributes to its chain, using the same `THRESHOLD - hash` formula as
+	/// the heaviest chain rule.
+	pub work: u64,
+	pub extrinsic_count: usize,
+	pub state: u64,
+}
+
+impl BlockRow {
+	/// Summarize a single block.
+	pub fn new(block: &Block) -> Self {
+		let header = &block.header;
+		let hash = hash(header);
+		BlockRow {
+			height: header.height,
+			hash,
+			parent: header.parent,
+			work: THRESHOLD.saturating_sub(hash),
+			extrinsic_count: block.body.len(),
+			state: header.state,
+		}
+	}
+
+	/// The values in the same order as `COLUMNS`.
+	fn values(&self) -> [Value; 6] {
+		[
+			Value::Number(self.height),
+			Value::Hash(self.hash),
+			Value::Hash(self.parent),
+			Value::Number(self.work),
+			Value::Number(self.extrinsic_count as u64),
+			Value::Number(self.state),
+		]
+	}
+}
+
+/// A single value in a row.
+enum Value {
+	Number(u64),
+	Hash(Hash),
+}
+
+impl Value {
+	/// The value as a CSV field.
+	fn csv(&self) -> String {
+		match self {
+			Value::Number(number) => format!("{number}"),
+			Value::Hash(hash) => format!("{}", HexHash(*hash)),
+		}
+	}
+
+	/// The value as a JSON number, or a string for hashes.
+	fn json(&self) -> String {
+		match self {
+			Value::Number(number) => format!("{number}"),
+			Value::Hash(hash) => format!("\"{}\"", HexHash(*hash)),
+		}
+	}
+}
+
+/// One row for each block in the chain, in chain order.
+pub fn rows(chain: &[Block]) -> Vec<BlockRow> {
+	chain.iter().map(BlockRow::new).collect()
+}
+
+/// Write the chain as CSV, with a header line naming the columns.
+pub fn to_csv(chain: &[Block]) -> String {
+	let mut out = COLUMNS.join(",");
+	out.push('\n');
+	for row in rows(chain) {
+		let values: Vec<String> = row.values().iter().map(Value::csv).collect();
+		out.push_str(&values.join(","));
+		out.push('\n');
+	}
+	out
+}
+
+/// Write the chain as JSON lines. Every value is an integer or a hex hash, so no escaping is
+/// needed.
+pub fn to_json_lines(chain: &[Block]) -> String {
+	let mut out = String::new();
+	for row in rows(chain) {
+		let fields: Vec<String> = COLUMNS
+			.iter()
+			.zip(row.values())
+			.map(|(column, value)| format!("\"{column}\":{}", value.json()))
+			.collect();
+		writeln!(out, "{{{}}}", fields.join(",")).expect("writing to a String cannot fail");
+	}
+	out
+}
+
+#[cfg(test)]
+fn build_chain() -> Vec<Block> {
+	let b0 = Block::genesis();
+	let b1 = b0.child(vec![1, 2, 3]);
+	let b2 = b1.child(vec![]);
+	vec![b0, b1, b2]
+}
+
+#[test]
+fn bc_export_rows_follow_the_chain() {
+	let chain = build_chain();
+	let rows = rows(&chain);
+
+	assert_eq!(rows.len(), 3);
+	assert_eq!(rows[1].height, 1);
+	assert_eq!(rows[1].parent, rows[0].hash);
+	assert_eq!(rows[1].extrinsic_count, 3);
+	assert_eq!(rows[1].state, 6);
+	assert_eq!(rows[2].extrinsic_count, 0);
+}
+
+#[test]
+fn bc_export_mined_blocks_have_work() {
+	let rows = rows(&build_chain());
+
+	assert!(rows[1..].iter().all(|row| row.work > 0));
+}
+
+#[test]
+fn bc_export_csv() {
+	let chain = build_chain();
+	let csv = to_csv(&chain);
+	let lines: Vec<&str> = csv.lines().collect();
+	let row = BlockRow::new(&chain[1]);
+
+	assert_eq!(lines.len(), 4);
+	assert_eq!(lines[0], "height,hash,parent,work,extrinsic_count,state");
+	assert_eq!(
+		lines[2],
+		format!("1,{},{},{},3,6", HexHash(row.hash), HexHash(row.parent), row.work)
+	);
+}
+
+#[test]
+fn bc_export_json_lines() {
+	let chain = build_chain();
+	let json = to_json_lines(&chain);
+	let row = BlockRow::new(&chain[0]);
+
+	assert_eq!(json.lines().count(), 3);
+	assert_eq!(
+		json.lines().next().unwrap(),
+		format!(
+			"{{\"height\":0,\"hash\":\"{}\",\"parent\":\"0x0000000000000000\",\"work\":{},\
+			 \"extrinsic_count\":0,\"state\":0}}",
+			HexHash(row.hash),
+			row.work
+		)
+	);
+}
@@ -0,0 +1,216 @@
+//! Laws that every fork choice rule should obey, checked on random fork trees.
+//!
+//! The fork choice tests in part 5 build a few forks by hand. This module instead generates random
+//! block trees, with random branch points, branch lengths, and blocks that were mined extra hard,
+//! and checks that a rule behaves sensibly on all of them. When a law fails, proptest shrinks the
+//! tree to the smallest one it can find.
+//!
+//! To check a rule, call `fork_choice_laws` from a test. To write property tests of your own, use
+//! `fork_trees` to generate the candidates. The laws are:
+//! 1. Membership. `best_chain` returns one of the candidates, not some other chain.
+//! 2. No better candidate. No candidate beats the chain `best_chain` returned.
+//! 3. Antisymmetry. If the rule answers the same whichever chain comes first, the chains are tied,
+//!    so their scores are equal. Otherwise the chain it prefers does not score lower.
+//!
+//! Some laws only make sense for some rules. The longest chain rule, for example, never prefers a
+//! strict prefix of a chain to the chain itself, which is checked separately below.
+
+use super::{
+	p4_batched_extrinsics::{Header, THRESHOLD},
+	p5_fork_choice::{
+		ForkChoice, ForkChoiceError, HeaviestChainRule, Lexicographic, LongestChain,
+		LongestChainRule, LowestTipHash, MostBlocksWithEvenHash, MostWork,
+	},
+};
+use crate::hash;
+use alloc::vec::Vec;
+use proptest::{
+	prelude::*,
+	sample::Index,
+	test_runner::{Config, TestRunner},
+};
+
+/// A random block tree, as the chains to each of its tips. Either every chain starts at genesis, or
+/// every chain starts just after it. Genesis was never mined, so this checks that rules cope with
+/// a header that has no work as well as with chains of mined headers only.
+#[derive(Clone, Debug)]
+pub struct ForkTree {
+	pub chains: Vec<Vec<Header>>,
+}
+
+impl ForkTree {
+	/// The chains as the candidates `best_chain` takes.
+	pub fn candidates(&self) -> Vec<&[Header]> {
+		self.chains.iter().map(Vec::as_slice).collect()
+	}
+}
+
+/// Mine the header until its hash is below `threshold`.
+fn mine(header: &mut Header, threshold: u64) {
+	while hash(header) > threshold {
+		header.consensus_digest += 1;
+	}
+}
+
+/// Generate fork trees. Each tree has a trunk of one to seven blocks and up to four branches. A
+/// branch leaves from any block already in the tree, including blocks of earlier branches, and
+/// each branch is either mined normally or twenty times harder.
+pub fn fork_trees() -> BoxedStrategy<ForkTree> {
+	let branches = proptest::collection::vec((any::<Index>(), 1..6usize, any::<bool>()), 0..=4);
+	(1..8usize, branches, any::<u64>(), any::<bool>())
+		.prop_map(|(trunk, branches, salt, from_genesis)| {
+			let genesis = Header::genesis();
+			// Every block so far, with the index of the block before it in `blocks`, if any.
+			let mut blocks: Vec<(Header, Option<usize>)> = Vec::new();
+			// Add a branch of `length` blocks after `from`, returning the index of its tip.
+			let grow = |blocks: &mut Vec<(Header, Option<usize>)>,
+			            from: Option<usize>,
+			            length: usize,
+			            hard: bool| {
+				let mut parent = from;
+				for i in 0..length {
+					let parent_header = parent.map_or(&genesis, |p| &blocks[p].0);
+					let mut header = parent_header.child(salt ^ blocks.len() as u64, i as u64);
+					if hard {
+						mine(&mut header, THRESHOLD / 20);
+					}
+					blocks.push((header, parent));
+					parent = Some(blocks.len() - 1);
+				}
+				blocks.len() - 1
+			};
+			let mut tips = vec![grow(&mut blocks, None, trunk, false)];
+			for (branch_point, length, hard) in branches {
+				// Index `blocks.len()` stands for genesis, so branches can leave from it too.
+				let point = branch_point.index(blocks.len() + 1);
+				let from = (point < blocks.len()).then_some(point);
+				tips.push(grow(&mut blocks, from, length, hard));
+			}
+
+			let chains = tips
+				.into_iter()
+				.map(|tip| {
+					let mut chain = Vec::new();
+					let mut next = Some(tip);
+					while let Some(i) = next {
+						chain.push(blocks[i].0.clone());
+						next = blocks[i].1;
+					}
+					if from_genesis {
+						chain.push(genesis.clone());
+					}
+					chain.reverse();
+					chain
+				})
+				.collect();
+			ForkTree { chains }
+		})
+		.boxed()
+}
+
+/// Check every law on `cases` random fork trees, panicking with the smallest failing tree if one
+/// does not hold.
+pub fn fork_choice_laws<R: ForkChoice>(cases: u32) {
+	let mut runner = TestRunner::new(Config::with_cases(cases));
+	let result = runner.run(&fork_trees(), |tree| {
+		let candidates = tree.candidates();
+		let best = R::best_chain(&candidates)
+			.map_err(|error| TestCaseError::fail(format!("best_chain failed with {error:?}")))?;
+		prop_assert!(
+			candidates.iter().any(|candidate| core::ptr::eq(*candidate, best)),
+			"best_chain returned a chain that is not one of the candidates"
+		);
+		for candidate in &candidates {
+			let strictly_better = R::first_chain_is_better(candidate, best)
+				&& !R::first_chain_is_better(best, candidate);
+			prop_assert!(!strictly_better, "a candidate beats the chain best_chain returned");
+		}
+		for a in &candidates {
+			for b in &candidates {
+				let (a_wins, b_wins) =
+					(R::first_chain_is_better(a, b), R::first_chain_is_better(b, a));
+				if a_wins == b_wins {
+					prop_assert_eq!(R::score(a), R::score(b), "a tie between different scores");
+				} else if a_wins {
+					prop_assert!(R::score(a) >= R::score(b), "the preferred chain scores lower");
+				}
+			}
+		}
+		Ok(())
+	});
+	if let Err(error) = result {
+		panic!("{} broke a law: {error}", core::any::type_name::<R>());
+	}
+}
+
+#[test]
+fn bc_fork_laws_generated_trees_are_valid() {
+	let mut runner = TestRunner::new(Config::with_cases(32));
+	runner
+		.run(&fork_trees(), |tree| {
+			prop_assert!(!tree.chains.is_empty());
+			let genesis = Header::genesis();
+			let from_genesis = tree.chains[0][0] == genesis;
+			for chain in &tree.chains {
+				let mined = if from_genesis {
+					prop_assert_eq!(&chain[0], &genesis);
+					&chain[1..]
+				} else {
+					prop_assert_eq!(chain[0].parent, hash(&genesis));
+					&chain[..]
+				};
+				for pair in chain.windows(2) {
+					prop_assert_eq!(pair[1].parent, hash(&pair[0]));
+					prop_assert_eq!(pair[1].height, pair[0].height + 1);
+				}
+				prop_assert!(mined.iter().all(|header| hash(header) <= THRESHOLD));
+			}
+			Ok(())
+		})
+		.unwrap();
+}
+
+#[test]
+fn bc_fork_laws_hold_for_every_rule() {
+	fork_choice_laws::<LongestChainRule>(64);
+	fork_choice_laws::<LongestChain<LowestTipHash>>(64);
+	fork_choice_laws::<LongestChain<MostWork>>(64);
+	fork_choice_laws::<HeaviestChainRule>(64);
+	fork_choice_laws::<MostBlocksWithEvenHash>(64);
+	fork_choice_laws::<Lexicographic<MostBlocksWithEvenHash, HeaviestChainRule>>(64);
+}
+
+#[test]
+fn bc_fork_laws_longest_chain_never_prefers_a_prefix() {
+	let mut runner = TestRunner::new(Config::with_cases(64));
+	runner
+		.run(&(fork_trees(), any::<Index>()), |(tree, cut)| {
+			for chain in &tree.chains {
+				let prefix = &chain[..cut.index(chain.len())];
+				prop_assert!(LongestChainRule::first_chain_is_better(chain, prefix));
+				prop_assert!(!LongestChainRule::first_chain_is_better(prefix, chain));
+			}
+			Ok(())
+		})
+		.unwrap();
+}
+
+/// A rule with a bug: it returns the right chain, but without its tip.
+struct ForgetfulRule;
+
+impl ForkChoice for ForgetfulRule {
+	fn first_chain_is_better(chain_1: &[Header], chain_2: &[Header]) -> bool {
+		LongestChainRule::first_chain_is_better(chain_1, chain_2)
+	}
+
+	fn best_chain<'a>(candidate_chains: &[&'a [Header]]) -> Result<&'a [Header], ForkChoiceError> {
+		let best = LongestChainRule::best_chain(candidate_chains)?;
+		Ok(&best[..best.len() - 1])
+	}
+}
+
+#[test]
+#[should_panic(expected = "not one of the candidates")]
+fn bc_fork_laws_catch_a_forgetful_rule() {
+	fork_choice_laws::<ForgetfulRule>(64);
+}
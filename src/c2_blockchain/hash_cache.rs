@@ -0,0 +1,84 @@
+//! Remember hashes that have already been computed.
+//!
+//! Fork choice rules hash every header of every candidate chain, and candidates that share a
+//! common prefix end up hashing the same headers again and again. On long forks those repeated
+//! `hash(header)` calls are where most of the time goes.
+//!
+//! A `HashCache` remembers the hash of each header it has seen, keyed by the header's identity
+//! (its address in memory) rather than its contents. Looking up by contents would mean hashing the
+//! header to find it, which is the very work we are trying to avoid. Identity is a safe key here
+//! because the cache borrows every header it stores for its whole lifetime, so the borrow checker
+//! guarantees a stored header can neither move nor change while the cache exists.
+
+use crate::hash;
+use alloc::collections::BTreeMap;
+use core::{hash::Hash as StdHash, marker::PhantomData};
+
+type Hash = u64;
+
+/// A memo of hashes for values borrowed for the lifetime `'a`.
+pub struct HashCache<'a, T> {
+	hashes: BTreeMap<usize, Hash>,
+	hits: usize,
+	_borrow: PhantomData<&'a T>,
+}
+
+impl<'a, T: StdHash> HashCache<'a, T> {
+	/// An empty cache.
+	pub fn new() -> Self {
+		HashCache { hashes: BTreeMap::new(), hits: 0, _borrow: PhantomData }
+	}
+
+	/// The hash of the value, computed only the first time this exact value is asked about.
+	pub fn hash(&mut self, value: &'a T) -> Hash {
+		let key = value as *const T as usize;
+		if let Some(hash) = self.hashes.get(&key) {
+			self.hits += 1;
+			return *hash;
+		}
+		let hash = hash(value);
+		self.hashes.insert(key, hash);
+		hash
+	}
+
+	/// How many lookups were answered without hashing.
+	pub fn hits(&self) -> usize {
+		self.hits
+	}
+
+	/// How many lookups had to hash, which is the number of distinct values seen.
+	pub fn misses(&self) -> usize {
+		self.hashes.len()
+	}
+}
+
+impl<'a, T: StdHash> Default for HashCache<'a, T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(test)]
+use super::p4_batched_extrinsics::Header;
+
+#[test]
+fn bc_hash_cache_hashes_each_value_once() {
+	let chain = [Header::genesis(), Header::genesis().child(1, 1)];
+	let mut cache = HashCache::new();
+
+	assert_eq!(cache.hash(&chain[0]), hash(&chain[0]));
+	assert_eq!(cache.hash(&chain[1]), hash(&chain[1]));
+	assert_eq!(cache.hash(&chain[0]), hash(&chain[0]));
+	assert_eq!((cache.hits(), cache.misses()), (1, 2));
+}
+
+#[test]
+fn bc_hash_cache_keys_by_identity() {
+	// Equal headers in different places are separate entries, but each still gets the right hash.
+	let a = Header::genesis();
+	let b = Header::genesis();
+	let mut cache = HashCache::new();
+
+	assert_eq!(cache.hash(&a), cache.hash(&b));
+	assert_eq!(cache.misses(), 2);
+}
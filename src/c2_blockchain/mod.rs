@@ -6,9 +6,25 @@
 // against them in future chapters. The prior iterations are not available outside this chapter.
 pub use p6_rich_state::{Block, Header};
 
+mod analysis;
+#[cfg(feature = "std")]
+mod calibration;
+mod corruption;
+mod double_spend;
+mod export;
+#[cfg(test)]
+mod fork_laws;
+mod hash_cache;
 mod p1_header_chain;
 mod p2_extrinsic_state;
 mod p3_consensus;
 pub mod p4_batched_extrinsics;
-mod p5_fork_choice;
-mod p6_rich_state;
+pub(crate) mod p5_fork_choice;
+pub(crate) mod p6_rich_state;
+mod reorg;
+pub mod repl;
+mod selfish_mining;
+mod signed;
+mod state_store;
+mod trace;
+mod viz;
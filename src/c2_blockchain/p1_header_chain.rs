@@ -3,6 +3,7 @@
 //! let's start with that.
 
 use crate::hash;
+use alloc::vec::Vec;
 
 // We will use Rust's built-in hashing where the output type is u64. I'll make an alias
 // so the code is slightly more readable.
@@ -10,6 +11,8 @@ type Hash = u64;
 
 /// The most basic blockchain header possible. We learned its basic structure from lecture.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "scale", derive(codec::Encode, codec::Decode))]
 pub struct Header {
 	parent: Hash,
 	height: u64,
@@ -8,6 +8,7 @@
 //! use some real batching.
 
 use crate::hash;
+use alloc::vec::Vec;
 
 // We will use Rust's built-in hashing where the output type is u64. I'll make an alias
 // so the code is slightly more readable.
@@ -17,6 +18,8 @@ type Hash = u64;
 /// using roots yet, but rather directly embedding some minimal extrinsic and state info
 /// into the header.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "scale", derive(codec::Encode, codec::Decode))]
 pub struct Header {
 	parent: Hash,
 	height: u64,
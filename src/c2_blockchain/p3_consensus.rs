@@ -6,6 +6,7 @@
 //! 2. Arbitrary / Political rules. Here we will implement two alternate validity rules
 
 use crate::hash;
+use alloc::vec::Vec;
 
 // We will use Rust's built-in hashing where the output type is u64. I'll make an alias
 // so the code is slightly more readable.
@@ -25,6 +26,8 @@ const FORK_HEIGHT: u64 = 2;
 /// hash below a certain threshold. Although we could call the field `nonce` we will leave
 /// the more general `digest` term. For PoA we would have a cryptographic signature in this field.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "scale", derive(codec::Encode, codec::Decode))]
 pub struct Header {
 	parent: Hash,
 	height: u64,
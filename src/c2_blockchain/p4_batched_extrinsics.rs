@@ -1,23 +1,34 @@
 //! Until now, each block has contained just a single extrinsic. Really we would prefer to batch
 //! them. Now, we stop relying solely on headers, and instead, create complete blocks.
-
-use crate::hash;
+//!
+//! The header commits to the batch with the Merkle root of the extrinsics. Anyone holding just a
+//! header can then be convinced that one particular extrinsic is in the block by a short proof,
+//! without downloading the rest of the body. This is what light clients rely on.
+
+use crate::{
+	crypto::merkle::{merkle_root, verify_proof, MerkleProof, MerkleTree},
+	hash,
+	mining::first_valid_digest,
+};
+use alloc::vec::Vec;
 type Hash = u64;
 
-const THRESHOLD: u64 = u64::max_value() / 100;
+pub(crate) const THRESHOLD: u64 = u64::max_value() / 100;
 
 /// The header no longer contains an extrinsic directly. Rather a vector of extrinsics will be
 /// stored in the block body. We are still storing the state in the header for now. This will change
 /// in an upcoming lesson as well.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "scale", derive(codec::Encode, codec::Decode))]
 pub struct Header {
-	parent: Hash,
-	height: u64,
+	pub(crate) parent: Hash,
+	pub(crate) height: u64,
 	// We now switch from storing an extrinsic directly, to storing an extrinsic root.
 	// This is basically a concise cryptographic commitment to the complete list of extrinsics.
 	// For example, a hash or a Merkle root.
-	extrinsics_root: Hash,
-	state: u64,
+	pub(crate) extrinsics_root: Hash,
+	pub(crate) state: u64,
 	pub consensus_digest: u64,
 }
 
@@ -43,18 +54,18 @@ impl Header {
 	/// Without the extrinsics themselves, we cannot calculate the final state
 	/// so that information is passed in.
 	pub fn child(&self, extrinsics_root: Hash, state: u64) -> Self {
-		let mut h = Header {
+		let h = Header {
 			parent: hash(self),
 			height: self.height + 1,
 			extrinsics_root,
 			state,
 			consensus_digest: u64::MIN,
 		};
-		//hash until under threshold
-		while hash(&h) > THRESHOLD {
-			h.consensus_digest += 1;
-		}
-		h
+		//hash until under threshold, on every core with `std`
+		let consensus_digest = first_valid_digest(u64::MIN, |digest| {
+			hash(&Header { consensus_digest: digest, ..h.clone() }) <= THRESHOLD
+		});
+		Header { consensus_digest, ..h }
 	}
 
 	/// Verify a single child header.
@@ -68,6 +79,12 @@ impl Header {
 		child.parent == hash(self) && child.height == self.height + 1
 	}
 
+	/// Check a proof, such as one from `Block::extrinsic_proof`, that the extrinsic is in the
+	/// block with this header.
+	pub fn contains_extrinsic(&self, extrinsic: u64, proof: &MerkleProof) -> bool {
+		verify_proof(self.extrinsics_root, &extrinsic, proof)
+	}
+
 	/// Verify that all the given headers form a valid chain from this header to the tip.
 	///
 	/// We can now trivially write the old verification function in terms of the new one.
@@ -87,6 +104,8 @@ impl Header {
 
 /// A complete Block is a header and the extrinsics.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "scale", derive(codec::Encode, codec::Decode))]
 pub struct Block {
 	pub(crate) header: Header,
 	pub(crate) body: Vec<u64>,
@@ -115,12 +134,19 @@ impl Block {
 	/// The extrinsics are batched now, so we need to execute each of them.
 	pub fn child(&self, extrinsics: Vec<u64>) -> Self {
 		let new_state = Self::execute_exts(self.header.state, &extrinsics);
-		Block { header: self.header.child(hash(&extrinsics), new_state), body: extrinsics }
+		Block { header: self.header.child(merkle_root(&extrinsics), new_state), body: extrinsics }
+	}
+
+	/// A proof that the extrinsic at `index` is in this block, or `None` if there is no such
+	/// extrinsic.
+	pub fn extrinsic_proof(&self, index: usize) -> Option<MerkleProof> {
+		MerkleTree::new(&self.body).proof(index)
 	}
 
 	//verify parent and child block
 	fn verify_block_child(parent: &Block, child: &Block) -> bool {
-		let header_cond = parent.header.verify_child(&child.header);
+		let header_cond = parent.header.verify_child(&child.header)
+			&& child.header.extrinsics_root == merkle_root(&child.body);
 		let body_cond = child.header.state == Self::execute_exts(parent.header.state, &child.body);
 		if !(header_cond && body_cond) {
 			return false;
@@ -266,3 +292,50 @@ fn bc_4_student_invalid_block_really_is_invalid() {
 	// Make sure that the block is not valid when executed.
 	assert!(!gb.verify_sub_chain(&[b1]));
 }
+
+#[test]
+fn bc_4_reordered_body_does_not_check() {
+	let b0 = Block::genesis();
+	let mut b1 = b0.child(vec![1, 2, 3]);
+	// The state is the same, but the header committed to a different order.
+	b1.body = vec![3, 2, 1];
+
+	assert!(!b0.verify_sub_chain(&[b1]));
+}
+
+#[test]
+fn bc_4_extrinsic_inclusion_proofs() {
+	let b1 = Block::genesis().child(vec![10, 20, 30, 40, 50]);
+	let header = &b1.header;
+
+	for (index, extrinsic) in b1.body.iter().enumerate() {
+		let proof = b1.extrinsic_proof(index).unwrap();
+		assert!(header.contains_extrinsic(*extrinsic, &proof));
+		assert!(!header.contains_extrinsic(extrinsic + 1, &proof));
+	}
+	assert!(b1.extrinsic_proof(5).is_none());
+	let other = Block::genesis().child(vec![10, 20, 31, 40, 50]);
+	assert!(!other.header.contains_extrinsic(10, &b1.extrinsic_proof(0).unwrap()));
+}
+
+#[cfg(feature = "scale")]
+#[test]
+fn bc_4_block_scale_round_trip() {
+	use codec::{Decode, Encode};
+
+	let b1 = Block::genesis().child(vec![1, 2, 3]);
+	let encoded = b1.encode();
+
+	assert_eq!(Block::decode(&mut &encoded[..]), Ok(b1));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn bc_4_block_json_round_trip() {
+	let b1 = Block::genesis().child(vec![1, 2, 3]);
+	let json = serde_json::to_string(&b1).unwrap();
+	let header_json = serde_json::to_string(&b1.header).unwrap();
+
+	assert_eq!(serde_json::from_str::<Block>(&json).unwrap(), b1);
+	assert_eq!(serde_json::from_str::<Header>(&header_json).unwrap(), b1.header);
+}
@@ -6,16 +6,47 @@
 //!
 //! Since we have nothing to add to the Block or Header data structures in this lesson,
 //! we will import them from the previous lesson.
+//!
+//! None of the rules here look inside the headers beyond hashing them, so they work for any
+//! hashable header type. In particular they also work for the `Header<Digest>` of the consensus
+//! chapter, whatever its consensus engine.
+
+use super::{
+	hash_cache::HashCache,
+	p4_batched_extrinsics::{Block, Header},
+	reorg::{common_ancestor, reorg},
+};
+use crate::{hash, mining::first_valid_digest};
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::{hash::Hash as StdHash, marker::PhantomData};
 
-use super::p4_batched_extrinsics::{Block, Header};
-use crate::hash;
+type Hash = u64;
 
 const THRESHOLD: u64 = u64::max_value() / 100;
 
+/// The ways choosing the best of several chains can fail.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ForkChoiceError {
+	/// There were no candidate chains to choose from.
+	NoCandidates,
+	/// The rule cannot score the candidate at this index. The rules of this chapter can score any
+	/// chain of headers, but a rule that needs more than the headers, such as the slots of a
+	/// consensus engine, may not be able to.
+	Incomparable(usize),
+	/// None of the candidates builds on the finalized block. See `FinalizedBase`.
+	NoFinalizedDescendant,
+	/// Every candidate failed validation. See `best_valid_chain`.
+	NoValidCandidate,
+	/// Every candidate would revert too much of the current chain. See `BoundedReorg`.
+	ReorgTooDeep,
+}
+
 /// Judge which blockchain is "best" when there are multiple candidates. There are several
 /// meaningful notions of "best" which is why this is a trait instead of just a
 /// method.
-pub trait ForkChoice {
+///
+/// The trait is generic over the type of header, which defaults to the header of this chapter.
+pub trait ForkChoice<H = Header> {
 	/// Compare two chains, and return the "best" one.
 	///
 	/// The chains are not assumed to start from the same genesis block, or even a
@@ -25,43 +56,103 @@ pub trait ForkChoice {
 	///
 	/// The chains are assumed to be valid, so it is up to the caller to check
 	/// validity first if they are unsure.
-	fn first_chain_is_better(chain_1: &[Header], chain_2: &[Header]) -> bool;
+	fn first_chain_is_better(chain_1: &[H], chain_2: &[H]) -> bool;
+
+	/// A number saying how good the chain is under this rule, so callers can log or chart why one
+	/// chain won. A better chain never scores lower, but equal scores do not mean a tie, because
+	/// a rule may look at more than the score. The longest chain rule, for example, still has to
+	/// break ties between chains of the same length.
+	///
+	/// The provided implementation scores a chain by its length.
+	fn score(chain: &[H]) -> u128 {
+		chain.len() as u128
+	}
 
 	/// Compare many chains and return the best one.
 	///
 	/// It is always possible to compare several chains if you are able to compare
 	/// two chains. Therefore this method has a provided implementation. However,
 	/// it may be much more performant to write a fork-choice-specific implementation.
-	fn best_chain<'a>(candidate_chains: &[&'a [Header]]) -> &'a [Header] {
-		let mut best = candidate_chains[0];
+	///
+	/// Fails with `ForkChoiceError::NoCandidates` if there are no candidates.
+	fn best_chain<'a>(candidate_chains: &[&'a [H]]) -> Result<&'a [H], ForkChoiceError> {
+		let mut best = *candidate_chains.first().ok_or(ForkChoiceError::NoCandidates)?;
 		for i in 1..candidate_chains.len() {
 			if Self::first_chain_is_better(best, candidate_chains[i]) {
 				continue
 			}
 			best = candidate_chains[i];
 		}
-		best
+		Ok(best)
 	}
 }
 
 /// The "best" chain is simply the longest chain.
-pub struct LongestChainRule;
+///
+/// Chains of the same length are common, for example whenever two miners find a block at the same
+/// height. The `Tie` parameter decides which of them wins. Nodes that must agree on the head, such
+/// as the nodes of a simulation, should use a tie break that only depends on the chains
+/// themselves, like `LowestTipHash` or `MostWork`. Most code uses `LongestChainRule`, which keeps
+/// whichever chain it received first.
+pub struct LongestChain<Tie>(PhantomData<Tie>);
 
-impl ForkChoice for LongestChainRule {
-	fn first_chain_is_better(chain_1: &[Header], chain_2: &[Header]) -> bool {
-		chain_1.len() >= chain_2.len()
+/// The longest chain rule, keeping the chain received first on a tie.
+pub type LongestChainRule = LongestChain<EarliestReceived>;
+
+/// How `LongestChain` chooses between two chains of the same length.
+pub trait TieBreak<H> {
+	/// Whether the first of two equally long chains should win.
+	fn first_wins_tie(chain_1: &[H], chain_2: &[H]) -> bool;
+}
+
+/// Keep the chain that was received first. The rule cannot see when a chain arrived, so the first
+/// argument, or the earliest candidate, is assumed to be the one received first.
+///
+/// This is what Bitcoin nodes do, and it is the reason two nodes can disagree about the head until
+/// the next block breaks the tie.
+pub struct EarliestReceived;
+
+impl<H> TieBreak<H> for EarliestReceived {
+	fn first_wins_tie(_: &[H], _: &[H]) -> bool {
+		true
 	}
+}
 
-	fn best_chain<'a>(candidate_chains: &[&'a [Header]]) -> &'a [Header] {
-		let mut best_length = candidate_chains[0].len();
-		let mut best_index = 0;
-		for i in 1..candidate_chains.len() {
-			if candidate_chains[i].len() > best_length {
-				best_length = candidate_chains[i].len();
-				best_index = i;
+/// Prefer the chain whose last header has the lower hash.
+pub struct LowestTipHash;
+
+impl<H: StdHash> TieBreak<H> for LowestTipHash {
+	fn first_wins_tie(chain_1: &[H], chain_2: &[H]) -> bool {
+		chain_1.last().map(hash) <= chain_2.last().map(hash)
+	}
+}
+
+/// Prefer the chain with more work, as measured by the heaviest chain rule. Chains with exactly
+/// the same work fall back to the first.
+pub struct MostWork;
+
+impl<H: StdHash> TieBreak<H> for MostWork {
+	fn first_wins_tie(chain_1: &[H], chain_2: &[H]) -> bool {
+		HeaviestChainRule::score(chain_1) >= HeaviestChainRule::score(chain_2)
+	}
+}
+
+impl<H, Tie: TieBreak<H>> ForkChoice<H> for LongestChain<Tie> {
+	fn first_chain_is_better(chain_1: &[H], chain_2: &[H]) -> bool {
+		chain_1.len() > chain_2.len()
+			|| (chain_1.len() == chain_2.len() && Tie::first_wins_tie(chain_1, chain_2))
+	}
+
+	fn best_chain<'a>(candidate_chains: &[&'a [H]]) -> Result<&'a [H], ForkChoiceError> {
+		let mut best = *candidate_chains.first().ok_or(ForkChoiceError::NoCandidates)?;
+		for candidate in &candidate_chains[1..] {
+			if candidate.len() > best.len()
+				|| (candidate.len() == best.len() && !Tie::first_wins_tie(best, candidate))
+			{
+				best = candidate;
 			}
 		}
-		candidate_chains[best_index]
+		Ok(best)
 	}
 }
 
@@ -73,46 +164,85 @@ impl ForkChoice for LongestChainRule {
 /// nonces. Modeling the amount of work required to achieve a particular hash
 /// is out of scope for this exercise, so we will use the not-really-right-but
 /// conceptually-good-enough formula `work = THRESHOLD - block_hash`
+///
+/// A header that was never mined, such as genesis, has a hash above the threshold and counts as no
+/// work. So chains can be compared whether or not they start at genesis, and every method of the
+/// rule agrees on how much work a chain has.
 pub struct HeaviestChainRule;
 
 /// Mutates a block (and its embedded header) to contain more PoW difficulty.
 /// This will be useful for exploring the heaviest chain rule. The expected
 /// usage is that you create a block using the normal `Block.child()` method
 /// and then pass the block to this helper for additional mining.
-fn mine_extra_hard(header: &mut Header, threshold: u64) {
-	//hash until under threshold
-	while hash(&header) > threshold {
-		header.consensus_digest += 1;
+///
+/// With `std` the search is spread over every available core, see `crate::mining`.
+pub(crate) fn mine_extra_hard(header: &mut Header, threshold: u64) {
+	let template = header.clone();
+	header.consensus_digest = first_valid_digest(header.consensus_digest, |digest| {
+		hash(&Header { consensus_digest: digest, ..template.clone() }) <= threshold
+	});
+}
+
+impl HeaviestChainRule {
+	/// The total work of the chain. A header that was never mined, such as genesis, adds nothing.
+	/// The work is added up in a `u128`, because a `u64` overflows after a couple of hundred
+	/// headers at this threshold, and sooner if they were mined extra hard.
+	pub fn chain_weight<H: StdHash>(chain: &[H]) -> u128 {
+		Self::chain_weight_by(chain, hash)
+	}
+
+	/// `chain_weight`, hashing the headers with the given function.
+	fn chain_weight_by<'a, H>(chain: &'a [H], mut hash: impl FnMut(&'a H) -> Hash) -> u128 {
+		chain
+			.iter()
+			.map(|header| u128::from(THRESHOLD.saturating_sub(hash(header))))
+			.sum()
 	}
 }
 
-impl ForkChoice for HeaviestChainRule {
-	fn first_chain_is_better(chain_1: &[Header], chain_2: &[Header]) -> bool {
-		let mut weight_1 = 0;
-		for header in chain_1 {
-			weight_1 += THRESHOLD - hash(header);
-		}
-		let mut weight_2 = 0;
-		for header in chain_2 {
-			weight_2 += THRESHOLD - hash(header);
-		}
-		println!("{}", weight_1);
-		println!("{}", weight_2);
+impl<H: StdHash> ForkChoice<H> for HeaviestChainRule {
+	fn first_chain_is_better(chain_1: &[H], chain_2: &[H]) -> bool {
+		let (weight_1, weight_2) = (Self::score(chain_1), Self::score(chain_2));
+		teaching_log!(
+			"fork_choice",
+			Detail,
+			"heaviest chain rule: the first chain has {weight_1} work and the second {weight_2}, so \
+			 the {} chain is better",
+			if weight_1 >= weight_2 { "first" } else { "second" }
+		);
 		weight_1 >= weight_2
 	}
 
-	// Specific implementation would remove the redundant hashing but that's okay this excercise
-	// fn best_chain<'a>(candidate_chains: &[&'a [Header]]) -> &'a [Header] {
-	// 	// Remember, this method is provided.
-	// 	todo!("Exercise 6")
-	// }
+	/// The total work of the chain, its `chain_weight`.
+	fn score(chain: &[H]) -> u128 {
+		Self::chain_weight(chain)
+	}
+
+	// Candidates usually share a prefix, so score them all through one cache rather than
+	// re-hashing the shared headers in every pairwise comparison. Each header is hashed once.
+	fn best_chain<'a>(candidate_chains: &[&'a [H]]) -> Result<&'a [H], ForkChoiceError> {
+		let mut cache = HashCache::new();
+		let mut work = |chain: &'a [H]| Self::chain_weight_by(chain, |header| cache.hash(header));
+		let mut best = *candidate_chains.first().ok_or(ForkChoiceError::NoCandidates)?;
+		let mut best_work = work(best);
+		for chain in &candidate_chains[1..] {
+			let chain_work = work(chain);
+			// Ties keep the earlier candidate, as `first_chain_is_better` does.
+			if chain_work > best_work {
+				best = chain;
+				best_work = chain_work;
+			}
+		}
+		Ok(best)
+	}
 }
 /// The best chain is the one with the most blocks that have even hashes.
 ///
 /// This exact rule is a bit contrived, but it does model a family of fork choice rules
 /// that are useful in the real world. We just can't code them here because we haven't
 /// implemented Proof of Authority yet. Consider the following real world examples
-/// that have very similar implementations.
+/// that have very similar implementations. The consensus chapter implements the first one for real
+/// as `MostPrimarySlotBlocks`.
 ///
 /// 1. Secondary authors. In each round there is one author who is supposed to author. If that
 ///    author fails to create a block, there is a secondary author who may do so. The best chain is
@@ -120,11 +250,12 @@ impl ForkChoice for HeaviestChainRule {
 ///
 /// 2. Interleaved Pow/PoA. In each round there is one author who is allowed to author. Anyone else
 ///    is allowed to mine a PoW-style block. The best chain is the one with the most PoA blocks, and
-///    ties are broken by the most accumulated work.
+///    ties are broken by the most accumulated work. With even hashes standing in for PoA blocks,
+///    that is `Lexicographic<MostBlocksWithEvenHash, HeaviestChainRule>`.
 pub struct MostBlocksWithEvenHash;
 
-impl ForkChoice for MostBlocksWithEvenHash {
-	fn first_chain_is_better(chain_1: &[Header], chain_2: &[Header]) -> bool {
+impl<H: StdHash> ForkChoice<H> for MostBlocksWithEvenHash {
+	fn first_chain_is_better(chain_1: &[H], chain_2: &[H]) -> bool {
 		let mut count_1 = 0;
 		for header in chain_1 {
 			count_1 += 1 - (hash(header) & 1);
@@ -136,25 +267,742 @@ impl ForkChoice for MostBlocksWithEvenHash {
 		count_1 > count_2
 	}
 
+	fn score(chain: &[H]) -> u128 {
+		chain.iter().filter(|header| hash(header) % 2 == 0).count() as u128
+	}
+
 	//same here, I'd worry if it was a production system
-	// fn best_chain<'a>(candidate_chains: &[&'a [Header]]) -> &'a [Header] {
+	// fn best_chain<'a>(candidate_chains: &[&'a [Header]]) -> Result<&'a [Header], ForkChoiceError> {
 		// Remember, this method is provided.
 		// todo!("Exercise 8")
 	// }
 }
 
-// This lesson has omitted one popular fork choice rule:
-// GHOST - Greedy Heaviest Observed SubTree
-//
-// I've omitted GHOST from here because it requires information about blocks that
-// are _not_ in the chain to decide which chain is best. Therefore it does't work
-// well with this relatively simple trait definition. We will return to the GHOST
-// rule later when we have written a full blockchain client
-//
-// The GHOST rule was first published in 2013 by Yonatan Sompolinsky and Aviv Zohar.
-// Learn more at https://eprint.iacr.org/2013/881.pdf
+/// Compare chains with rule `A`, and only if `A` considers them equally good, with rule `B`.
+///
+/// Rules only answer "is the first chain better?", so a tie has to be recognized from two
+/// questions: `A` ties when it gives the same answer with the chains swapped. That covers rules
+/// that prefer the first chain on a tie, like `LongestChainRule`, and rules that prefer the second,
+/// like `MostBlocksWithEvenHash`.
+///
+/// Combinators nest, so `Lexicographic<A, Lexicographic<B, C>>` tries three rules in turn.
+pub struct Lexicographic<A, B>(PhantomData<(A, B)>);
+
+impl<H, A: ForkChoice<H>, B: ForkChoice<H>> ForkChoice<H> for Lexicographic<A, B> {
+	fn first_chain_is_better(chain_1: &[H], chain_2: &[H]) -> bool {
+		let first = A::first_chain_is_better(chain_1, chain_2);
+		if first == A::first_chain_is_better(chain_2, chain_1) {
+			return B::first_chain_is_better(chain_1, chain_2);
+		}
+		first
+	}
+
+	/// The score of `A`, which decides every comparison that is not a tie.
+	fn score(chain: &[H]) -> u128 {
+		A::score(chain)
+	}
+}
+
+/// Which of two compared chains won.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Winner {
+	First,
+	Second,
+}
+
+/// Why a rule preferred the winner.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reason {
+	/// The winner has the higher score under the rule.
+	HigherScore,
+	/// Both chains have the same score, so the rule's tie break, or whatever else it looks at
+	/// beyond the score, decided.
+	Tie,
+}
+
+/// Everything `compare_chains` found out about two chains. Each pair lists the first chain's value,
+/// then the second's.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Comparison {
+	pub winner: Winner,
+	pub reason: Reason,
+	/// The scores under the rule that made the choice. See `ForkChoice::score`.
+	pub scores: (u128, u128),
+	pub lengths: (usize, usize),
+	/// The work of each chain, as the heaviest chain rule counts it, whichever rule chose.
+	pub weights: (u128, u128),
+	/// The height of the last block both chains share, or `None` if they show no shared history.
+	pub divergence_height: Option<u64>,
+}
+
+/// Compare two chains with rule `R`, and explain the result.
+///
+/// `first_chain_is_better` only answers with a `bool`, which is all a node needs but says nothing
+/// about why. This reports the numbers behind the decision, so users can see what a rule cared
+/// about, and where the chains went their separate ways. The winner is always the one
+/// `first_chain_is_better` picks.
+pub fn compare_chains<R: ForkChoice>(chain_1: &[Header], chain_2: &[Header]) -> Comparison {
+	let winner =
+		if R::first_chain_is_better(chain_1, chain_2) { Winner::First } else { Winner::Second };
+	let scores = (R::score(chain_1), R::score(chain_2));
+	Comparison {
+		winner,
+		reason: if scores.0 == scores.1 { Reason::Tie } else { Reason::HigherScore },
+		scores,
+		lengths: (chain_1.len(), chain_2.len()),
+		weights: (HeaviestChainRule::score(chain_1), HeaviestChainRule::score(chain_2)),
+		divergence_height: common_ancestor(chain_1, chain_2).map(|ancestor| ancestor.height),
+	}
+}
+
+/// Check every candidate chain of blocks, discard the invalid ones, and let rule `R` choose among
+/// the rest.
+///
+/// `ForkChoice` assumes its chains are valid, because checking them needs the blocks and not just
+/// the headers. But an invalid chain can easily be the longest or the heaviest, since its author
+/// did not have to execute anything correctly, and a caller who forgets to check first would
+/// follow it. This entry point does both steps, so it cannot be misused that way.
+///
+/// The first block of each candidate is trusted, usually because it is genesis or a block the
+/// caller already imported, and the rest are checked against it with `Block::verify_sub_chain`.
+/// Empty candidates have nothing to trust and are discarded.
+pub fn best_valid_chain<'a, R: ForkChoice>(
+	candidate_chains: &[&'a [Block]],
+) -> Result<&'a [Block], ForkChoiceError> {
+	if candidate_chains.is_empty() {
+		return Err(ForkChoiceError::NoCandidates);
+	}
+	let valid: Vec<usize> = (0..candidate_chains.len())
+		.filter(|i| match candidate_chains[*i] {
+			[] => false,
+			[_] => true,
+			[base, rest @ ..] => base.verify_sub_chain(rest),
+		})
+		.collect();
+	if valid.is_empty() {
+		return Err(ForkChoiceError::NoValidCandidate);
+	}
+	let headers: Vec<Vec<Header>> = valid
+		.iter()
+		.map(|i| candidate_chains[*i].iter().map(|block| block.header.clone()).collect())
+		.collect();
+	let header_chains: Vec<&[Header]> = headers.iter().map(Vec::as_slice).collect();
+	let best = R::best_chain(&header_chains).map_err(|error| match error {
+		ForkChoiceError::Incomparable(i) => ForkChoiceError::Incomparable(valid[i]),
+		error => error,
+	})?;
+	// Chains are told apart by address, because two candidates may hold the same headers.
+	let chosen = header_chains
+		.iter()
+		.position(|chain| core::ptr::eq(*chain, best))
+		.expect("the rule chooses one of the candidates");
+	Ok(candidate_chains[valid[chosen]])
+}
+
+/// Only consider chains that build on a finalized block, and let the inner rule choose among them.
+///
+/// Fork choice rules like the heaviest chain rule never make a decision permanent: a long enough
+/// fork can always replace any block. Real chains pair them with a _finality gadget_, such as
+/// Polkadot's GRANDPA, which marks blocks as final once enough validators vote for them. From then
+/// on a node ignores every fork that does not contain the finalized block, however heavy it is.
+///
+/// Because it needs to know which block is final, this wrapper is a value rather than a type like
+/// the rules above. Like them, it is given chains and not a block tree, so it can only check the
+/// ancestry a chain itself shows. Pass chains that contain the finalized block, or that start
+/// right after it. Chains that start later are rejected, because their ancestry is unknown.
+pub struct FinalizedBase<Inner> {
+	pub finalized_hash: Hash,
+	pub finalized_height: u64,
+	_inner: PhantomData<Inner>,
+}
+
+impl<Inner: ForkChoice> FinalizedBase<Inner> {
+	/// Choose among the chains that descend from the given finalized block.
+	pub fn new(finalized: &Header) -> Self {
+		FinalizedBase {
+			finalized_hash: hash(finalized),
+			finalized_height: finalized.height,
+			_inner: PhantomData,
+		}
+	}
+
+	/// Whether the chain visibly builds on the finalized block.
+	pub fn descends_from_finalized(&self, chain: &[Header]) -> bool {
+		let Some(first) = chain.first() else {
+			return false;
+		};
+		match first.height.checked_sub(self.finalized_height) {
+			Some(0) => hash(first) == self.finalized_hash,
+			Some(1) => first.parent == self.finalized_hash,
+			Some(_) => false,
+			None => {
+				let offset = (self.finalized_height - first.height) as usize;
+				chain.get(offset).is_some_and(|header| hash(header) == self.finalized_hash)
+			},
+		}
+	}
+
+	/// The best of the candidates that descend from the finalized block, according to the inner
+	/// rule. If the inner rule fails, the index in its error is the index in `candidate_chains`.
+	pub fn best_chain<'a>(
+		&self,
+		candidate_chains: &[&'a [Header]],
+	) -> Result<&'a [Header], ForkChoiceError> {
+		if candidate_chains.is_empty() {
+			return Err(ForkChoiceError::NoCandidates);
+		}
+		let indices: Vec<usize> = (0..candidate_chains.len())
+			.filter(|i| self.descends_from_finalized(candidate_chains[*i]))
+			.collect();
+		if indices.is_empty() {
+			return Err(ForkChoiceError::NoFinalizedDescendant);
+		}
+		let descendants: Vec<&'a [Header]> = indices.iter().map(|i| candidate_chains[*i]).collect();
+		Inner::best_chain(&descendants).map_err(|error| match error {
+			ForkChoiceError::Incomparable(i) => ForkChoiceError::Incomparable(indices[i]),
+			error => error,
+		})
+	}
+}
+
+/// Refuse to switch to a chain that would revert more than `max_depth` blocks of the current one,
+/// and let the inner rule choose among the rest.
+///
+/// Proof of work chains have no finality gadget, so in principle any block can be reverted by a
+/// heavier fork. In practice, people treat a block buried deep enough as final, and some clients
+/// enforce it: they stay on their chain rather than follow a fork that branches off too far back.
+/// This is sometimes called finality by depth. It protects against an attacker who secretly mines
+/// a long fork, but a node that was cut off from the network for too long can no longer rejoin
+/// the rest of it without help.
+///
+/// Whether a reorg is too deep depends on the chain the node currently follows, so this wrapper
+/// keeps that chain and has to be told whenever the head changes. The current chain only needs its
+/// last `max_depth + 1` blocks, as long as every candidate contains, or starts right after, one of
+/// them. A candidate that shares no visible history with the current chain is refused, because the
+/// depth of the reorg cannot be known.
+pub struct BoundedReorg<Inner> {
+	pub max_depth: u64,
+	head: Vec<Header>,
+	_inner: PhantomData<Inner>,
+}
+
+impl<Inner: ForkChoice> BoundedReorg<Inner> {
+	/// A rule without a current chain yet, which allows every candidate until `update_head`.
+	pub fn new(max_depth: u64) -> Self {
+		BoundedReorg { max_depth, head: Vec::new(), _inner: PhantomData }
+	}
+
+	/// Remember the chain the node now follows, usually the one `best_chain` just chose.
+	pub fn update_head(&mut self, chain: &[Header]) {
+		self.head = chain.to_vec();
+	}
+
+	/// Whether switching to the chain reverts at most `max_depth` blocks of the current chain.
+	pub fn allows(&self, chain: &[Header]) -> bool {
+		self.head.is_empty()
+			|| reorg(&self.head, chain).is_some_and(|reorg| reorg.depth() <= self.max_depth)
+	}
+
+	/// The best of the allowed candidates, according to the inner rule. If the inner rule fails,
+	/// the index in its error is the index in `candidate_chains`.
+	pub fn best_chain<'a>(
+		&self,
+		candidate_chains: &[&'a [Header]],
+	) -> Result<&'a [Header], ForkChoiceError> {
+		if candidate_chains.is_empty() {
+			return Err(ForkChoiceError::NoCandidates);
+		}
+		let indices: Vec<usize> = (0..candidate_chains.len())
+			.filter(|i| self.allows(candidate_chains[*i]))
+			.collect();
+		if indices.is_empty() {
+			return Err(ForkChoiceError::ReorgTooDeep);
+		}
+		let allowed: Vec<&'a [Header]> = indices.iter().map(|i| candidate_chains[*i]).collect();
+		Inner::best_chain(&allowed).map_err(|error| match error {
+			ForkChoiceError::Incomparable(i) => ForkChoiceError::Incomparable(indices[i]),
+			error => error,
+		})
+	}
+}
+
+/// A block that validators have voted on, and how much stake voted for it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+	pub height: u64,
+	pub hash: Hash,
+	pub votes: u64,
+}
+
+/// Prefer chains containing the highest justified checkpoint, and use the heaviest chain rule
+/// among chains that contain the same one.
+///
+/// This is the idea behind Casper FFG, the finality overlay of Ethereum's proof of stake.
+/// Validators vote on checkpoint blocks, and a checkpoint with votes from at least two thirds of
+/// the stake is _justified_. Honest validators never vote against a justified checkpoint, so a
+/// chain without it would need the votes of more than a third of the stake that promised not to
+/// vote that way. Nodes therefore follow the highest justified checkpoint before anything else.
+/// Real Casper also finalizes a checkpoint once the one after it is justified, which this rule
+/// leaves out.
+///
+/// Checkpoints are passed in rather than counted from the chain, because these blocks do not
+/// carry votes.
+pub struct JustifiedCheckpointRule {
+	pub checkpoints: Vec<Checkpoint>,
+	/// The stake of every validator together.
+	pub total_votes: u64,
+}
+
+impl JustifiedCheckpointRule {
+	/// Whether at least two thirds of the stake voted for the checkpoint.
+	pub fn is_justified(&self, checkpoint: &Checkpoint) -> bool {
+		checkpoint.votes as u128 * 3 >= self.total_votes as u128 * 2
+	}
+
+	/// The height of the highest justified checkpoint the chain contains.
+	pub fn justified_height(&self, chain: &[Header]) -> Option<u64> {
+		let justified: BTreeMap<Hash, u64> = self
+			.checkpoints
+			.iter()
+			.filter(|checkpoint| self.is_justified(checkpoint))
+			.map(|checkpoint| (checkpoint.hash, checkpoint.height))
+			.collect();
+		chain.iter().filter_map(|header| justified.get(&hash(header)).copied()).max()
+	}
+
+	/// The heaviest of the candidates that contain the highest justified checkpoint any of them
+	/// contains.
+	pub fn best_chain<'a>(
+		&self,
+		candidate_chains: &[&'a [Header]],
+	) -> Result<&'a [Header], ForkChoiceError> {
+		let heights: Vec<Option<u64>> =
+			candidate_chains.iter().map(|chain| self.justified_height(chain)).collect();
+		let highest = *heights.iter().max().ok_or(ForkChoiceError::NoCandidates)?;
+		let indices: Vec<usize> =
+			(0..candidate_chains.len()).filter(|i| heights[*i] == highest).collect();
+		let contenders: Vec<&'a [Header]> = indices.iter().map(|i| candidate_chains[*i]).collect();
+		HeaviestChainRule::best_chain(&contenders)
+	}
+}
+
+/// Headers that arrived before their parent.
+///
+/// Headers travel through the network independently, so a node often hears of a block before its
+/// parent. It cannot score such a header yet, but throwing it away would mean downloading it
+/// again, so it waits here until the parent is imported.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OrphanPool {
+	/// The waiting headers, keyed by the hash of the parent they wait for.
+	waiting: BTreeMap<Hash, Vec<Header>>,
+}
+
+impl OrphanPool {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Hold a header until its parent arrives. A header that is already waiting is not added
+	/// again, so hearing of the same orphan from several peers does not grow the pool.
+	pub fn add(&mut self, header: Header) {
+		let header_hash = hash(&header);
+		let siblings = self.waiting.entry(header.parent).or_default();
+		if !siblings.iter().any(|waiting| hash(waiting) == header_hash) {
+			siblings.push(header);
+		}
+	}
+
+	/// Remove and return the headers that were waiting for this parent.
+	pub fn take_children(&mut self, parent: Hash) -> Vec<Header> {
+		self.waiting.remove(&parent).unwrap_or_default()
+	}
+
+	/// Drop the headers below the given height.
+	pub fn prune_below(&mut self, height: u64) {
+		self.waiting.retain(|_, headers| {
+			headers.retain(|header| header.height >= height);
+			!headers.is_empty()
+		});
+	}
+
+	/// How many headers are waiting.
+	pub fn len(&self) -> usize {
+		self.waiting.values().map(Vec::len).sum()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.waiting.is_empty()
+	}
+}
+
+/// Every header a node has heard of, whether or not it is on the chain the node currently prefers.
+///
+/// The `ForkChoice` trait only ever sees whole chains, so it cannot look at blocks that are _not_
+/// on a candidate chain. Some rules need exactly that information, so this structure keeps the
+/// full tree of known headers instead. Headers whose parent has not arrived yet are held back as
+/// orphans and attached as soon as the parent is imported.
+pub struct BlockTree {
+	root: Hash,
+	headers: BTreeMap<Hash, Header>,
+	/// The children of each header, in the order they were imported.
+	children: BTreeMap<Hash, Vec<Hash>>,
+	orphans: OrphanPool,
+}
+
+impl BlockTree {
+	/// A tree containing only the given root, usually the genesis header.
+	pub fn new(root: Header) -> Self {
+		let root_hash = hash(&root);
+		BlockTree {
+			root: root_hash,
+			headers: BTreeMap::from([(root_hash, root)]),
+			children: BTreeMap::new(),
+			orphans: OrphanPool::new(),
+		}
+	}
+
+	/// Add a header to the tree. Returns whether it is connected to the root, rather than waiting
+	/// as an orphan. Importing a header also connects any orphans that were waiting for it.
+	///
+	/// A header whose height is not one more than its parent's is rejected, and so is an orphan
+	/// found to have the wrong height once its parent arrives. The tree relies on children being
+	/// higher than their parents.
+	pub fn import(&mut self, header: Header) -> bool {
+		match self.headers.get(&header.parent) {
+			None => {
+				self.orphans.add(header);
+				return false;
+			},
+			Some(parent) if header.height != parent.height + 1 => return false,
+			Some(_) => {},
+		}
+		let mut ready = vec![header];
+		while let Some(header) = ready.pop() {
+			let header_hash = hash(&header);
+			if self.headers.contains_key(&header_hash)
+				|| header.height != self.headers[&header.parent].height + 1
+			{
+				continue;
+			}
+			self.children.entry(header.parent).or_default().push(header_hash);
+			self.headers.insert(header_hash, header);
+			ready.extend(self.orphans.take_children(header_hash));
+		}
+		true
+	}
+
+	/// How many headers are connected to the root, including the root itself.
+	pub fn len(&self) -> usize {
+		self.headers.len()
+	}
+
+	/// How many headers are still waiting for their parent.
+	pub fn orphan_count(&self) -> usize {
+		self.orphans.len()
+	}
+
+	/// The chain from the root to the given header, or `None` if the header is not connected.
+	pub fn chain_to(&self, head: Hash) -> Option<Vec<Header>> {
+		let mut chain = vec![self.headers.get(&head)?.clone()];
+		let mut current = head;
+		while current != self.root {
+			current = chain[chain.len() - 1].parent;
+			chain.push(self.headers[&current].clone());
+		}
+		chain.reverse();
+		Some(chain)
+	}
+
+	/// The total weight of every header in the subtree below (and including) each header.
+	fn subtree_totals(&self, weight: impl Fn(Hash) -> u128) -> BTreeMap<Hash, u128> {
+		// Children are always higher than their parents, so visiting the highest headers first
+		// computes every subtree before the header it hangs off.
+		let mut order: Vec<(&Hash, &Header)> = self.headers.iter().collect();
+		order.sort_by_key(|(_, header)| core::cmp::Reverse(header.height));
+		let mut totals = BTreeMap::new();
+		for (header_hash, _) in order {
+			let below: u128 = self
+				.children
+				.get(header_hash)
+				.map_or(0, |children| children.iter().map(|child| totals[child]).sum());
+			totals.insert(*header_hash, weight(*header_hash) + below);
+		}
+		totals
+	}
+
+	/// Starting from the root, step into the child with the largest total until reaching a leaf.
+	fn greedy_descent(&self, totals: &BTreeMap<Hash, u128>) -> Hash {
+		let mut head = self.root;
+		while let Some(children) = self.children.get(&head) {
+			// Pick the first child with the largest total. `max_by_key` would pick the last.
+			head = children.iter().copied().fold(children[0], |best, child| {
+				if totals[&child] > totals[&best] {
+					child
+				} else {
+					best
+				}
+			});
+		}
+		head
+	}
+}
+
+/// GHOST, the Greedy Heaviest Observed SubTree rule.
+///
+/// The heaviest chain rule only counts the work on the chain itself. When blocks are found faster
+/// than they spread through the network, many honest blocks end up as siblings of each other, and
+/// their work is wasted. An attacker mining privately wastes nothing, so it needs less than half
+/// of the hash power to outpace the honest chain.
+///
+/// GHOST counts that work too. Starting from the root, it steps into whichever child has the most
+/// work in its _whole subtree_, siblings and their descendants included, until it reaches a leaf.
+/// Every block that builds on a branch supports it, even if it did not end up on the chain.
+///
+/// Because it needs blocks that are not on any candidate chain, this rule works on a `BlockTree`
+/// rather than implementing `ForkChoice`. Ties go to the child that was imported first.
+///
+/// The GHOST rule was first published in 2013 by Yonatan Sompolinsky and Aviv Zohar.
+/// Learn more at https://eprint.iacr.org/2013/881.pdf
+pub struct GhostRule;
+
+impl GhostRule {
+	/// The hash of the head GHOST chooses.
+	pub fn best_head(tree: &BlockTree) -> Hash {
+		tree.greedy_descent(
+			&tree.subtree_totals(|header_hash| THRESHOLD.saturating_sub(header_hash) as u128),
+		)
+	}
+
+	/// The chain from the root to the head GHOST chooses.
+	pub fn best_chain(tree: &BlockTree) -> Vec<Header> {
+		tree.chain_to(Self::best_head(tree)).expect("the best head is always connected")
+	}
+}
+
+/// A validator's vote that the block with `target_hash` is the head of the chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Attestation {
+	pub validator: u32,
+	pub target_hash: Hash,
+}
 
-//
+/// The latest attestation from each validator. An attestation replaces the validator's earlier
+/// vote, so validators can switch to another branch whenever they change their minds.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VotePool {
+	latest: BTreeMap<u32, Hash>,
+}
+
+impl VotePool {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Record the attestation, returning the vote it replaces, if the validator had voted before.
+	pub fn attest(&mut self, attestation: Attestation) -> Option<Hash> {
+		self.latest.insert(attestation.validator, attestation.target_hash)
+	}
+
+	/// How many validators currently vote for exactly this block.
+	pub fn votes_for(&self, target_hash: Hash) -> u128 {
+		self.latest.values().filter(|vote| **vote == target_hash).count() as u128
+	}
+}
+
+/// LMD-GHOST, the Latest Message Driven GHOST rule.
+///
+/// In a proof of stake chain there is no work to count, but there are validators who say which
+/// head they prefer. LMD-GHOST walks the tree just like GHOST, except that each subtree is weighed
+/// by the number of validators whose _latest_ vote is for a block inside it. Only the latest vote
+/// counts, so a validator cannot support two branches at once, and a vote for a block also
+/// supports all of its ancestors.
+///
+/// Votes for blocks that are not in the tree yet are ignored until the block arrives. Ties go to
+/// the child that was imported first. Ethereum breaks ties by hash instead.
+///
+/// LMD-GHOST is the fork choice rule of Ethereum's beacon chain.
+/// Learn more at https://arxiv.org/abs/2003.03052
+pub struct LmdGhost;
+
+impl LmdGhost {
+	/// The hash of the head the votes choose.
+	pub fn best_head(tree: &BlockTree, votes: &VotePool) -> Hash {
+		tree.greedy_descent(&tree.subtree_totals(|header_hash| votes.votes_for(header_hash)))
+	}
+
+	/// The chain from the root to the head the votes choose.
+	pub fn best_chain(tree: &BlockTree, votes: &VotePool) -> Vec<Header> {
+		tree.chain_to(Self::best_head(tree, votes))
+			.expect("the best head is always connected")
+	}
+}
+
+/// A fork choice rule that scores a chain by adding up a score for each of its headers.
+///
+/// `LongestChainRule`, `HeaviestChainRule` and `MostBlocksWithEvenHash` work this way, which means
+/// a node does not have to rescore whole chains as headers arrive. The score of a chain ending in a
+/// new header is the score of the chain ending in its parent plus the new header's own score. See
+/// `IncrementalForkChoice`.
+pub trait AdditiveForkChoice: ForkChoice {
+	/// What a single header with this hash adds to its chain's score.
+	fn header_score(header_hash: Hash) -> u128;
+}
+
+impl AdditiveForkChoice for LongestChainRule {
+	fn header_score(_: Hash) -> u128 {
+		1
+	}
+}
+
+impl AdditiveForkChoice for HeaviestChainRule {
+	/// The same work per header that `chain_weight` adds up, so an unmined header such as genesis
+	/// scores zero.
+	fn header_score(header_hash: Hash) -> u128 {
+		THRESHOLD.saturating_sub(header_hash) as u128
+	}
+}
+
+impl AdditiveForkChoice for MostBlocksWithEvenHash {
+	fn header_score(header_hash: Hash) -> u128 {
+		(1 - (header_hash & 1)) as u128
+	}
+}
+
+/// Keep track of the best head while headers arrive one at a time.
+///
+/// Calling `best_chain` after every new header hashes every header of every candidate again, so
+/// following a chain of `n` headers costs `O(n²)` hashes. This remembers the score of the chain
+/// ending at each known header instead, so each import hashes once and does a single lookup.
+///
+/// Ties keep the head that was imported first, like `HeaviestChainRule::best_chain`. Headers whose
+/// parent is not known yet wait in an `OrphanPool`.
+pub struct IncrementalForkChoice<R> {
+	known: BTreeMap<Hash, Known>,
+	orphans: OrphanPool,
+	best: Hash,
+	_rule: PhantomData<R>,
+}
+
+/// What `IncrementalForkChoice` remembers about each header connected to the root.
+#[derive(Clone, Copy)]
+struct Known {
+	parent: Hash,
+	height: u64,
+	/// The score of the chain from the root to this header.
+	score: u128,
+}
+
+impl<R: AdditiveForkChoice> IncrementalForkChoice<R> {
+	/// Start following the chain from the given root, usually the genesis header.
+	pub fn new(root: &Header) -> Self {
+		let root_hash = hash(root);
+		let known =
+			Known { parent: root.parent, height: root.height, score: R::header_score(root_hash) };
+		IncrementalForkChoice {
+			known: BTreeMap::from([(root_hash, known)]),
+			orphans: OrphanPool::new(),
+			best: root_hash,
+			_rule: PhantomData,
+		}
+	}
+
+	/// Score a new header, and make it the best head if its chain is now the best. Returns whether
+	/// the header was connected, which it is not if its parent is unknown. Importing a header also
+	/// connects any orphans that were waiting for it.
+	///
+	/// A header whose height is not one more than its parent's is rejected, as is an orphan found
+	/// to have the wrong height once its parent arrives. Pruning relies on children being higher
+	/// than their parents.
+	pub fn import_header(&mut self, header: Header) -> bool {
+		match self.known.get(&header.parent) {
+			None => {
+				self.orphans.add(header);
+				return false;
+			},
+			Some(parent) if header.height != parent.height + 1 => return false,
+			Some(_) => {},
+		}
+		let mut ready = vec![header];
+		while let Some(header) = ready.pop() {
+			let header_hash = hash(&header);
+			if self.known.contains_key(&header_hash)
+				|| header.height != self.known[&header.parent].height + 1
+			{
+				continue;
+			}
+			let score = self.known[&header.parent].score + R::header_score(header_hash);
+			let known = Known { parent: header.parent, height: header.height, score };
+			self.known.insert(header_hash, known);
+			if score > self.known[&self.best].score {
+				self.best = header_hash;
+			}
+			ready.extend(self.orphans.take_children(header_hash));
+		}
+		true
+	}
+
+	/// The hash of the head of the best chain seen so far.
+	pub fn best_head(&self) -> Hash {
+		self.best
+	}
+}
+
+/// A fork choice rule that follows headers as they arrive, instead of comparing whole chains.
+///
+/// `ForkChoice` is the easiest way to think about fork choice, but a real client never holds a
+/// neat list of candidate chains. It receives headers one at a time, sometimes before their
+/// parents, and it cannot remember every fork forever. This trait is the interface such a client
+/// needs: import whatever arrives, ask for the best head at any time, and forget old history once
+/// it is final.
+pub trait StatefulForkChoice {
+	/// Add a header. A header whose parent is unknown waits in an orphan pool until the parent
+	/// is imported.
+	fn import(&mut self, header: Header);
+
+	/// The hash of the head of the best chain.
+	fn best_head(&self) -> Hash;
+
+	/// Treat the best chain's header at this height as final. Forget every header below it, every
+	/// branch that does not descend from it, and every orphan at or below its height, since those
+	/// could only connect below it. The best head itself is never forgotten, so heights above it
+	/// are treated as its height.
+	fn prune_below(&mut self, height: u64);
+}
+
+impl<R: AdditiveForkChoice> StatefulForkChoice for IncrementalForkChoice<R> {
+	fn import(&mut self, header: Header) {
+		self.import_header(header);
+	}
+
+	fn best_head(&self) -> Hash {
+		self.best
+	}
+
+	fn prune_below(&mut self, height: u64) {
+		// Walk back from the best head to the new root.
+		let mut root = self.best;
+		while self.known[&root].height > height {
+			match self.known.get(&self.known[&root].parent) {
+				Some(_) => root = self.known[&root].parent,
+				None => break,
+			}
+		}
+		let root_height = self.known[&root].height;
+
+		// Parents are always lower than their children, so visiting the headers by height finds
+		// every parent's fate before its children's.
+		let mut headers: Vec<(Hash, Known)> =
+			core::mem::take(&mut self.known).into_iter().collect();
+		headers.sort_by_key(|(_, known)| known.height);
+		for (header_hash, known) in headers {
+			if header_hash == root || self.known.contains_key(&known.parent) {
+				self.known.insert(header_hash, known);
+			}
+		}
+		// An orphan at the root's height or lower could only connect below the root.
+		self.orphans.prune_below(root_height + 1);
+	}
+}
 
 /// Build and return a valid chain with the given number of blocks.
 fn build_valid_chain(n: u64) -> Vec<Header> {
@@ -222,7 +1070,7 @@ fn bc_5_longest_chain() {
 
 	assert!(LongestChainRule::first_chain_is_better(chain_1, chain_2));
 
-	assert_eq!(LongestChainRule::best_chain(&[chain_1, chain_2]), chain_1);
+	assert_eq!(LongestChainRule::best_chain(&[chain_1, chain_2]), Ok(&chain_1[..]));
 }
 
 #[test]
@@ -239,21 +1087,58 @@ fn bc_5_mine_to_custom_difficulty() {
 	assert!(hash(&b1.header) < custom_threshold);
 }
 
+#[test]
+fn bc_5_mine_extra_hard_finds_the_first_valid_digest() {
+	let threshold = u64::MAX / 1000;
+	let mut header = Block::genesis().child(vec![1, 2, 3]).header;
+	let unmined = header.clone();
+	mine_extra_hard(&mut header, threshold);
+
+	// However the search was split across threads, no earlier digest was good enough.
+	assert!(hash(&header) <= threshold);
+	assert!((unmined.consensus_digest..header.consensus_digest).all(|digest| {
+		hash(&Header { consensus_digest: digest, ..unmined.clone() }) > threshold
+	}));
+	assert_eq!(Header { consensus_digest: unmined.consensus_digest, ..header }, unmined);
+}
+
+/// Compare the time to mine at a hard threshold with one worker and with every core. The threshold
+/// is calibrated so that one worker takes about a quarter of a second per block. Run it with
+/// `cargo test --release bc_5_parallel_mining_speedup -- --ignored --nocapture`.
+#[cfg(feature = "std")]
+#[test]
+#[ignore]
+fn bc_5_parallel_mining_speedup() {
+	use crate::mining::search_parallel;
+	use std::time::{Duration, Instant};
+
+	let threshold = super::calibration::threshold_for(Duration::from_millis(250));
+	let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+	for workers in [1, cores] {
+		let started = Instant::now();
+		for extrinsic in 0..8 {
+			let header = Block::genesis().child(vec![extrinsic]).header;
+			search_parallel(header.consensus_digest, workers, &|digest| {
+				hash(&Header { consensus_digest: digest, ..header.clone() }) <= threshold
+			});
+		}
+		println!("{workers} worker(s): {:?}", started.elapsed());
+	}
+}
+
 #[test]
 fn bc_5_heaviest_chain() {
 	let g = Header::genesis();
 
-	let mut i = 0;
-	let h_a1 = loop {
-		let header = g.child(hash(&[i]), i);
-		// Extrinsics root hash must be higher than threshold (less work done)
-		if hash(&header) > THRESHOLD {
-			break header
-		}
-		i += 1;
-	};
+	// `child` always mines below the threshold, so keep changing the digest until the hash is
+	// higher than threshold (less work done)
+	let mut h_a1 = g.child(hash(&[0]), 0);
+	while hash(&h_a1) <= THRESHOLD {
+		h_a1.consensus_digest += 1;
+	}
 	let chain_1 = &[g.clone(), h_a1];
 
+	let mut i = 1;
 	let h_b1 = loop {
 		let header = g.child(hash(&[i]), i);
 		// Extrinsics root hash must be lower than threshold (more work done)
@@ -266,7 +1151,8 @@ fn bc_5_heaviest_chain() {
 
 	assert!(HeaviestChainRule::first_chain_is_better(chain_2, chain_1));
 
-	assert_eq!(HeaviestChainRule::best_chain(&[chain_1, chain_2]), chain_2);
+	assert_eq!(HeaviestChainRule::best_chain(&[chain_1, chain_2]), Ok(&chain_2[..]));
+	assert_eq!(HeaviestChainRule::best_chain(&[&chain_1[1..], &chain_2[1..]]), Ok(&chain_2[1..]));
 }
 
 #[test]
@@ -307,7 +1193,7 @@ fn bc_5_most_even_blocks() {
 
 	assert!(MostBlocksWithEvenHash::first_chain_is_better(chain_1, chain_2));
 
-	assert_eq!(MostBlocksWithEvenHash::best_chain(&[chain_1, chain_2]), chain_1);
+	assert_eq!(MostBlocksWithEvenHash::best_chain(&[chain_1, chain_2]), Ok(&chain_1[..]));
 }
 
 #[test]
@@ -316,11 +1202,606 @@ fn bc_5_longest_vs_heaviest() {
 
 	assert!(LongestChainRule::first_chain_is_better(&longest_chain, &pow_chain));
 
-	assert_eq!(LongestChainRule::best_chain(&[&longest_chain, &pow_chain]), &longest_chain);
+	assert_eq!(LongestChainRule::best_chain(&[&longest_chain, &pow_chain]), Ok(&longest_chain[..]));
 
 	let (_, longest_chain, pow_chain) = create_fork_one_side_longer_other_side_heavier();
 
 	assert!(HeaviestChainRule::first_chain_is_better(&pow_chain, &longest_chain));
 
-	// assert_eq!(HeaviestChainRule::best_chain(&[&longest_chain, &pow_chain]), &pow_chain);
+	assert_eq!(HeaviestChainRule::best_chain(&[&longest_chain, &pow_chain]), Ok(&pow_chain[..]));
+}
+
+#[test]
+fn bc_5_ghost_prefers_the_bushier_subtree() {
+	let g = Header::genesis();
+	// One branch has a single block with many competing children, the other is a longer chain.
+	let a1 = g.child(1, 1);
+	let siblings: Vec<Header> = (0..8).map(|i| a1.child(i, i)).collect();
+	let mut b = vec![g.child(2, 2)];
+	for i in 0..3 {
+		let next = b[i].child(i as u64, i as u64);
+		b.push(next);
+	}
+
+	let mut tree = BlockTree::new(g.clone());
+	assert!(tree.import(a1.clone()));
+	for header in siblings.iter().chain(&b) {
+		assert!(tree.import(header.clone()));
+	}
+	let ghost = GhostRule::best_chain(&tree);
+
+	assert_eq!(tree.len(), 1 + 1 + 8 + 4);
+	assert_eq!(ghost.len(), 3);
+	assert_eq!(ghost[1], a1);
+	assert!(siblings.contains(&ghost[2]));
+
+	// The heaviest chain rule only sees the chains themselves, and prefers the longer one.
+	let b_chain = tree.chain_to(hash(&b[3])).unwrap();
+	assert_eq!(HeaviestChainRule::best_chain(&[&ghost, &b_chain]), Ok(&b_chain[..]));
+}
+
+#[test]
+fn bc_5_ghost_tree_connects_orphans() {
+	let g = Header::genesis();
+	let h1 = g.child(1, 1);
+	let h2 = h1.child(2, 3);
+	let mut tree = BlockTree::new(g.clone());
+
+	assert!(!tree.import(h2.clone()));
+	assert_eq!(tree.orphan_count(), 1);
+	assert_eq!(tree.chain_to(hash(&h2)), None);
+	assert_eq!(GhostRule::best_head(&tree), hash(&g));
+
+	assert!(tree.import(h1.clone()));
+	assert_eq!(tree.orphan_count(), 0);
+	assert_eq!(tree.len(), 3);
+	assert_eq!(GhostRule::best_chain(&tree), vec![g, h1, h2]);
+}
+
+#[test]
+fn bc_5_block_tree_rejects_wrong_heights() {
+	let g = Header::genesis();
+	let h1 = g.child(1, 1);
+	let h2 = h1.child(2, 3);
+	let mut tree = BlockTree::new(g.clone());
+	assert!(tree.import(h1.clone()));
+
+	// Headers that skip a height, or repeat their parent's.
+	assert!(!tree.import(Header { height: 3, ..h1.child(2, 3) }));
+	assert!(!tree.import(Header { height: 1, ..h1.child(4, 4) }));
+	assert_eq!(tree.len(), 2);
+
+	// An orphan has no parent to be checked against yet, so it is dropped once the parent arrives.
+	assert!(!tree.import(Header { height: 1, ..h2.child(5, 5) }));
+	assert!(tree.import(h2.clone()));
+	assert_eq!(tree.len(), 3);
+	assert_eq!(tree.orphan_count(), 0);
+	assert_eq!(GhostRule::best_chain(&tree), vec![g, h1, h2]);
+}
+
+#[test]
+fn bc_5_lmd_ghost_follows_switching_votes() {
+	let g = Header::genesis();
+	let a1 = g.child(1, 1);
+	let a2 = a1.child(1, 2);
+	let b1 = g.child(2, 2);
+	let b2 = b1.child(1, 3);
+	let b3 = b1.child(2, 4);
+	let mut tree = BlockTree::new(g.clone());
+	for header in [&a1, &a2, &b1, &b2, &b3] {
+		assert!(tree.import(header.clone()));
+	}
+	let mut votes = VotePool::new();
+	let vote = |validator, target: &Header| Attestation { validator, target_hash: hash(target) };
+
+	// With no votes every subtree weighs nothing, and the first imported children win.
+	assert_eq!(LmdGhost::best_head(&tree, &votes), hash(&a2));
+
+	// Three validators on the a branch against two on the b branch.
+	for validator in 0..3 {
+		assert_eq!(votes.attest(vote(validator, &a2)), None);
+	}
+	votes.attest(vote(3, &b2));
+	votes.attest(vote(4, &b3));
+	assert_eq!(LmdGhost::best_chain(&tree, &votes), vec![g.clone(), a1.clone(), a2.clone()]);
+
+	// Two validators switch, so the b branch has four votes. Within it, b3 now has three.
+	assert_eq!(votes.attest(vote(0, &b3)), Some(hash(&a2)));
+	votes.attest(vote(1, &b3));
+	assert_eq!(votes.votes_for(hash(&a2)), 1);
+	assert_eq!(LmdGhost::best_chain(&tree, &votes), vec![g, b1, b3.clone()]);
+
+	// A vote for an unknown block counts for nothing, so moving there weakens b3.
+	votes.attest(vote(4, &b3.child(1, 5)));
+	votes.attest(vote(0, &b2));
+	assert_eq!(LmdGhost::best_head(&tree, &votes), hash(&b2));
+}
+
+#[test]
+fn bc_5_longest_chain_tie_breaks_agree_across_nodes() {
+	let g = Header::genesis();
+	let (a, b) = ([g.clone(), g.child(1, 1)], [g.clone(), g.child(2, 2)]);
+	let lower_tip = if hash(&a[1]) < hash(&b[1]) { &a } else { &b };
+	let a_has_more_work = HeaviestChainRule::first_chain_is_better(&a[1..], &b[1..]);
+	let more_work = if a_has_more_work { &a } else { &b };
+
+	// Two nodes that received the chains in opposite orders.
+	let node_1 = [&a[..], &b[..]];
+	let node_2 = [&b[..], &a[..]];
+
+	assert_ne!(LongestChainRule::best_chain(&node_1), LongestChainRule::best_chain(&node_2));
+	for view in [node_1, node_2] {
+		assert_eq!(LongestChain::<LowestTipHash>::best_chain(&view), Ok(&lower_tip[..]));
+		assert_eq!(LongestChain::<MostWork>::best_chain(&view), Ok(&more_work[..]));
+	}
+	assert!(LongestChain::<LowestTipHash>::first_chain_is_better(lower_tip, &a));
+	assert!(LongestChain::<LowestTipHash>::first_chain_is_better(lower_tip, &b));
+
+	// Length still comes first.
+	let longer = [g.clone(), g.child(3, 3), g.child(3, 3).child(4, 4)];
+	assert_eq!(LongestChain::<LowestTipHash>::best_chain(&[&a, &longer, &b]), Ok(&longer[..]));
+	assert!(!LongestChain::<MostWork>::first_chain_is_better(&a, &longer));
+}
+
+#[test]
+fn bc_5_scores_explain_the_choice() {
+	let (_, longest_chain, pow_chain) = create_fork_one_side_longer_other_side_heavier();
+	let (longest_chain, pow_chain) = (&longest_chain[..], &pow_chain[..]);
+	let work = |chain: &[Header]| -> u128 {
+		chain.iter().map(|header| HeaviestChainRule::header_score(hash(header))).sum()
+	};
+
+	assert_eq!(LongestChainRule::score(longest_chain), longest_chain.len() as u128);
+	assert!(LongestChainRule::score(longest_chain) > LongestChainRule::score(pow_chain));
+	assert_eq!(HeaviestChainRule::score(pow_chain), work(pow_chain));
+	assert!(HeaviestChainRule::score(pow_chain) > HeaviestChainRule::score(longest_chain));
+	assert_eq!(
+		Lexicographic::<HeaviestChainRule, LongestChainRule>::score(pow_chain),
+		HeaviestChainRule::score(pow_chain)
+	);
+
+	// Genesis is not mined, so it adds no work instead of overflowing.
+	assert_eq!(HeaviestChainRule::score(&[Header::genesis()]), 0);
+	let evens = longest_chain.iter().filter(|header| hash(header) % 2 == 0).count();
+	assert_eq!(MostBlocksWithEvenHash::score(longest_chain), evens as u128);
+}
+
+#[test]
+fn bc_5_best_valid_chain_skips_invalid_candidates() {
+	let g = Block::genesis();
+	let valid = [g.clone(), g.child(vec![1]), g.child(vec![1]).child(vec![2])];
+	// A longer chain whose second block claims a state its extrinsics do not produce.
+	let mut invalid = vec![g.clone(), g.child(vec![3])];
+	invalid.push(Block { header: invalid[1].header.child(0, 99), body: vec![] });
+	invalid.push(invalid[2].child(vec![]));
+
+	assert_eq!(best_valid_chain::<LongestChainRule>(&[&invalid, &valid]), Ok(&valid[..]));
+	assert_eq!(
+		best_valid_chain::<LongestChainRule>(&[&invalid, &[]]),
+		Err(ForkChoiceError::NoValidCandidate)
+	);
+	assert_eq!(best_valid_chain::<LongestChainRule>(&[]), Err(ForkChoiceError::NoCandidates));
+	assert_eq!(best_valid_chain::<HeaviestChainRule>(&[&invalid, &valid]), Ok(&valid[..]));
+	// The rule is only given the valid candidate, but its error names the candidate's place among
+	// all of them.
+	assert_eq!(
+		best_valid_chain::<MinedOnly>(&[&invalid, &valid]),
+		Err(ForkChoiceError::Incomparable(1))
+	);
+}
+
+#[test]
+fn bc_5_bounded_reorg_keeps_deep_blocks() {
+	let g = Header::genesis();
+	let mut current = vec![g.clone()];
+	for i in 0..5 {
+		let next = current[i].child(0, i as u64);
+		current.push(next);
+	}
+	// Longer chains that fork after block 1, reverting four blocks, and after block 3, reverting
+	// two.
+	let mut deep_fork = current[..2].to_vec();
+	deep_fork.extend(add_fork(&current[1], 8, false));
+	let mut shallow_fork = current[..4].to_vec();
+	shallow_fork.extend(add_fork(&current[3], 4, false));
+	let mut rule = BoundedReorg::<LongestChainRule>::new(3);
+
+	assert!(rule.allows(&deep_fork));
+	rule.update_head(&current);
+	assert!(!rule.allows(&deep_fork));
+	assert_eq!(rule.best_chain(&[&current, &deep_fork]), Ok(&current[..]));
+	assert_eq!(rule.best_chain(&[&deep_fork]), Err(ForkChoiceError::ReorgTooDeep));
+	assert_eq!(rule.best_chain(&[&current, &deep_fork, &shallow_fork]), Ok(&shallow_fork[..]));
+	rule.update_head(&shallow_fork);
+
+	// The old chain is now a fork itself, and returning to it reverts four blocks.
+	assert!(!rule.allows(&current));
+	assert_eq!(rule.best_chain(&[]), Err(ForkChoiceError::NoCandidates));
+}
+
+#[test]
+fn bc_5_work_of_thousands_of_headers_does_not_overflow() {
+	let chain = build_valid_chain(3_000);
+	let (long, short) = (&chain[1..], &chain[1..2_000]);
+	let weight = HeaviestChainRule::chain_weight(long);
+
+	assert!(weight > u64::MAX as u128);
+	assert_eq!(weight, HeaviestChainRule::score(long));
+	// Genesis was never mined, so it adds no work.
+	assert_eq!(HeaviestChainRule::chain_weight(&chain), weight);
+	assert!(HeaviestChainRule::first_chain_is_better(long, short));
+	assert!(!HeaviestChainRule::first_chain_is_better(short, long));
+	assert_eq!(HeaviestChainRule::best_chain(&[short, long, &chain[1..10]]), Ok(long));
+}
+
+/// The heaviest chain rule, except that it refuses to score a chain with a header that was never
+/// mined. It checks that rules wrapping another report its errors by the caller's indices.
+#[cfg(test)]
+struct MinedOnly;
+
+#[cfg(test)]
+impl ForkChoice for MinedOnly {
+	fn first_chain_is_better(chain_1: &[Header], chain_2: &[Header]) -> bool {
+		HeaviestChainRule::first_chain_is_better(chain_1, chain_2)
+	}
+
+	fn best_chain<'a>(candidate_chains: &[&'a [Header]]) -> Result<&'a [Header], ForkChoiceError> {
+		let unmined = |chain: &[Header]| chain.iter().any(|header| hash(header) > THRESHOLD);
+		match candidate_chains.iter().position(|chain| unmined(chain)) {
+			Some(index) => Err(ForkChoiceError::Incomparable(index)),
+			None => HeaviestChainRule::best_chain(candidate_chains),
+		}
+	}
+}
+
+/// A header that counts how often it is hashed.
+#[cfg(test)]
+struct CountedHeader<'a>(Header, &'a core::cell::Cell<usize>);
+
+#[cfg(test)]
+impl StdHash for CountedHeader<'_> {
+	fn hash<S: core::hash::Hasher>(&self, state: &mut S) {
+		self.1.set(self.1.get() + 1);
+		self.0.hash(state);
+	}
+}
+
+#[test]
+fn bc_5_heaviest_best_chain_hashes_each_header_once() {
+	let hashes = core::cell::Cell::new(0);
+	let chain: Vec<CountedHeader> = build_valid_chain(1_000)[1..]
+		.iter()
+		.map(|header| CountedHeader(header.clone(), &hashes))
+		.collect();
+	// Many candidates that all share a prefix of the same headers.
+	let candidates: Vec<&[CountedHeader]> = (1..=10).map(|i| &chain[..i * 99]).collect();
+
+	assert_eq!(HeaviestChainRule::best_chain(&candidates).map(<[_]>::len), Ok(990));
+	assert_eq!(hashes.get(), 990);
+}
+
+#[test]
+fn bc_5_stateful_fork_choice_connects_orphans() {
+	let chain = build_valid_chain(6);
+	let mut rule = IncrementalForkChoice::<LongestChainRule>::new(&chain[0]);
+
+	for header in [&chain[3], &chain[5], &chain[4], &chain[5]] {
+		rule.import(header.clone());
+	}
+	assert_eq!(StatefulForkChoice::best_head(&rule), hash(&chain[0]));
+	// The same orphan heard of twice waits only once.
+	assert_eq!(rule.orphans.len(), 3);
+
+	rule.import(chain[1].clone());
+	rule.import(chain[2].clone());
+	assert_eq!(StatefulForkChoice::best_head(&rule), hash(&chain[5]));
+	assert!(rule.orphans.is_empty());
+	assert_eq!(rule.known.len(), 6);
+}
+
+#[test]
+fn bc_5_stateful_fork_choice_prunes_final_history() {
+	let chain = build_valid_chain(6);
+	let fork = add_fork(&chain[1], 2, false);
+	let mut rule = IncrementalForkChoice::<LongestChainRule>::new(&chain[0]);
+	for header in chain[1..].iter().chain(&fork) {
+		rule.import(header.clone());
+	}
+	// An orphan that could only connect below the pruning height, and one that might still
+	// connect above it.
+	rule.import(chain[0].child(7, 7).child(8, 8));
+	rule.import(chain[5].child(9, 9).child(10, 10));
+	assert_eq!(rule.known.len(), 6 + 2);
+
+	rule.prune_below(3);
+	let mut remaining: Vec<Hash> = rule.known.keys().copied().collect();
+	remaining.sort();
+	let mut expected: Vec<Hash> = chain[3..].iter().map(hash).collect();
+	expected.sort();
+	assert_eq!(remaining, expected);
+	assert_eq!(rule.orphans.len(), 1);
+	assert_eq!(StatefulForkChoice::best_head(&rule), hash(&chain[5]));
+
+	// The chain keeps growing from what is left, and pruning past the head keeps the head.
+	rule.import(chain[5].child(9, 9));
+	assert_eq!(rule.known.len(), 5);
+	rule.prune_below(100);
+	assert_eq!(rule.known.len(), 1);
+	assert_eq!(StatefulForkChoice::best_head(&rule), hash(&chain[5].child(9, 9).child(10, 10)));
+}
+
+#[test]
+fn bc_5_compare_chains_explains_the_winner() {
+	let (prefix, longest_chain, pow_chain) = create_fork_one_side_longer_other_side_heavier();
+	let by_length = compare_chains::<LongestChainRule>(&longest_chain, &pow_chain);
+	let by_work = compare_chains::<HeaviestChainRule>(&longest_chain, &pow_chain);
+
+	assert_eq!(by_length.winner, Winner::First);
+	assert_eq!(by_length.reason, Reason::HigherScore);
+	assert_eq!(by_length.lengths, (4, 3));
+	assert_eq!(by_length.scores, (4, 3));
+	// `add_fork` starts both forks with the same child of the prefix, so they diverge after it.
+	assert_eq!(by_length.divergence_height, Some(prefix[prefix.len() - 1].height + 1));
+	assert_eq!(by_work.winner, Winner::Second);
+	assert_eq!(by_work.weights, by_length.weights);
+	assert_eq!(by_work.scores, by_work.weights);
+	assert!(by_work.weights.1 > by_work.weights.0);
+
+	// Two chains of the same length tie, and the longest chain rule keeps the first.
+	let tip = &prefix[prefix.len() - 1];
+	let tied = compare_chains::<LongestChainRule>(&[tip.child(1, 1)], &[tip.child(2, 2)]);
+	assert_eq!((tied.winner, tied.reason), (Winner::First, Reason::Tie));
+	assert_eq!(tied.divergence_height, Some(tip.height));
+	let unrelated = compare_chains::<LongestChainRule>(&prefix[..1], &pow_chain);
+	assert_eq!(unrelated.divergence_height, None);
+}
+
+#[test]
+fn bc_5_lexicographic_falls_back_on_ties() {
+	type LengthThenWork = Lexicographic<LongestChainRule, HeaviestChainRule>;
+	type WorkThenLength = Lexicographic<HeaviestChainRule, LongestChainRule>;
+	let (prefix, longest_chain, pow_chain) = create_fork_one_side_longer_other_side_heavier();
+
+	assert!(LengthThenWork::first_chain_is_better(&longest_chain, &pow_chain));
+	assert!(WorkThenLength::first_chain_is_better(&pow_chain, &longest_chain));
+
+	// Two single block chains are the same length, so only their work decides.
+	let tip = &prefix[prefix.len() - 1];
+	let (a, b) = ([tip.child(1, 1)], [tip.child(2, 2)]);
+	let heavier_first = HeaviestChainRule::first_chain_is_better(&a, &b);
+	assert_eq!(LengthThenWork::first_chain_is_better(&a, &b), heavier_first);
+	assert_eq!(LengthThenWork::first_chain_is_better(&b, &a), !heavier_first);
+}
+
+#[test]
+fn bc_5_lexicographic_with_a_strict_rule() {
+	type EvenThenWork = Lexicographic<MostBlocksWithEvenHash, HeaviestChainRule>;
+	let g = Header::genesis();
+	// Two blocks with even hashes tie on the first rule, which never prefers either chain.
+	let even: Vec<[Header; 1]> = (0..)
+		.map(|i| [g.child(i, i)])
+		.filter(|c| hash(&c[0]) % 2 == 0)
+		.take(2)
+		.collect();
+	let odd = (0..).map(|i| [g.child(i, i)]).find(|c| hash(&c[0]) % 2 == 1).unwrap();
+	let (a, b) = (&even[0], &even[1]);
+
+	assert!(!MostBlocksWithEvenHash::first_chain_is_better(a, b));
+	assert!(!MostBlocksWithEvenHash::first_chain_is_better(b, a));
+	assert_eq!(
+		EvenThenWork::first_chain_is_better(a, b),
+		HeaviestChainRule::first_chain_is_better(a, b)
+	);
+	assert!(EvenThenWork::first_chain_is_better(a, &odd));
+	assert!(!EvenThenWork::first_chain_is_better(&odd, a));
+	assert_eq!(EvenThenWork::best_chain(&[&odd, a]), Ok(&a[..]));
+}
+
+#[test]
+fn bc_5_incremental_matches_best_chain() {
+	let chain = build_valid_chain(6);
+	let short_fork = add_fork(&chain[2], 2, false);
+	let long_fork = add_fork(&chain[1], 6, false);
+	let mut longest = IncrementalForkChoice::<LongestChainRule>::new(&chain[0]);
+	let mut heaviest = IncrementalForkChoice::<HeaviestChainRule>::new(&chain[0]);
+	for header in chain[1..].iter().chain(&short_fork).chain(&long_fork) {
+		assert!(longest.import_header(header.clone()));
+		assert!(heaviest.import_header(header.clone()));
+	}
+	assert!(!longest.import_header(Header::genesis().child(0, 0).child(0, 0)));
+
+	// Genesis is shared by every candidate and adds no work, so leaving it out changes nothing.
+	let candidates: [Vec<Header>; 3] = [
+		chain[1..].to_vec(),
+		[&chain[1..3], &short_fork[..]].concat(),
+		[&chain[1..2], &long_fork[..]].concat(),
+	];
+	let candidates: Vec<&[Header]> = candidates.iter().map(Vec::as_slice).collect();
+	let tip = |chain: &[Header]| hash(&chain[chain.len() - 1]);
+
+	assert_eq!(longest.best_head(), tip(LongestChainRule::best_chain(&candidates).unwrap()));
+	assert_eq!(heaviest.best_head(), tip(HeaviestChainRule::best_chain(&candidates).unwrap()));
+}
+
+#[test]
+fn bc_5_incremental_rejects_wrong_heights() {
+	let chain = build_valid_chain(3);
+	let mut rule = IncrementalForkChoice::<LongestChainRule>::new(&chain[0]);
+	assert!(rule.import_header(chain[1].clone()));
+
+	// A header that skips heights.
+	assert!(!rule.import_header(Header { height: 50, ..chain[1].child(7, 7) }));
+	// An orphan is checked once its parent arrives.
+	assert!(!rule.import_header(Header { height: 50, ..chain[2].child(7, 7) }));
+	assert!(rule.import_header(chain[2].clone()));
+	assert_eq!(rule.best_head(), hash(&chain[2]));
+	assert_eq!(rule.known.len(), 3);
+	assert!(rule.orphans.is_empty());
+}
+
+/// Compare following a growing chain by calling `best_chain` after every header with following it
+/// incrementally. Run it with
+/// `cargo test --release bc_5_incremental_fork_choice_speedup -- --ignored --nocapture`.
+#[cfg(feature = "std")]
+#[test]
+#[ignore]
+fn bc_5_incremental_fork_choice_speedup() {
+	use std::time::Instant;
+
+	for length in [100, 200, 400, 800] {
+		let chain = build_valid_chain(length);
+		// A rival branch from genesis, so that there is always a choice to make.
+		let rival = add_fork(&chain[0], 3, false);
+
+		let started = Instant::now();
+		for end in 2..chain.len() {
+			let _ = core::hint::black_box(HeaviestChainRule::best_chain(&[&chain[1..end], &rival]));
+		}
+		let from_scratch = started.elapsed();
+
+		let started = Instant::now();
+		let mut incremental = IncrementalForkChoice::<HeaviestChainRule>::new(&chain[0]);
+		for header in rival.iter().chain(&chain[1..]) {
+			incremental.import_header(header.clone());
+			core::hint::black_box(incremental.best_head());
+		}
+		let streamed = started.elapsed();
+
+		println!("{length} headers: from scratch {from_scratch:?}, incremental {streamed:?}");
+	}
+}
+
+#[test]
+fn bc_5_best_chain_errors() {
+	let g = Header::genesis();
+	let mined = [g.child(1, 1)];
+	let unmined = [g.clone()];
+
+	let none: [&[Header]; 0] = [];
+	assert_eq!(LongestChainRule::best_chain(&none), Err(ForkChoiceError::NoCandidates));
+	assert_eq!(HeaviestChainRule::best_chain(&none), Err(ForkChoiceError::NoCandidates));
+	assert_eq!(MostBlocksWithEvenHash::best_chain(&none), Err(ForkChoiceError::NoCandidates));
+
+	// Genesis is not mined, so it counts as no work rather than failing.
+	assert!(hash(&g) > THRESHOLD);
+	assert_eq!(HeaviestChainRule::score(&unmined), 0);
+	assert_eq!(HeaviestChainRule::best_chain(&[&unmined, &mined]), Ok(&mined[..]));
+	assert_eq!(LongestChainRule::best_chain(&[&mined, &unmined]), Ok(&mined[..]));
+	assert_eq!(MinedOnly::best_chain(&[&mined, &unmined]), Err(ForkChoiceError::Incomparable(1)));
+}
+
+#[test]
+fn bc_5_finalized_base_ignores_forks_below_finality() {
+	let chain = build_valid_chain(5);
+	// A longer fork that leaves the chain before the finalized block, and a short one after it.
+	let reverting = [&chain[..2], &add_fork(&chain[1], 6, false)[..]].concat();
+	let building = [&chain[..4], &add_fork(&chain[3], 1, false)[..]].concat();
+	let finality = FinalizedBase::<LongestChainRule>::new(&chain[2]);
+
+	assert!(finality.descends_from_finalized(&chain));
+	assert!(finality.descends_from_finalized(&chain[2..]));
+	assert!(finality.descends_from_finalized(&chain[3..]));
+	assert!(!finality.descends_from_finalized(&chain[4..]));
+	assert!(!finality.descends_from_finalized(&chain[..2]));
+	assert!(!finality.descends_from_finalized(&reverting));
+
+	let candidates = [&reverting[..], &chain[..], &building[..]];
+	assert_eq!(LongestChainRule::best_chain(&candidates), Ok(&reverting[..]));
+	assert_eq!(finality.best_chain(&candidates), Ok(&chain[..]));
+	assert_eq!(finality.best_chain(&[&reverting]), Err(ForkChoiceError::NoFinalizedDescendant));
+	assert_eq!(finality.best_chain(&[]), Err(ForkChoiceError::NoCandidates));
+}
+
+#[test]
+fn bc_5_finalized_base_reports_original_indices() {
+	let chain = build_valid_chain(3);
+	let finality = FinalizedBase::<MinedOnly>::new(&chain[1]);
+	let rival = [chain[0].child(5, 5)];
+
+	// The rival is filtered out, and genesis is not mined, so the inner rule refuses to score the
+	// remaining chain. The error still names it by its place among all the candidates.
+	assert_eq!(finality.best_chain(&[&rival, &chain]), Err(ForkChoiceError::Incomparable(1)));
+	assert_eq!(finality.best_chain(&[&rival, &chain[1..]]), Ok(&chain[1..]));
+}
+
+/// A chain of consensus chapter headers sealed by the given engine, after a genesis header.
+#[cfg(test)]
+fn consensus_chain<C: crate::c3_consensus::Consensus>(
+	engine: &C,
+	genesis_digest: C::Digest,
+	length: u64,
+	salt: u64,
+) -> Vec<crate::c3_consensus::Header<C::Digest>> {
+	use crate::c3_consensus::Header as ConsensusHeader;
+
+	let genesis = ConsensusHeader {
+		parent: 0,
+		height: 0,
+		state_root: 0,
+		extrinsics_root: 0,
+		consensus_digest: (),
+	};
+	let mut chain = vec![genesis.convert_to_digest(genesis_digest)];
+	for height in 1..=length {
+		let parent = &chain[chain.len() - 1];
+		let partial = ConsensusHeader {
+			parent: hash(parent),
+			height,
+			state_root: salt,
+			extrinsics_root: 0,
+			consensus_digest: (),
+		};
+		let sealed = engine.seal(&parent.consensus_digest, partial).expect("the engine can seal");
+		chain.push(sealed);
+	}
+	chain
+}
+
+#[test]
+fn bc_5_rules_work_on_consensus_headers() {
+	use crate::c3_consensus::{p1_pow::PoW, p3_poa::SimplePoa, ConsensusAuthority};
+
+	let poa = SimplePoa { authorities: vec![ConsensusAuthority::Alice] };
+	let long = consensus_chain(&poa, ConsensusAuthority::Alice, 3, 1);
+	let short = consensus_chain(&poa, ConsensusAuthority::Alice, 2, 2);
+	assert_eq!(LongestChainRule::best_chain(&[&short, &long]), Ok(&long[..]));
+	assert!(!MostBlocksWithEvenHash::first_chain_is_better(&short, &short));
+
+	// Proof of work headers have work, so the heaviest chain rule can compare them too. Genesis
+	// is not mined, so leave it out.
+	let pow = PoW { threshold: THRESHOLD };
+	let long = consensus_chain(&pow, 0, 6, 1);
+	let short = consensus_chain(&pow, 0, 1, 2);
+	assert_eq!(HeaviestChainRule::best_chain(&[&short[1..], &long[1..]]), Ok(&long[1..]));
+	assert_eq!(
+		Lexicographic::<LongestChainRule, HeaviestChainRule>::best_chain(&[&short, &long]),
+		Ok(&long[..])
+	);
+}
+
+#[test]
+fn bc_5_justified_checkpoint_beats_work() {
+	let chain = build_valid_chain(6);
+	// A fork from block 2 that is both longer and mined harder than the main chain.
+	let fork = [&chain[1..3], &add_fork(&chain[2], 5, true)[..]].concat();
+	let main = &chain[1..];
+	let checkpoint =
+		|header: &Header, votes| Checkpoint { height: header.height, hash: hash(header), votes };
+	let mut rule =
+		JustifiedCheckpointRule { checkpoints: vec![checkpoint(&chain[4], 60)], total_votes: 100 };
+
+	// Sixty percent is not enough to justify, so the heaviest chain wins.
+	assert_eq!(rule.justified_height(main), None);
+	assert_eq!(HeaviestChainRule::best_chain(&[main, &fork]), Ok(&fork[..]));
+	assert_eq!(rule.best_chain(&[main, &fork]), Ok(&fork[..]));
+
+	rule.checkpoints[0].votes = 67;
+	assert_eq!(rule.justified_height(main), Some(4));
+	assert_eq!(rule.best_chain(&[main, &fork]), Ok(main));
+
+	// A justified checkpoint on the fork only helps it if it is the highest one.
+	rule.checkpoints.push(checkpoint(&fork[2], 100));
+	assert_eq!(rule.justified_height(&fork), Some(3));
+	assert_eq!(rule.best_chain(&[&fork, main]), Ok(main));
+	assert_eq!(rule.best_chain(&[]), Err(ForkChoiceError::NoCandidates));
 }
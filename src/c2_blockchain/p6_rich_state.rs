@@ -12,9 +12,24 @@
 //!
 //! This notion of state may sound familiar from our previous work on state machines. Indeed this
 //! naming coincidence foreshadows a key abstraction that we will make in a coming chapter.
-
-type Hash = u64;
-use crate::hash;
+//!
+//! A state root is only a commitment if nobody can find a different state with the same root. Our
+//! 64 bit toy hash does not promise that, so the header and block here are generic over the
+//! `Hasher` that computes their hashes. `Header` and `Block` use the toy hash, and
+//! `GenericBlock<Sha256, SumAndProduct>` builds the same chain with SHA-256.
+//!
+//! The block is also generic over the `StateMachine` that executes its extrinsics, so the machines
+//! from chapter 1 can run on this chain too. `Block` uses `SumAndProduct`, the sum and product
+//! described above.
+
+use crate::{
+	c1_state_machine::StateMachine,
+	crypto::hasher::{Hasher, ToyHasher},
+	hash,
+	mining::first_valid_digest,
+};
+use alloc::vec::Vec;
+use core::hash::Hash;
 
 const THRESHOLD: u64 = u64::max_value() / 100;
 
@@ -22,39 +37,92 @@ const THRESHOLD: u64 = u64::max_value() / 100;
 /// doubling of state size remember that in real world blockchains, the state is often really really
 /// large.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "scale", derive(codec::Encode, codec::Decode))]
 pub struct State {
-	sum: u64,
-	product: u64,
+	pub(crate) sum: u64,
+	pub(crate) product: u64,
+}
+
+/// Each extrinsic is a number that is added to the sum and multiplied into the product.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "scale", derive(codec::Encode, codec::Decode))]
+pub struct SumAndProduct;
+
+impl StateMachine for SumAndProduct {
+	type State = State;
+	type Transition = u64;
+
+	fn next_state(starting_state: &State, ext: &u64) -> State {
+		State { sum: starting_state.sum + ext, product: starting_state.product * ext }
+	}
 }
 
+/// Rules an extrinsic must follow before it may be executed. A state machine from chapter 1 has no
+/// way to refuse a transition, so this is where a chain says which extrinsics are not allowed at
+/// all, such as ones with a bad signature. A block containing one is invalid, even if its state
+/// root matches.
+pub trait ExtrinsicRules: StateMachine {
+	/// Whether the extrinsic may be applied to the state. Every extrinsic is allowed unless the
+	/// machine says otherwise.
+	fn allows(_state: &Self::State, _extrinsic: &Self::Transition) -> bool {
+		true
+	}
+
+	/// Whether the block body as a whole may be executed, checked once before any extrinsic is
+	/// applied. This is the place for checks that do not need the state and are cheaper to do for
+	/// the whole body at once, such as verifying every signature in one batch. Every body is
+	/// allowed unless the machine says otherwise.
+	fn allows_body(_extrinsics: &[Self::Transition]) -> bool {
+		true
+	}
+}
+
+impl ExtrinsicRules for SumAndProduct {}
+
+impl ExtrinsicRules for crate::c1_state_machine::p1_switches::LightSwitch {}
+
 /// The header no longer contains the state directly, but rather, it contains a hash of
 /// the complete state. This hash will allow block verifiers to cryptographically confirm
 /// that they got the same state as the author without having a complete copy of the
 /// author's state
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
-pub struct Header {
-	parent: Hash,
-	height: u64,
-	extrinsics_root: Hash,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "scale", derive(codec::Encode, codec::Decode))]
+#[cfg_attr(
+	feature = "serde",
+	serde(bound(
+		serialize = "H::Output: serde::Serialize",
+		deserialize = "H::Output: serde::Deserialize<'de>"
+	))
+)]
+pub struct GenericHeader<H: Hasher> {
+	pub(crate) parent: H::Output,
+	pub(crate) height: u64,
+	pub(crate) extrinsics_root: H::Output,
 	/// Stores a cryptographic commitment, like a Merkle root or a hash to the complete
 	/// post state.
-	state_root: Hash,
-	consensus_digest: u64,
+	pub(crate) state_root: H::Output,
+	pub(crate) consensus_digest: u64,
 }
 
+/// A header hashed with the toy hash.
+pub type Header = GenericHeader<ToyHasher>;
+
 // Methods for creating and verifying headers.
 //
 // We already moved the execution logic to the block level in the last section.
 // So this code is similar to last time. One key addition we are making is that
 // genesis blocks can have an initial state, or "genesis state" other than the
 // default. So we need to commit the initial state root to the genesis header here.
-impl Header {
+impl<H: Hasher> GenericHeader<H> {
 	/// Returns a new valid genesis header.
-	fn genesis(genesis_state_root: Hash) -> Self {
-		Header {
-			parent: u64::MIN,
+	fn genesis(genesis_state_root: H::Output) -> Self {
+		GenericHeader {
+			parent: H::Output::default(),
 			height: u64::MIN,
-			extrinsics_root: u64::MIN,
+			extrinsics_root: H::Output::default(),
 			state_root: genesis_state_root,
 			consensus_digest: 0,
 		}
@@ -64,28 +132,29 @@ impl Header {
 	///
 	/// The state root is passed in similarly to how the complete state
 	/// was in the previous section.
-	fn child(&self, extrinsics_root: Hash, state_root: Hash) -> Self {
-		let mut h = Header {
-			parent: hash(self),
+	fn child(&self, extrinsics_root: H::Output, state_root: H::Output) -> Self {
+		let h = GenericHeader {
+			parent: H::digest(self),
 			height: self.height + 1,
 			extrinsics_root: extrinsics_root,
 			state_root: state_root,
 			consensus_digest: u64::MIN,
 		};
-		//hash until under threshold
-		while hash(&h) > THRESHOLD {
-			h.consensus_digest += 1;
-		}
-		h
+		//hash until under threshold, on every core with `std`
+		let consensus_digest = first_valid_digest(u64::MIN, |digest| {
+			let candidate = GenericHeader { consensus_digest: digest, ..h.clone() };
+			H::leading_u64(&H::digest(&candidate)) <= THRESHOLD
+		});
+		GenericHeader { consensus_digest, ..h }
 	}
 
 	/// Verify a single child header.
-	fn verify_child(&self, child: &Header) -> bool {
-		child.parent == hash(self) && child.height == self.height + 1
+	fn verify_child(&self, child: &Self) -> bool {
+		child.parent == H::digest(self) && child.height == self.height + 1
 	}
 
 	/// Verify that all the given headers form a valid chain from this header to the tip.
-	fn verify_sub_chain(&self, chain: &[Header]) -> bool {
+	fn verify_sub_chain(&self, chain: &[Self]) -> bool {
 		for i in 1..chain.len() {
 			if !chain[i - 1].verify_child(&chain[i]) {
 				return false;
@@ -96,12 +165,27 @@ impl Header {
 }
 
 /// A complete Block is a header and the extrinsics.
+///
+/// Like the hasher, the state machine `M` is only used as a type, but deriving the traits below
+/// requires that it implements them too. A unit struct can simply derive them.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
-pub struct Block {
-	pub(crate) header: Header,
-	pub(crate) body: Vec<u64>,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "scale", derive(codec::Encode, codec::Decode))]
+#[cfg_attr(
+	feature = "serde",
+	serde(bound(
+		serialize = "H::Output: serde::Serialize, M::Transition: serde::Serialize",
+		deserialize = "H::Output: serde::Deserialize<'de>, M::Transition: serde::Deserialize<'de>"
+	))
+)]
+pub struct GenericBlock<H: Hasher, M: StateMachine> {
+	pub(crate) header: GenericHeader<H>,
+	pub(crate) body: Vec<M::Transition>,
 }
 
+/// A block of sums and products, hashed with the toy hash.
+pub type Block = GenericBlock<ToyHasher, SumAndProduct>;
+
 /// Methods for creating and verifying blocks.
 ///
 /// We no longer have access to a state simply by having access to a block.
@@ -112,39 +196,74 @@ pub struct Block {
 ///
 /// These methods also differ from last time because you will need to
 /// calculate state roots to pass to the header-level methods.
-impl Block {
+impl<H: Hasher, M: ExtrinsicRules> GenericBlock<H, M>
+where
+	M::State: Clone + Hash,
+	M::Transition: Hash,
+{
 	/// Returns a new valid genesis block. By convention this block has no extrinsics.
-	pub fn genesis(genesis_state: &State) -> Self {
-		let state_hash = hash(genesis_state);
-		Block { header: Header::genesis(state_hash), body: vec![] }
+	pub fn genesis(genesis_state: &M::State) -> Self {
+		let state_hash = H::digest(genesis_state);
+		GenericBlock { header: GenericHeader::genesis(state_hash), body: vec![] }
 	}
 
 	/// Create and return a valid child block.
-	pub fn child(&self, pre_state: &State, extrinsics: Vec<u64>) -> Self {
+	///
+	/// The extrinsics are executed without checking `ExtrinsicRules`, so it is up to the author to
+	/// only include allowed ones. Verification does check them.
+	pub fn child(&self, pre_state: &M::State, extrinsics: Vec<M::Transition>) -> Self {
 		let mut state = pre_state.clone();
 		let new_state = Self::transition_state(&mut state, &extrinsics);
-		let hash_of_state = hash(&new_state);
-		Block { header: self.header.child(hash(&extrinsics), hash_of_state), body: extrinsics }
+		let hash_of_state = H::digest(&new_state);
+		let header = self.header.child(H::digest(&extrinsics), hash_of_state);
+		GenericBlock { header, body: extrinsics }
 	}
 
 	//execute the state transition function
-	fn transition_state(prev_state: &mut State, exts: &Vec<u64>) -> State {
+	fn transition_state(prev_state: &mut M::State, exts: &Vec<M::Transition>) -> M::State {
 		for ext in exts {
-			prev_state.product *= ext;
-			prev_state.sum += ext;
+			*prev_state = M::next_state(prev_state, ext);
 		}
 		prev_state.clone()
 	}
 
+	/// Execute the extrinsics, checking the body is allowed as a whole and each extrinsic is
+	/// allowed by the state it is applied to. Returns `false` at the first check that fails.
+	fn execute_checked(state: &mut M::State, exts: &[M::Transition]) -> bool {
+		if !M::allows_body(exts) {
+			return false;
+		}
+		for ext in exts {
+			if !M::allows(state, ext) {
+				return false;
+			}
+			*state = M::next_state(state, ext);
+		}
+		true
+	}
+
 	//verify parent and child block
-	fn verify_block_child(parent: &Block, mut pre_state: State, child: &Block) -> (bool, State) {
+	fn verify_block_child(
+		parent: &Self,
+		mut pre_state: M::State,
+		child: &Self,
+	) -> (bool, M::State) {
 		let header_cond = parent.header.verify_child(&child.header);
-		let post_state = Self::transition_state(&mut pre_state, &child.body);
-		let body_cond = hash(&post_state) == child.header.state_root;
+		let allowed = Self::execute_checked(&mut pre_state, &child.body);
+		let body_cond = allowed && H::digest(&pre_state) == child.header.state_root;
 		if !(header_cond && body_cond) {
-			return (false, post_state);
+			return (false, pre_state);
+		}
+		(true, pre_state)
+	}
+
+	/// Verify a child of this block, given the state this block left behind, and return the state
+	/// the child leaves behind. Returns `None` if the child is invalid.
+	pub(crate) fn execute_child(&self, post_state: &M::State, child: &Self) -> Option<M::State> {
+		match Self::verify_block_child(self, post_state.clone(), child) {
+			(true, state) => Some(state),
+			(false, _) => None,
 		}
-		(true, post_state)
 	}
 
 	/// Verify that all the given blocks form a valid chain from this block to the tip.
@@ -152,14 +271,14 @@ impl Block {
 	/// This time we need to validate the initial block itself by confirming that we
 	/// have been given a valid pre-state. And we still need to verify the headers,
 	/// execute all transactions, and check the final state.
-	pub fn verify_sub_chain(&self, pre_state: &State, chain: &[Block]) -> bool {
+	pub fn verify_sub_chain(&self, pre_state: &M::State, chain: &[Self]) -> bool {
 		//setup
 		let mut curr_state = pre_state.clone();
 		let mut passed;
 		
 		//check pre_state with first block
-		curr_state = Self::transition_state(&mut curr_state, &self.body);
-		if !(hash(&curr_state) == self.header.state_root) {
+		let allowed = Self::execute_checked(&mut curr_state, &self.body);
+		if !allowed || H::digest(&curr_state) != self.header.state_root {
 			return false;
 		}
 		
@@ -178,6 +297,62 @@ impl Block {
 		}
 		true
 	}
+
+	/// Verify the same chain as `verify_sub_chain`, using several threads for the stateless work.
+	///
+	/// Checking that a header links to its parent only needs the two headers, so every link in the
+	/// chain can be checked at once. Executing the extrinsics cannot, because each block starts
+	/// from the state the previous block left behind. So verification runs as a two phase
+	/// pipeline:
+	/// 1. The workers share a counter of the next unchecked link. Each claims a batch of links at a
+	///    time and checks it, so a worker that finishes early simply claims more work instead of
+	///    sitting idle. Any failure stops everyone.
+	/// 2. Only if every link is valid, the blocks are executed in order on this thread.
+	#[cfg(feature = "std")]
+	pub fn verify_sub_chain_parallel(
+		&self,
+		pre_state: &M::State,
+		chain: &[Self],
+		workers: usize,
+	) -> bool {
+		use core::iter::once;
+		use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+		const BATCH: usize = 64;
+
+		let links: Vec<(&GenericHeader<H>, &GenericHeader<H>)> = once(self)
+			.chain(chain)
+			.map(|block| &block.header)
+			.zip(chain.iter().map(|block| &block.header))
+			.collect();
+		let next = AtomicUsize::new(0);
+		let valid = AtomicBool::new(true);
+		std::thread::scope(|scope| {
+			for _ in 0..workers.max(1) {
+				scope.spawn(|| {
+					while valid.load(Ordering::Relaxed) {
+						let start = next.fetch_add(BATCH, Ordering::Relaxed);
+						if start >= links.len() {
+							break;
+						}
+						let batch = &links[start..links.len().min(start + BATCH)];
+						if !batch.iter().all(|(parent, child)| parent.verify_child(child)) {
+							valid.store(false, Ordering::Relaxed);
+						}
+					}
+				});
+			}
+		});
+		if !valid.into_inner() {
+			return false;
+		}
+
+		let mut state = pre_state.clone();
+		once(self).chain(chain).all(|block| {
+			Self::execute_checked(&mut state, &block.body)
+				&& H::digest(&state) == block.header.state_root
+		})
+	}
 }
 
 /// Create an invalid child block of the given block. The returned block should have an
@@ -336,3 +511,110 @@ fn bc_6_student_invalid_block_really_is_invalid() {
 	// Make sure that the block is not valid when executed.
 	assert!(!gb.verify_sub_chain(&state, &[b1]));
 }
+
+#[cfg(feature = "std")]
+#[test]
+fn bc_6_parallel_verification_matches_sequential() {
+	let state = State { sum: 6, product: 9 };
+	let g = Block::genesis(&state);
+	let mut chain = vec![g.clone()];
+	let mut post_state = state.clone();
+	for i in 0..200 {
+		// The leading zero keeps the product from overflowing.
+		let extrinsics = vec![0, i % 3 + 1];
+		chain.push(chain[chain.len() - 1].child(&post_state, extrinsics.clone()));
+		Block::transition_state(&mut post_state, &extrinsics);
+	}
+
+	for workers in [1, 4] {
+		assert!(g.verify_sub_chain_parallel(&state, &chain[1..], workers));
+
+		let mut broken_link = chain.clone();
+		broken_link[150].header.height += 1;
+		assert!(!g.verify_sub_chain_parallel(&state, &broken_link[1..], workers));
+
+		let mut broken_state = chain.clone();
+		broken_state[150].body = vec![];
+		assert!(!g.verify_sub_chain_parallel(&state, &broken_state[1..], workers));
+		assert!(!g.verify_sub_chain(&state, &broken_state[1..]));
+	}
+}
+
+/// Compare sequential and parallel verification of a long chain. Run it with
+/// `cargo test --release bc_6_parallel_verification_speedup -- --ignored --nocapture`.
+#[cfg(feature = "std")]
+#[test]
+#[ignore]
+fn bc_6_parallel_verification_speedup() {
+	use std::time::Instant;
+
+	let state = State { sum: 0, product: 1 };
+	let g = Block::genesis(&state);
+	let mut chain = vec![g.clone()];
+	let mut post_state = state.clone();
+	for i in 0..20_000 {
+		let extrinsics = vec![1, i];
+		chain.push(chain[chain.len() - 1].child(&post_state, extrinsics.clone()));
+		Block::transition_state(&mut post_state, &extrinsics);
+	}
+	let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+
+	let started = Instant::now();
+	assert!(g.verify_sub_chain(&state, &chain[1..]));
+	println!("sequential: {:?}", started.elapsed());
+
+	let started = Instant::now();
+	assert!(g.verify_sub_chain_parallel(&state, &chain[1..], cores));
+	println!("{cores} worker(s): {:?}", started.elapsed());
+}
+
+#[test]
+fn bc_6_sha256_chain() {
+	use crate::crypto::hasher::Sha256;
+
+	let state = State { sum: 0, product: 1 };
+	let g = GenericBlock::<Sha256, SumAndProduct>::genesis(&state);
+	let b1 = g.child(&state, vec![2, 3]);
+	let b2 = b1.child(&State { sum: 5, product: 6 }, vec![4]);
+
+	assert_eq!(b1.header.parent, Sha256::digest(&g.header));
+	assert_eq!(g.header.state_root, Sha256::digest(&state));
+	assert!(Sha256::leading_u64(&Sha256::digest(&b2.header)) <= THRESHOLD);
+	assert!(g.verify_sub_chain(&state, &[b1.clone(), b2.clone()]));
+
+	let mut forged = b2;
+	forged.header.state_root = Sha256::digest(&State { sum: 10, product: 24 });
+	assert!(!g.verify_sub_chain(&state, &[b1, forged]));
+}
+
+#[test]
+fn bc_6_chain_of_light_switch_toggles() {
+	use crate::c1_state_machine::p1_switches::LightSwitch;
+
+	let g = GenericBlock::<ToyHasher, LightSwitch>::genesis(&false);
+	let b1 = g.child(&false, vec![(), (), ()]);
+	let b2 = b1.child(&true, vec![()]);
+
+	assert_eq!(b1.header.state_root, hash(&true));
+	assert_eq!(b2.header.state_root, hash(&false));
+	assert!(g.verify_sub_chain(&false, &[b1.clone(), b2.clone()]));
+	assert!(!g.verify_sub_chain(&true, &[b1, b2]));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn bc_6_block_json_round_trip() {
+	use crate::crypto::hasher::Sha256;
+	type Sha256Block = GenericBlock<Sha256, SumAndProduct>;
+
+	let state = State { sum: 0, product: 1 };
+	let b1 = Block::genesis(&state).child(&state, vec![2, 3]);
+	let sha_b1 = Sha256Block::genesis(&state).child(&state, vec![2, 3]);
+	let json = serde_json::to_string(&b1).unwrap();
+	let header_json = serde_json::to_string(&b1.header).unwrap();
+	let sha_json = serde_json::to_string(&sha_b1).unwrap();
+
+	assert_eq!(serde_json::from_str::<Block>(&json).unwrap(), b1);
+	assert_eq!(serde_json::from_str::<Header>(&header_json).unwrap(), b1.header);
+	assert_eq!(serde_json::from_str::<Sha256Block>(&sha_json).unwrap(), sha_b1);
+}
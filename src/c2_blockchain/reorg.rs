@@ -0,0 +1,126 @@
+//! Find where two chains split, and how far a node has to roll back to switch between them.
+//!
+//! A fork choice rule only says which chain is best. A node that was following a different chain
+//! then has to actually switch: undo the blocks since the last common ancestor, restoring the
+//! state from before them, and execute the blocks of the new chain instead. How many blocks it
+//! undoes is the _depth_ of the reorg. Shallow reorgs happen all the time, while deep ones are
+//! rare and usually mean something went wrong, which is why exchanges wait for confirmations.
+//!
+//! Like the fork choice rules, these functions accept whole chains or just the divergent parts.
+//! A chain that starts right after the other's fork point is recognized through its parent hash.
+
+use super::p4_batched_extrinsics::Header;
+use crate::hash;
+use alloc::collections::BTreeMap;
+
+type Hash = u64;
+
+/// The last block two chains share.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CommonAncestor {
+	pub hash: Hash,
+	pub height: u64,
+}
+
+/// What switching from one chain's head to another's involves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Reorg {
+	pub ancestor: CommonAncestor,
+	/// The blocks of the old chain after the ancestor, which are rolled back.
+	pub retracted: u64,
+	/// The blocks of the new chain after the ancestor, which are executed.
+	pub enacted: u64,
+}
+
+impl Reorg {
+	/// How many blocks of state have to be rolled back.
+	pub fn depth(&self) -> u64 {
+		self.retracted
+	}
+}
+
+/// The last block both chains contain, or `None` if they share no history that either of them
+/// shows.
+pub fn common_ancestor(chain_1: &[Header], chain_2: &[Header]) -> Option<CommonAncestor> {
+	let first = chain_1.first()?;
+	// Every block chain 1 shows, including the parent of its first header.
+	let mut known: BTreeMap<Hash, u64> =
+		chain_1.iter().map(|header| (hash(header), header.height)).collect();
+	if let Some(parent_height) = first.height.checked_sub(1) {
+		known.insert(first.parent, parent_height);
+	}
+
+	let found =
+		|hash: Hash| known.get(&hash).map(|height| CommonAncestor { hash, height: *height });
+	chain_2
+		.iter()
+		.rev()
+		.find_map(|header| found(hash(header)))
+		.or_else(|| found(chain_2.first()?.parent))
+}
+
+/// The reorg needed to switch from the head of `from` to the head of `to`, or `None` if the chains
+/// share no visible history.
+pub fn reorg(from: &[Header], to: &[Header]) -> Option<Reorg> {
+	let ancestor = common_ancestor(from, to)?;
+	let blocks_after =
+		|chain: &[Header]| chain.last().map_or(0, |tip| tip.height - ancestor.height);
+	Some(Reorg { ancestor, retracted: blocks_after(from), enacted: blocks_after(to) })
+}
+
+#[cfg(test)]
+fn build_chain(length: u64, start: &Header, salt: u64) -> Vec<Header> {
+	let mut chain = vec![start.child(salt, salt)];
+	for i in 1..length {
+		let next = chain[chain.len() - 1].child(salt, i);
+		chain.push(next);
+	}
+	chain
+}
+
+#[test]
+fn bc_reorg_full_chains() {
+	let g = Header::genesis();
+	let shared = build_chain(3, &g, 0);
+	let a = [&[g.clone()][..], &shared, &build_chain(2, &shared[2], 1)].concat();
+	let b = [&[g][..], &shared, &build_chain(4, &shared[2], 2)].concat();
+	let expected = CommonAncestor { hash: hash(&shared[2]), height: 3 };
+
+	assert_eq!(common_ancestor(&a, &b), Some(expected));
+	assert_eq!(common_ancestor(&b, &a), Some(expected));
+	assert_eq!(reorg(&a, &b), Some(Reorg { ancestor: expected, retracted: 2, enacted: 4 }));
+	assert_eq!(reorg(&b, &a).map(|r| r.depth()), Some(4));
+}
+
+#[test]
+fn bc_reorg_divergent_parts_only() {
+	let g = Header::genesis();
+	let shared = build_chain(3, &g, 0);
+	let a = build_chain(2, &shared[2], 1);
+	let b = build_chain(4, &shared[2], 2);
+	let expected = CommonAncestor { hash: hash(&shared[2]), height: 3 };
+
+	assert_eq!(common_ancestor(&a, &b), Some(expected));
+	assert_eq!(common_ancestor(&shared, &b), Some(expected));
+	assert_eq!(common_ancestor(&b, &shared), Some(expected));
+	assert_eq!(reorg(&a, &b).map(|r| r.depth()), Some(2));
+}
+
+#[test]
+fn bc_reorg_extending_the_chain_is_not_a_reorg() {
+	let g = Header::genesis();
+	let chain = build_chain(5, &g, 0);
+
+	assert_eq!(reorg(&chain[..3], &chain).map(|r| (r.depth(), r.enacted)), Some((0, 2)));
+	assert_eq!(reorg(&chain, &chain).map(|r| (r.depth(), r.enacted)), Some((0, 0)));
+}
+
+#[test]
+fn bc_reorg_unrelated_chains() {
+	let g = Header::genesis();
+	let chain = build_chain(5, &g, 0);
+
+	assert_eq!(common_ancestor(&chain[..1], &chain[3..]), None);
+	assert_eq!(reorg(&chain, &[]), None);
+	assert_eq!(reorg(&[], &chain), None);
+}
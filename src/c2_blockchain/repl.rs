@@ -0,0 +1,245 @@
+//! An interactive session for building chains by hand.
+//!
+//! Writing a test is a slow way to answer "what happens if I fork here and mine a little harder
+//! over there?". This module keeps a growing block tree built from the batched extrinsic blocks of
+//! part 4 and lets you extend it one command at a time, such as `child 1 2 3`, `fork 2`,
+//! `mine harder`, or `choose heaviest`. The `bfs-repl` binary reads the commands from the terminal.
+
+use super::{
+	p4_batched_extrinsics::{Block, Header, THRESHOLD},
+	p5_fork_choice::{
+		mine_extra_hard, ForkChoice, HeaviestChainRule, LongestChainRule, MostBlocksWithEvenHash,
+	},
+	viz::{render_ascii, render_dot},
+};
+use crate::{hash, hex::HexHash};
+use alloc::{
+	string::{String, ToString},
+	vec::Vec,
+};
+
+/// The help text printed by the `help` command.
+pub const HELP: &str = "\
+child <extrinsics...>  build a child of the head block
+fork <height>          move the head back to that height on the current chain
+mine harder            re-mine the head block to a 10x harder threshold, up to a limit
+choose <rule>          move the head to the best tip (longest, heaviest or even)
+show                   draw the block tree, marking the chain to the head with *
+show dot               print the block tree as a Graphviz graph, with every branch's length and work
+help                   show this message";
+
+/// The hardest threshold `mine harder` mines to, a thousand times harder than usual. Every step
+/// takes ten times as many tries as the one before, so without a limit a few more steps would keep
+/// the REPL busy for hours, and the threshold would eventually reach zero, which no hash can meet.
+const HARDEST_THRESHOLD: u64 = THRESHOLD / 1_000;
+
+/// Every block built so far, and which one new children are built on.
+pub struct Session {
+	blocks: Vec<Block>,
+	head: usize,
+}
+
+impl Default for Session {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Session {
+	/// A session containing only the genesis block.
+	pub fn new() -> Self {
+		Session { blocks: vec![Block::genesis()], head: 0 }
+	}
+
+	/// Run one command, returning what to print or an explanation of what went wrong.
+	pub fn execute(&mut self, line: &str) -> Result<String, String> {
+		let words: Vec<&str> = line.split_whitespace().collect();
+		match words.as_slice() {
+			["child", extrinsics @ ..] => {
+				let extrinsics = extrinsics
+					.iter()
+					.map(|word| word.parse().map_err(|_| format!("`{word}` is not a number")))
+					.collect::<Result<Vec<u64>, String>>()?;
+				let child = self.blocks[self.head].child(extrinsics);
+				self.blocks.push(child);
+				self.head = self.blocks.len() - 1;
+				Ok(self.describe(self.head))
+			},
+			["fork", height] => {
+				let height: u64 =
+					height.parse().map_err(|_| format!("`{height}` is not a height"))?;
+				let chain = self.chain_to(self.head);
+				let block = chain
+					.into_iter()
+					.find(|block| self.blocks[*block].header.height == height)
+					.ok_or(format!("the current chain has no block at height {height}"))?;
+				self.head = block;
+				Ok(self.describe(self.head))
+			},
+			["mine", "harder"] => {
+				if self.head == 0 {
+					return Err(String::from("genesis is not mined"));
+				}
+				let head_hash = hash(&self.blocks[self.head].header);
+				if self.blocks.iter().any(|block| block.header.parent == head_hash) {
+					return Err(String::from(
+						"the head already has children, and re-mining it would orphan them",
+					));
+				}
+				let header = &mut self.blocks[self.head].header;
+				let threshold = hash(header).min(THRESHOLD) / 10;
+				if threshold < HARDEST_THRESHOLD {
+					return Err(format!(
+						"the head is already mined as hard as the REPL allows, below {}",
+						HexHash(HARDEST_THRESHOLD * 10)
+					));
+				}
+				mine_extra_hard(header, threshold);
+				Ok(self.describe(self.head))
+			},
+			["choose", rule] => {
+				// Every chain shares the genesis block, which is not mined and so has no meaningful
+				// work. Compare only what comes after it.
+				let chains: Vec<Vec<Header>> = self
+					.tips()
+					.into_iter()
+					.map(|tip| {
+						self.chain_to(tip)[1..]
+							.iter()
+							.map(|i| self.blocks[*i].header.clone())
+							.collect()
+					})
+					.collect();
+				let candidates: Vec<&[Header]> = chains.iter().map(Vec::as_slice).collect();
+				let best = match *rule {
+					"longest" => LongestChainRule::best_chain(&candidates),
+					"heaviest" => HeaviestChainRule::best_chain(&candidates),
+					"even" => MostBlocksWithEvenHash::best_chain(&candidates),
+					_ => {
+						return Err(format!("unknown rule `{rule}`, try longest, heaviest or even"))
+					},
+				}
+				.map_err(|error| format!("the {rule} rule cannot choose: {error:?}"))?;
+				self.head = match best.last() {
+					Some(tip) => self.index_of(hash(tip)).expect("the best chain is one of ours"),
+					None => 0,
+				};
+				Ok(self.describe(self.head))
+			},
+			["show", format @ ..] => {
+				let headers: Vec<Header> =
+					self.blocks.iter().map(|block| block.header.clone()).collect();
+				let canonical: Vec<Header> = self
+					.chain_to(self.head)
+					.iter()
+					.map(|i| self.blocks[*i].header.clone())
+					.collect();
+				match format {
+					[] => Ok(render_ascii(&headers, &canonical).trim_end().to_string()),
+					["dot"] => Ok(render_dot(&headers, &canonical).trim_end().to_string()),
+					_ => Err(String::from("show draws text, or a Graphviz graph with `show dot`")),
+				}
+			},
+			["help"] => Ok(HELP.to_string()),
+			[] => Ok(String::new()),
+			_ => Err(format!("unknown command `{}`, try `help`", line.trim())),
+		}
+	}
+
+	/// A one line summary of a block.
+	fn describe(&self, index: usize) -> String {
+		let block = &self.blocks[index];
+		format!(
+			"head is #{} {} (parent {}, {} extrinsics, state {})",
+			block.header.height,
+			HexHash(hash(&block.header)),
+			HexHash(block.header.parent),
+			block.body.len(),
+			block.header.state,
+		)
+	}
+
+	fn index_of(&self, block_hash: u64) -> Option<usize> {
+		self.blocks.iter().position(|block| hash(&block.header) == block_hash)
+	}
+
+	/// The indices of the blocks from genesis to the given block.
+	fn chain_to(&self, mut index: usize) -> Vec<usize> {
+		let mut chain = vec![index];
+		while index != 0 {
+			index = self.index_of(self.blocks[index].header.parent).expect("parents are known");
+			chain.push(index);
+		}
+		chain.reverse();
+		chain
+	}
+
+	/// The blocks with no children.
+	fn tips(&self) -> Vec<usize> {
+		(0..self.blocks.len())
+			.filter(|i| {
+				let block_hash = hash(&self.blocks[*i].header);
+				!self.blocks.iter().any(|block| block.header.parent == block_hash)
+			})
+			.collect()
+	}
+}
+
+#[test]
+fn bc_repl_build_and_fork() {
+	let mut session = Session::new();
+
+	assert!(session.execute("child 1 2 3").unwrap().starts_with("head is #1 "));
+	assert!(session.execute("child 4").unwrap().starts_with("head is #2 "));
+	assert!(session.execute("fork 1").unwrap().starts_with("head is #1 "));
+	assert!(session.execute("child 5").unwrap().contains("state 11"));
+	assert_eq!(session.tips().len(), 2);
+	assert_eq!(session.execute("show").unwrap().lines().count(), 4);
+	assert_eq!(session.execute("show dot").unwrap().matches(" -> ").count(), 3);
+	assert!(session.execute("show svg").is_err());
+}
+
+#[test]
+fn bc_repl_choose_and_mine() {
+	let mut session = Session::new();
+	session.execute("child 1").unwrap();
+	session.execute("child 2").unwrap();
+	session.execute("fork 0").unwrap();
+	session.execute("child 3").unwrap();
+
+	assert!(session.execute("choose longest").unwrap().starts_with("head is #2 "));
+	assert!(session.execute("choose heaviest").is_ok());
+	assert!(session.execute("choose even").is_ok());
+	session.execute("choose longest").unwrap();
+	assert!(session.execute("mine harder").is_ok());
+	assert!(session.execute("fork 1").is_ok());
+	assert!(session.execute("mine harder").is_err());
+}
+
+#[test]
+fn bc_repl_reports_bad_input() {
+	let mut session = Session::new();
+
+	assert!(session.execute("choose heaviest").unwrap().starts_with("head is #0 "));
+	assert!(session.execute("child one").is_err());
+	assert!(session.execute("fork 7").is_err());
+	assert!(session.execute("choose shortest").is_err());
+	assert!(session.execute("mine harder").is_err());
+	assert!(session.execute("dance").is_err());
+}
+
+#[test]
+fn bc_repl_mine_harder_stops_at_a_limit() {
+	let mut session = Session::new();
+	session.execute("child 1").unwrap();
+
+	// Every step at least divides the threshold by ten, so the limit is reached within three.
+	let mut steps = 0;
+	while session.execute("mine harder").is_ok() {
+		steps += 1;
+		assert!(steps <= 3);
+	}
+	assert!(hash(&session.blocks[1].header) < HARDEST_THRESHOLD * 10);
+	let error = session.execute("mine harder").unwrap_err();
+	assert!(error.contains("as hard as the REPL allows"));
+}
@@ -0,0 +1,166 @@
+//! Selfish mining: earning more than your share of blocks by hiding them.
+//!
+//! It is tempting to think that a proof of work miner with a fraction `alpha` of the hash power
+//! earns a fraction `alpha` of the blocks, no matter what it does. Eyal and Sirer showed in 2013
+//! that this is not so (https://arxiv.org/abs/1311.0243). A selfish miner keeps the blocks it finds
+//! private and builds on them in secret. Whenever the honest miners catch up, it publishes just
+//! enough of its private chain to orphan their work. The honest miners waste effort on blocks that
+//! never make it into the longest chain, and the selfish miner's share of the blocks that do can
+//! be well above its share of the hash power.
+//!
+//! This module reproduces that result with the batched extrinsic headers from part 4, the longest
+//! chain rule from part 5, and a seeded `DeterministicRng`. Every block is really mined, but which
+//! side finds the next block is drawn at random according to `alpha`, rather than racing real
+//! threads, so runs are reproducible. The selfish strategy is the one from the paper:
+//! - With no private lead, a block the honest miners find is simply adopted.
+//! - With a lead of one, when the honest miners find a block the selfish miner publishes its own,
+//!   and the two branches race. A fraction `gamma` of the honest miners happen to build on the
+//!   selfish branch.
+//! - With a lead of two, when the honest miners find a block the selfish miner publishes its whole
+//!   private chain, which is longer, and the honest block is orphaned.
+//! - With a longer lead, the selfish miner waits. Honest blocks are orphaned either way.
+
+use super::{
+	p4_batched_extrinsics::Header,
+	p5_fork_choice::{ForkChoice, LongestChainRule},
+};
+use crate::rng::DeterministicRng;
+use alloc::vec::Vec;
+
+/// The author tag put in the extrinsics root of every block, so we can count who mined the final
+/// chain.
+const HONEST: u64 = 0;
+const SELFISH: u64 = 1;
+
+/// How the blocks of one run were shared out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SelfishMiningResult {
+	/// Blocks by the selfish miner in the final longest chain.
+	pub selfish_blocks: u64,
+	/// Blocks by the honest miners in the final longest chain.
+	pub honest_blocks: u64,
+	/// Blocks that were mined but did not make it into the final chain.
+	pub orphaned_blocks: u64,
+}
+
+impl SelfishMiningResult {
+	/// The selfish miner's share of the blocks in the final chain.
+	pub fn revenue_share(&self) -> f64 {
+		self.selfish_blocks as f64 / (self.selfish_blocks + self.honest_blocks).max(1) as f64
+	}
+}
+
+/// Mine `blocks` blocks in total, each found by the selfish miner with probability `alpha`, and
+/// report who owns the longest chain at the end.
+pub fn simulate(
+	alpha: f64,
+	gamma: f64,
+	blocks: u32,
+	rng: &mut DeterministicRng,
+) -> SelfishMiningResult {
+	// The chain the honest miners build on, the selfish miner's chain, and how much of the
+	// selfish chain the honest miners have seen.
+	let mut public = vec![Header::genesis()];
+	let mut private = public.clone();
+	let mut published = private.len();
+	let mut race = false;
+
+	for _ in 0..blocks {
+		if rng.chance(alpha) {
+			let block = private.last().expect("chains are never empty").child(SELFISH, 0);
+			private.push(block);
+			if race {
+				// Publish the new block at once to win the race.
+				published = private.len();
+				race = false;
+			}
+		} else {
+			if race && rng.chance(gamma) {
+				public = private[..published].to_vec();
+			}
+			race = false;
+			let block = public.last().expect("chains are never empty").child(HONEST, 0);
+			public.push(block);
+
+			if private.len() < public.len() {
+				// The selfish miner had no lead, or just lost a race, so it gives up its branch.
+				private.clone_from(&public);
+				published = private.len();
+			} else if private.len() <= public.len() + 1 {
+				// With a lead of one, publish and race. With a lead of two, publish and win.
+				race = private.len() == public.len();
+				published = private.len();
+			}
+		}
+
+		// The honest miners switch to whatever published chain the fork choice rule prefers.
+		// Ties keep the chain they already had.
+		if !LongestChainRule::first_chain_is_better(&public, &private[..published]) {
+			public = private[..published].to_vec();
+		}
+	}
+
+	// At the end the selfish miner publishes everything it has left.
+	let best =
+		LongestChainRule::best_chain(&[&public, &private]).expect("there are two candidates");
+	let selfish_blocks =
+		best[1..].iter().filter(|header| header.extrinsics_root == SELFISH).count();
+	let honest_blocks = best.len() - 1 - selfish_blocks;
+	SelfishMiningResult {
+		selfish_blocks: selfish_blocks as u64,
+		honest_blocks: honest_blocks as u64,
+		orphaned_blocks: blocks as u64 - (best.len() as u64 - 1),
+	}
+}
+
+/// The selfish miner's revenue share averaged over `runs` runs, each with its own generator forked
+/// from `seed`.
+pub fn average_revenue_share(alpha: f64, gamma: f64, blocks: u32, runs: u32, seed: u64) -> f64 {
+	let root = DeterministicRng::new(seed);
+	let shares: Vec<f64> = (0..runs)
+		.map(|run| simulate(alpha, gamma, blocks, &mut root.fork(&format!("run {run}"))))
+		.map(|result| result.revenue_share())
+		.collect();
+	shares.iter().sum::<f64>() / shares.len().max(1) as f64
+}
+
+/// The long run revenue share the paper derives for the selfish strategy.
+pub fn expected_revenue_share(alpha: f64, gamma: f64) -> f64 {
+	let beta = 1.0 - alpha;
+	let numerator =
+		alpha * beta * beta * (4.0 * alpha + gamma * (1.0 - 2.0 * alpha)) - alpha * alpha * alpha;
+	let denominator = 1.0 - alpha * (1.0 + (2.0 - alpha) * alpha);
+	numerator / denominator
+}
+
+#[test]
+fn bc_selfish_mining_is_reproducible() {
+	let a = simulate(0.3, 0.5, 300, &mut DeterministicRng::new(9));
+	let b = simulate(0.3, 0.5, 300, &mut DeterministicRng::new(9));
+
+	assert_eq!(a, b);
+	assert_eq!(a.selfish_blocks + a.honest_blocks + a.orphaned_blocks, 300);
+}
+
+#[test]
+fn bc_selfish_mining_pays_for_a_large_miner() {
+	let share = average_revenue_share(0.4, 0.5, 1_000, 8, 1);
+
+	assert!(share > 0.45, "share was {share}");
+	assert!((share - expected_revenue_share(0.4, 0.5)).abs() < 0.05, "share was {share}");
+}
+
+#[test]
+fn bc_selfish_mining_does_not_pay_for_a_small_miner() {
+	let share = average_revenue_share(0.1, 0.0, 1_000, 8, 2);
+
+	assert!(share < 0.1, "share was {share}");
+	assert!((share - expected_revenue_share(0.1, 0.0)).abs() < 0.03, "share was {share}");
+}
+
+#[test]
+fn bc_selfish_mining_with_no_hash_power_earns_nothing() {
+	let result = simulate(0.0, 0.5, 100, &mut DeterministicRng::new(3));
+
+	assert_eq!(result, SelfishMiningResult { honest_blocks: 100, ..Default::default() });
+}
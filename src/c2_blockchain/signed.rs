@@ -0,0 +1,260 @@
+//! Extrinsics that are signed by the account that sent them.
+//!
+//! In chapter 1, a transaction says which user it comes from, and the state machine believes it.
+//! On a real chain anyone can submit anything, so a transfer from Alice must prove that Alice
+//! actually asked for it. The proof is a signature made with Alice's key, and every node executing
+//! the block checks the signature before applying the transaction.
+//!
+//! `Signed` wraps any state machine so that its transitions must come signed. The signature covers
+//! the bytes the payload's `Hash` implementation writes, the same encoding the hashes of this
+//! chapter use. A block with a bad signature anywhere in its body does not verify, even if its
+//! state root is what executing the payloads would give.
+//!
+//! The signatures do not depend on the state, so a node checks all of a block's signatures in one
+//! batch before executing anything. Some schemes, such as ed25519, check a batch much faster than
+//! the same signatures one at a time. A failed batch does not say which signature was bad, so
+//! `first_invalid` checks them one by one when that is needed.
+//!
+//! A signature alone does not stop the author of a later block from including Alice's transfer a
+//! second time, since the copy carries a perfectly good signature. So each account also has a
+//! nonce in the state, counting the extrinsics it has sent. An extrinsic signs the nonce together
+//! with the payload and is only allowed if its nonce is the sender's next one. Replaying an old
+//! extrinsic, or skipping ahead, makes the block invalid.
+//!
+//! Nonces are part of the state, so they belong to a fork. An extrinsic included on one fork is
+//! still new on a fork that split off before it, and may be included there too.
+
+use super::p6_rich_state::ExtrinsicRules;
+use crate::{
+	c1_state_machine::StateMachine,
+	crypto::{hasher::encoded, sig::Pair},
+};
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::{hash::Hash, marker::PhantomData};
+
+/// A payload, the public key of the account that sent it, and that account's signature over the
+/// nonce and the payload.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SignedExtrinsic<P: Pair, Payload> {
+	pub payload: Payload,
+	pub signer: P::Public,
+	/// How many extrinsics the signer had sent before this one.
+	pub nonce: u64,
+	pub signature: P::Signature,
+}
+
+impl<P: Pair, Payload: Hash> SignedExtrinsic<P, Payload> {
+	/// Sign the nonce and payload with the given key pair.
+	pub fn new(pair: &P, nonce: u64, payload: Payload) -> Self {
+		let signature = pair.sign(&encoded(&(nonce, &payload)));
+		SignedExtrinsic { payload, signer: pair.public(), nonce, signature }
+	}
+
+	/// Whether the signature is the signer's signature over the nonce and payload.
+	pub fn verify(&self) -> bool {
+		P::verify(&self.signature, &encoded(&(self.nonce, &self.payload)), &self.signer)
+	}
+
+	/// Whether every signature in the body verifies, checked as a single batch.
+	pub fn verify_all(body: &[Self]) -> bool {
+		let messages: Vec<Vec<u8>> = body
+			.iter()
+			.map(|extrinsic| encoded(&(extrinsic.nonce, &extrinsic.payload)))
+			.collect();
+		let batch: Vec<_> = body
+			.iter()
+			.zip(&messages)
+			.map(|(extrinsic, message)| {
+				(&extrinsic.signature, message.as_slice(), &extrinsic.signer)
+			})
+			.collect();
+		P::verify_batch(&batch)
+	}
+
+	/// The position of the first extrinsic in the body whose signature does not verify, if any.
+	/// This checks the signatures one at a time, so use it to find the culprit after `verify_all`
+	/// fails rather than to check a body.
+	pub fn first_invalid(body: &[Self]) -> Option<usize> {
+		body.iter().position(|extrinsic| !extrinsic.verify())
+	}
+}
+
+/// The state of the wrapped machine, and the next nonce of every account that has sent anything.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SignedState<P: Pair, S> {
+	pub inner: S,
+	pub nonces: BTreeMap<P::Public, u64>,
+}
+
+impl<P: Pair, S> SignedState<P, S> {
+	/// A state where no account has sent anything yet.
+	pub fn new(inner: S) -> Self {
+		SignedState { inner, nonces: BTreeMap::new() }
+	}
+
+	/// The nonce the account's next extrinsic must have.
+	pub fn next_nonce(&self, account: &P::Public) -> u64 {
+		self.nonces.get(account).copied().unwrap_or(0)
+	}
+}
+
+/// The state machine `M`, with every transition signed in the signature scheme of `P`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Signed<M, P>(PhantomData<(M, P)>);
+
+impl<M: StateMachine, P: Pair> StateMachine for Signed<M, P> {
+	type State = SignedState<P, M::State>;
+	type Transition = SignedExtrinsic<P, M::Transition>;
+
+	fn next_state(starting_state: &Self::State, t: &Self::Transition) -> Self::State {
+		let mut nonces = starting_state.nonces.clone();
+		*nonces.entry(t.signer.clone()).or_insert(0) += 1;
+		SignedState { inner: M::next_state(&starting_state.inner, &t.payload), nonces }
+	}
+}
+
+impl<M, P> ExtrinsicRules for Signed<M, P>
+where
+	M: ExtrinsicRules,
+	M::Transition: Clone + Hash,
+	P: Pair,
+{
+	/// Only the nonce and the inner rules are checked here. The signature was already checked
+	/// along with the rest of the body by `allows_body`.
+	fn allows(state: &Self::State, extrinsic: &Self::Transition) -> bool {
+		extrinsic.nonce == state.next_nonce(&extrinsic.signer)
+			&& M::allows(&state.inner, &extrinsic.payload)
+	}
+
+	fn allows_body(extrinsics: &[Self::Transition]) -> bool {
+		if !SignedExtrinsic::verify_all(extrinsics) {
+			#[cfg(feature = "teaching_log")]
+			if let Some(index) = SignedExtrinsic::first_invalid(extrinsics) {
+				teaching_log!(
+					"signed",
+					Events,
+					"rejected a block body because extrinsic {index} has a bad signature"
+				);
+			}
+			return false;
+		}
+		let payloads: Vec<M::Transition> =
+			extrinsics.iter().map(|extrinsic| extrinsic.payload.clone()).collect();
+		M::allows_body(&payloads)
+	}
+}
+
+#[cfg(test)]
+use super::p6_rich_state::{GenericBlock, State, SumAndProduct};
+#[cfg(test)]
+use crate::crypto::{hasher::ToyHasher, keyring::Keyring, sig::ToyPair};
+
+#[cfg(test)]
+type SignedBlock = GenericBlock<ToyHasher, Signed<SumAndProduct, ToyPair>>;
+
+#[cfg(test)]
+fn genesis_state() -> SignedState<ToyPair, State> {
+	SignedState::new(State { sum: 0, product: 1 })
+}
+
+#[test]
+fn bc_signed_extrinsics_verify() {
+	let alice = Keyring::Alice.pair::<ToyPair>();
+	let bob = Keyring::Bob.pair::<ToyPair>();
+	let state = genesis_state();
+	let g = SignedBlock::genesis(&state);
+	let b1 =
+		g.child(&state, vec![SignedExtrinsic::new(&alice, 0, 2), SignedExtrinsic::new(&bob, 0, 3)]);
+
+	assert!(b1.body.iter().all(SignedExtrinsic::verify));
+	assert!(g.verify_sub_chain(&state, &[b1]));
+}
+
+#[test]
+fn bc_signed_bad_signature_rejects_the_block() {
+	let alice = Keyring::Alice.pair::<ToyPair>();
+	let state = genesis_state();
+	let g = SignedBlock::genesis(&state);
+
+	// Alice signed 2, but the author changed it to 20.
+	let mut tampered = SignedExtrinsic::new(&alice, 1, 2);
+	tampered.payload = 20;
+	let b1 = g.child(&state, vec![SignedExtrinsic::new(&alice, 0, 1), tampered]);
+	assert!(!g.verify_sub_chain(&state, &[b1]));
+
+	// Bob's key, claiming to be Alice.
+	let mut impostor = SignedExtrinsic::new(&Keyring::Bob.pair::<ToyPair>(), 0, 5);
+	impostor.signer = alice.public();
+	let b1 = g.child(&state, vec![impostor]);
+	assert!(!g.verify_sub_chain(&state, &[b1]));
+}
+
+#[test]
+fn bc_signed_batch_rejects_one_bad_signature() {
+	let alice = Keyring::Alice.pair::<ToyPair>();
+	let bob = Keyring::Bob.pair::<ToyPair>();
+	let state = genesis_state();
+	let g = SignedBlock::genesis(&state);
+	let mut body = vec![
+		SignedExtrinsic::new(&alice, 0, 2),
+		SignedExtrinsic::new(&bob, 0, 3),
+		SignedExtrinsic::new(&alice, 1, 4),
+	];
+	assert!(SignedExtrinsic::verify_all(&body));
+	assert!(g.verify_sub_chain(&state, &[g.child(&state, body.clone())]));
+
+	// Signatures are only checked by `allows_body`, as one batch, so the batch rejects this block.
+	body[1].payload = 30;
+	assert!(!SignedExtrinsic::verify_all(&body));
+	assert_eq!(SignedExtrinsic::first_invalid(&body), Some(1));
+	assert!(!g.verify_sub_chain(&state, &[g.child(&state, body)]));
+}
+
+#[test]
+fn bc_signed_nonces_must_be_next() {
+	let alice = Keyring::Alice.pair::<ToyPair>();
+	let state = genesis_state();
+	let g = SignedBlock::genesis(&state);
+	let valid = g.child(
+		&state,
+		vec![SignedExtrinsic::new(&alice, 0, 2), SignedExtrinsic::new(&alice, 1, 3)],
+	);
+	let replayed = g.child(
+		&state,
+		vec![SignedExtrinsic::new(&alice, 0, 2), SignedExtrinsic::new(&alice, 0, 2)],
+	);
+	let skipped = g.child(&state, vec![SignedExtrinsic::new(&alice, 1, 2)]);
+	// Changing the nonce of a signed extrinsic breaks its signature.
+	let mut renumbered = SignedExtrinsic::new(&alice, 0, 2);
+	renumbered.nonce = 5;
+	let renumbered = g.child(&state, vec![SignedExtrinsic::new(&alice, 0, 2), renumbered]);
+
+	assert!(g.verify_sub_chain(&state, &[valid]));
+	assert!(!g.verify_sub_chain(&state, &[replayed]));
+	assert!(!g.verify_sub_chain(&state, &[skipped]));
+	assert!(!g.verify_sub_chain(&state, &[renumbered]));
+}
+
+#[test]
+fn bc_signed_replay_across_forks() {
+	let alice = Keyring::Alice.pair::<ToyPair>();
+	let transfer = SignedExtrinsic::new(&alice, 0, 7);
+	let state_0 = genesis_state();
+	let g = SignedBlock::genesis(&state_0);
+
+	// Fork A includes the transfer right away.
+	let a1 = g.child(&state_0, vec![transfer.clone()]);
+	let state_a1 = Signed::<SumAndProduct, ToyPair>::next_state(&state_0, &transfer);
+	assert_eq!(state_a1.next_nonce(&alice.public()), 1);
+	let a2 = a1.child(&state_a1, vec![transfer.clone()]);
+	assert!(!g.verify_sub_chain(&state_0, &[a1.clone(), a2]));
+
+	// Fork B split off before it, so the same transfer is still new there.
+	let b1 = g.child(&state_0, vec![]);
+	let b2 = b1.child(&state_0, vec![transfer.clone()]);
+	assert!(g.verify_sub_chain(&state_0, &[b1, b2]));
+
+	// Once included, the next transfer on fork A needs the next nonce.
+	let a2 = a1.child(&state_a1, vec![SignedExtrinsic::new(&alice, 1, 7)]);
+	assert!(g.verify_sub_chain(&state_0, &[a1, a2]));
+}
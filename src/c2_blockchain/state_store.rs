@@ -0,0 +1,215 @@
+//! Key-value state, kept outside the blocks.
+//!
+//! Part 6 moved the state out of the header and left a state root in its place. Its state is still
+//! a fixed struct though, while a real chain stores arbitrary data: balances, nonces, contract
+//! storage, all under their own keys. `KvState` is that general shape, a sorted map from byte
+//! strings to byte strings, and `KeyValue` is the state machine that writes to it.
+//!
+//! The state root must be the same for everyone who holds the same entries, however they got
+//! there. Entries are kept sorted by key, and the root hashes them in that order, so two nodes that
+//! applied the same writes in different blocks still agree.
+//!
+//! Since blocks only carry the root, someone has to keep the states themselves. The `StateStore`
+//! remembers the state after every block it imported, so a child on any fork can be executed on
+//! top of its parent's state.
+
+use super::p6_rich_state::{ExtrinsicRules, GenericBlock};
+use crate::{c1_state_machine::StateMachine, crypto::hasher::Hasher};
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::hash::Hash;
+
+/// A map from keys to values, hashed in key order.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct KvState {
+	entries: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl KvState {
+	/// An empty state.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// The value stored under the key, if any.
+	pub fn get(&self, key: &[u8]) -> Option<&[u8]> {
+		self.entries.get(key).map(Vec::as_slice)
+	}
+
+	/// Store the value under the key, replacing any previous value.
+	pub fn insert(&mut self, key: Vec<u8>, value: Vec<u8>) {
+		self.entries.insert(key, value);
+	}
+
+	/// Remove the key and its value.
+	pub fn remove(&mut self, key: &[u8]) {
+		self.entries.remove(key);
+	}
+
+	/// The number of keys stored.
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	/// Whether no keys are stored.
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+
+	/// The state root, committing to every entry.
+	pub fn root<H: Hasher>(&self) -> H::Output {
+		H::digest(self)
+	}
+}
+
+/// A write to the key-value state.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum KvWrite {
+	Set { key: Vec<u8>, value: Vec<u8> },
+	Remove { key: Vec<u8> },
+}
+
+/// The state machine of a chain whose extrinsics write to a key-value state.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct KeyValue;
+
+impl StateMachine for KeyValue {
+	type State = KvState;
+	type Transition = KvWrite;
+
+	fn next_state(starting_state: &KvState, t: &KvWrite) -> KvState {
+		let mut state = starting_state.clone();
+		match t {
+			KvWrite::Set { key, value } => state.insert(key.clone(), value.clone()),
+			KvWrite::Remove { key } => state.remove(key),
+		}
+		state
+	}
+}
+
+impl ExtrinsicRules for KeyValue {}
+
+/// Why the store refused a block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportError {
+	/// The store has no state for the block's parent, so it cannot be executed.
+	UnknownParent,
+	/// The block does not follow its parent, breaks the extrinsic rules, or its state root does
+	/// not match its execution.
+	InvalidBlock,
+}
+
+/// The state after each imported block, by block hash.
+pub struct StateStore<H: Hasher, M: StateMachine> {
+	states: BTreeMap<H::Output, M::State>,
+}
+
+impl<H: Hasher, M: ExtrinsicRules> StateStore<H, M>
+where
+	M::State: Clone + Hash,
+	M::Transition: Hash,
+{
+	/// A store holding only the genesis state. Returns `None` if it is not the state the genesis
+	/// block commits to.
+	pub fn new(genesis: &GenericBlock<H, M>, genesis_state: M::State) -> Option<Self> {
+		if H::digest(&genesis_state) != genesis.header.state_root {
+			return None;
+		}
+		let mut states = BTreeMap::new();
+		states.insert(H::digest(&genesis.header), genesis_state);
+		Some(StateStore { states })
+	}
+
+	/// The state after the block with the given hash, if it was imported.
+	pub fn state_at(&self, block_hash: &H::Output) -> Option<&M::State> {
+		self.states.get(block_hash)
+	}
+
+	/// Execute the block on its parent's state and remember the result.
+	pub fn import(
+		&mut self,
+		parent: &GenericBlock<H, M>,
+		block: &GenericBlock<H, M>,
+	) -> Result<&M::State, ImportError> {
+		let parent_state =
+			self.states.get(&H::digest(&parent.header)).ok_or(ImportError::UnknownParent)?;
+		let state = parent.execute_child(parent_state, block).ok_or(ImportError::InvalidBlock)?;
+		Ok(self.states.entry(H::digest(&block.header)).or_insert(state))
+	}
+
+	/// The number of states stored.
+	pub fn len(&self) -> usize {
+		self.states.len()
+	}
+
+	/// Whether no states are stored. A store always has at least the genesis state.
+	pub fn is_empty(&self) -> bool {
+		self.states.is_empty()
+	}
+}
+
+#[cfg(test)]
+use crate::crypto::hasher::ToyHasher;
+
+#[cfg(test)]
+type KvBlock = GenericBlock<ToyHasher, KeyValue>;
+
+#[cfg(test)]
+fn set(key: &str, value: &str) -> KvWrite {
+	KvWrite::Set { key: key.into(), value: value.into() }
+}
+
+#[test]
+fn bc_state_root_ignores_write_order() {
+	let mut first = KvState::new();
+	first.insert(b"alice".to_vec(), b"10".to_vec());
+	first.insert(b"bob".to_vec(), b"20".to_vec());
+	let mut second = KvState::new();
+	second.insert(b"bob".to_vec(), b"20".to_vec());
+	second.insert(b"carol".to_vec(), b"5".to_vec());
+	second.insert(b"alice".to_vec(), b"10".to_vec());
+	second.remove(b"carol");
+
+	assert_eq!(first, second);
+	assert_eq!(first.root::<ToyHasher>(), second.root::<ToyHasher>());
+	second.insert(b"bob".to_vec(), b"21".to_vec());
+	assert_ne!(first.root::<ToyHasher>(), second.root::<ToyHasher>());
+}
+
+#[test]
+fn bc_state_store_executes_each_fork() {
+	let genesis_state = KvState::new();
+	let g = KvBlock::genesis(&genesis_state);
+	let mut store = StateStore::new(&g, genesis_state.clone()).unwrap();
+
+	let a1 = g.child(&genesis_state, vec![set("alice", "10")]);
+	let b1 = g.child(&genesis_state, vec![set("alice", "7"), set("bob", "3")]);
+	let a1_state = store.import(&g, &a1).unwrap().clone();
+	store.import(&g, &b1).unwrap();
+	let a2 = a1.child(&a1_state, vec![KvWrite::Remove { key: b"alice".to_vec() }]);
+	store.import(&a1, &a2).unwrap();
+
+	let state_after = |block: &KvBlock| store.state_at(&ToyHasher::digest(&block.header)).unwrap();
+	assert_eq!(state_after(&a1).get(b"alice"), Some(&b"10"[..]));
+	assert_eq!(state_after(&b1).get(b"alice"), Some(&b"7"[..]));
+	assert!(state_after(&a2).is_empty());
+	assert_eq!(a2.header.state_root, KvState::new().root::<ToyHasher>());
+	assert_eq!(store.len(), 4);
+}
+
+#[test]
+fn bc_state_store_rejects_what_it_cannot_check() {
+	let genesis_state = KvState::new();
+	let g = KvBlock::genesis(&genesis_state);
+	let mut store = StateStore::new(&g, genesis_state.clone()).unwrap();
+	let a1 = g.child(&genesis_state, vec![set("alice", "10")]);
+	let a2 = a1.child(&genesis_state, vec![set("bob", "1")]);
+
+	// The parent was never imported.
+	assert_eq!(store.import(&a1, &a2), Err(ImportError::UnknownParent));
+	store.import(&g, &a1).unwrap();
+	// Built on the wrong pre-state, so its root does not match.
+	assert_eq!(store.import(&a1, &a2), Err(ImportError::InvalidBlock));
+	// A genesis state the genesis block does not commit to.
+	let other_genesis = KeyValue::next_state(&genesis_state, &set("alice", "1"));
+	assert!(StateStore::new(&g, other_genesis).is_none());
+}
@@ -0,0 +1,162 @@
+//! Step through a block one extrinsic at a time.
+//!
+//! When a block's state root does not match, `verify_sub_chain` can only say "invalid". To find
+//! out why, this module re-executes the block with the same state transition function and records
+//! the state before and after every extrinsic, so you can see exactly where your execution starts
+//! to disagree with the author's.
+//!
+//! There are no events or fees in these blocks, so a trace step is only the state diff.
+
+use super::p6_rich_state::{Block, State, SumAndProduct};
+use crate::{c1_state_machine::StateMachine, hash, hex::HexHash};
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+type Hash = u64;
+
+/// What one extrinsic did to the state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceStep {
+	pub index: usize,
+	pub extrinsic: u64,
+	pub pre_state: State,
+	pub post_state: State,
+}
+
+/// Every step of a block's execution, and whether the result matches the header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExecutionTrace {
+	pub height: u64,
+	pub steps: Vec<TraceStep>,
+	/// The root of the state after the last extrinsic.
+	pub computed_root: Hash,
+	/// The root the block's header commits to.
+	pub expected_root: Hash,
+}
+
+impl ExecutionTrace {
+	/// Execute the block on top of `pre_state`, recording every step.
+	pub fn new(block: &Block, pre_state: &State) -> Self {
+		let mut state = pre_state.clone();
+		let steps = block
+			.body
+			.iter()
+			.enumerate()
+			.map(|(index, extrinsic)| {
+				let pre_state = state.clone();
+				state = SumAndProduct::next_state(&state, extrinsic);
+				TraceStep { index, extrinsic: *extrinsic, pre_state, post_state: state.clone() }
+			})
+			.collect();
+		ExecutionTrace {
+			height: block.header.height,
+			steps,
+			computed_root: hash(&state),
+			expected_root: block.header.state_root,
+		}
+	}
+
+	/// Whether executing the block produced the state root in its header.
+	pub fn matches(&self) -> bool {
+		self.computed_root == self.expected_root
+	}
+
+	/// The trace as a single JSON object.
+	pub fn to_json(&self) -> String {
+		let state =
+			|state: &State| format!("{{\"sum\":{},\"product\":{}}}", state.sum, state.product);
+		let steps: Vec<String> = self
+			.steps
+			.iter()
+			.map(|step| {
+				format!(
+					"{{\"index\":{},\"extrinsic\":{},\"pre_state\":{},\"post_state\":{}}}",
+					step.index,
+					step.extrinsic,
+					state(&step.pre_state),
+					state(&step.post_state)
+				)
+			})
+			.collect();
+		format!(
+			"{{\"height\":{},\"steps\":[{}],\"computed_root\":\"{}\",\"expected_root\":\"{}\",\
+			 \"matches\":{}}}",
+			self.height,
+			steps.join(","),
+			HexHash(self.computed_root),
+			HexHash(self.expected_root),
+			self.matches()
+		)
+	}
+}
+
+impl fmt::Display for ExecutionTrace {
+	/// One line per extrinsic showing only the fields it changed, then the root comparison.
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "block #{}", self.height)?;
+		for step in &self.steps {
+			let (pre, post) = (&step.pre_state, &step.post_state);
+			let mut changes = Vec::new();
+			if pre.sum != post.sum {
+				changes.push(format!("sum {} -> {}", pre.sum, post.sum));
+			}
+			if pre.product != post.product {
+				changes.push(format!("product {} -> {}", pre.product, post.product));
+			}
+			if changes.is_empty() {
+				changes.push(String::from("no change"));
+			}
+			writeln!(f, "  [{}] extrinsic {}: {}", step.index, step.extrinsic, changes.join(", "))?;
+		}
+		if self.matches() {
+			write!(f, "  state root {} matches", HexHash(self.computed_root))
+		} else {
+			write!(
+				f,
+				"  state root MISMATCH: computed {}, header has {}",
+				HexHash(self.computed_root),
+				HexHash(self.expected_root)
+			)
+		}
+	}
+}
+
+#[test]
+fn bc_trace_records_every_extrinsic() {
+	let state = State { sum: 6, product: 9 };
+	let b1 = Block::genesis(&state).child(&state, vec![2, 1, 3]);
+	let trace = ExecutionTrace::new(&b1, &state);
+
+	assert!(trace.matches());
+	assert_eq!(trace.steps.len(), 3);
+	assert_eq!(trace.steps[0].pre_state, state);
+	assert_eq!(trace.steps[0].post_state, State { sum: 8, product: 18 });
+	assert_eq!(trace.steps[1].pre_state, trace.steps[0].post_state);
+	assert_eq!(trace.steps[2].post_state, State { sum: 12, product: 54 });
+}
+
+#[test]
+fn bc_trace_pretty_print_shows_diffs_and_mismatch() {
+	let state = State { sum: 6, product: 9 };
+	let mut b1 = Block::genesis(&state).child(&state, vec![1, 2]);
+	b1.body = vec![1, 3];
+	let printed = format!("{}", ExecutionTrace::new(&b1, &state));
+	let lines: Vec<&str> = printed.lines().collect();
+
+	assert_eq!(lines[0], "block #1");
+	assert_eq!(lines[1], "  [0] extrinsic 1: sum 6 -> 7");
+	assert_eq!(lines[2], "  [1] extrinsic 3: sum 7 -> 10, product 9 -> 27");
+	assert!(lines[3].starts_with("  state root MISMATCH"));
+}
+
+#[test]
+fn bc_trace_json() {
+	let state = State { sum: 0, product: 1 };
+	let b1 = Block::genesis(&state).child(&state, vec![5]);
+	let json = ExecutionTrace::new(&b1, &state).to_json();
+
+	assert!(json.starts_with("{\"height\":1,\"steps\":[{\"index\":0,\"extrinsic\":5,"));
+	assert!(json.contains("\"pre_state\":{\"sum\":0,\"product\":1}"));
+	assert!(json.contains("\"post_state\":{\"sum\":5,\"product\":5}"));
+	assert!(json.ends_with("\"matches\":true}"));
+}
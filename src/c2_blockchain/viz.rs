@@ -0,0 +1,267 @@
+//! Draw a block tree.
+//!
+//! Fork choice is much easier to reason about when you can see the forks. This module takes any
+//! collection of headers, links them into a tree through their parent hashes, and draws the tree
+//! either as text for the terminal, as an SVG image for slides, or as a Graphviz graph. Blocks on
+//! the canonical chain (for example the result of a fork choice rule) are highlighted, and fork
+//! points, blocks with more than one child, are labeled.
+//!
+//! The Graphviz graph also labels the tip of every branch with the length and the work of the
+//! chain leading to it, which are exactly the numbers the longest and heaviest chain rules
+//! compare. Render it with `dot -Tpng blocks.dot -o blocks.png`.
+//!
+//! These headers have no notion of finality yet, so there is nothing to draw for it.
+
+use super::p4_batched_extrinsics::{Header, THRESHOLD};
+use crate::{hash, hex::HexHash};
+use alloc::{collections::BTreeSet, string::String, vec::Vec};
+use core::fmt::Write;
+
+type Hash = u64;
+
+/// The headers linked into a tree. Children are ordered so that the canonical child comes first.
+struct Tree<'a> {
+	headers: Vec<&'a Header>,
+	hashes: Vec<Hash>,
+	children: Vec<Vec<usize>>,
+	roots: Vec<usize>,
+	canonical: BTreeSet<Hash>,
+}
+
+impl<'a> Tree<'a> {
+	fn new(headers: &'a [Header], canonical: &[Header]) -> Self {
+		let hashes: Vec<Hash> = headers.iter().map(hash).collect();
+		let canonical: BTreeSet<Hash> = canonical.iter().map(hash).collect();
+		let mut children = vec![Vec::new(); headers.len()];
+		let mut roots = Vec::new();
+		for (i, header) in headers.iter().enumerate() {
+			match hashes.iter().position(|hash| *hash == header.parent) {
+				Some(parent) => children[parent].push(i),
+				None => roots.push(i),
+			}
+		}
+		for siblings in &mut children {
+			siblings.sort_by_key(|child| !canonical.contains(&hashes[*child]));
+		}
+		Tree { headers: headers.iter().collect(), hashes, children, roots, canonical }
+	}
+
+	fn is_canonical(&self, node: usize) -> bool {
+		self.canonical.contains(&self.hashes[node])
+	}
+
+	fn is_fork(&self, node: usize) -> bool {
+		self.children[node].len() > 1
+	}
+
+	/// A short label such as `#3 0x1f2e3d4c`.
+	fn label(&self, node: usize) -> String {
+		let hash = format!("{}", HexHash(self.hashes[node]));
+		format!("#{} {}", self.headers[node].height, &hash[..10])
+	}
+}
+
+/// Draw the tree as text, one block per line, in the style of the `tree` command. Canonical blocks
+/// are marked with `*`.
+///
+/// ```text
+/// #0 0x00b3c64d * (fork)
+/// ├── #1 0x00a8e0d5 *
+/// │   └── #2 0x0059e4f6 *
+/// └── #1 0x0112c7f3
+/// ```
+pub fn render_ascii(headers: &[Header], canonical: &[Header]) -> String {
+	let tree = Tree::new(headers, canonical);
+	let mut out = String::new();
+	for root in &tree.roots {
+		ascii_node(&tree, *root, "", None, &mut out);
+	}
+	out
+}
+
+/// Write one node and its subtree. `last` is `None` for a root, otherwise whether the node is the
+/// last of its siblings.
+fn ascii_node(tree: &Tree, node: usize, prefix: &str, last: Option<bool>, out: &mut String) {
+	let (branch, indent) = match last {
+		None => ("", ""),
+		Some(false) => ("├── ", "│   "),
+		Some(true) => ("└── ", "    "),
+	};
+	let mut line = format!("{prefix}{branch}{}", tree.label(node));
+	if tree.is_canonical(node) {
+		line.push_str(" *");
+	}
+	if tree.is_fork(node) {
+		line.push_str(" (fork)");
+	}
+	writeln!(out, "{line}").expect("writing to a String cannot fail");
+
+	let prefix = format!("{prefix}{indent}");
+	let children = &tree.children[node];
+	for (i, child) in children.iter().enumerate() {
+		ascii_node(tree, *child, &prefix, Some(i + 1 == children.len()), out);
+	}
+}
+
+const COLUMN_WIDTH: usize = 140;
+const ROW_HEIGHT: usize = 50;
+const MARGIN: usize = 30;
+
+/// Draw the tree as an SVG image. Height increases to the right and every fork opens a new row
+/// below. Canonical blocks and the links between them are drawn in a strong color, and fork
+/// points get a thicker outline.
+pub fn render_svg(headers: &[Header], canonical: &[Header]) -> String {
+	let tree = Tree::new(headers, canonical);
+
+	// Give every node a row. A node shares its row with its first child, and every other child
+	// starts a new row at the bottom.
+	let mut rows = vec![0; headers.len()];
+	let mut next_row = 0;
+	let mut stack: Vec<(usize, Option<usize>)> =
+		tree.roots.iter().rev().map(|root| (*root, None)).collect();
+	while let Some((node, row)) = stack.pop() {
+		rows[node] = row.unwrap_or_else(|| {
+			next_row += 1;
+			next_row - 1
+		});
+		for (i, child) in tree.children[node].iter().enumerate().rev() {
+			stack.push((*child, if i == 0 { Some(rows[node]) } else { None }));
+		}
+	}
+
+	let min_height = tree.headers.iter().map(|header| header.height).min().unwrap_or(0);
+	let position = |node: usize| {
+		let column = (tree.headers[node].height - min_height) as usize;
+		(MARGIN + column * COLUMN_WIDTH, MARGIN + rows[node] * ROW_HEIGHT)
+	};
+	let columns = tree.headers.iter().map(|header| header.height - min_height + 1).max();
+	let width = columns.unwrap_or(1) as usize * COLUMN_WIDTH + 2 * MARGIN;
+	let height = next_row.max(1) * ROW_HEIGHT + 2 * MARGIN;
+
+	let mut out = String::new();
+	let mut line = |s: String| {
+		out.push_str(&s);
+		out.push('\n');
+	};
+	line(format!(
+		"<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+		 font-family=\"monospace\" font-size=\"11\">"
+	));
+	for (parent, children) in tree.children.iter().enumerate() {
+		for child in children {
+			let ((x1, y1), (x2, y2)) = (position(parent), position(*child));
+			let color = if tree.is_canonical(*child) { "#1f6feb" } else { "#999" };
+			line(format!(
+				"<path d=\"M{x1} {y1} L{} {y2} L{x2} {y2}\" fill=\"none\" stroke=\"{color}\"/>",
+				x2 - COLUMN_WIDTH / 2
+			));
+		}
+	}
+	for node in 0..tree.headers.len() {
+		let (x, y) = position(node);
+		let fill = if tree.is_canonical(node) { "#1f6feb" } else { "#fff" };
+		let stroke_width = if tree.is_fork(node) { 3 } else { 1 };
+		line(format!(
+			"<circle cx=\"{x}\" cy=\"{y}\" r=\"8\" fill=\"{fill}\" stroke=\"#333\" \
+			 stroke-width=\"{stroke_width}\"/>"
+		));
+		line(format!("<text x=\"{}\" y=\"{}\">{}</text>", x + 12, y + 4, tree.label(node)));
+	}
+	line(String::from("</svg>"));
+	out
+}
+
+/// Draw the tree as a Graphviz `digraph`, with height increasing to the right. Canonical blocks are
+/// filled, fork points get a double outline, and every tip is labeled with the length and work of
+/// its chain, counted from the root of its tree. As in the heaviest chain rule, a block's work is
+/// how far its hash is below the threshold, so an unmined block such as genesis adds none.
+pub fn render_dot(headers: &[Header], canonical: &[Header]) -> String {
+	let tree = Tree::new(headers, canonical);
+	let mut out = String::from(
+		"digraph blocks {\n\trankdir=LR;\n\tnode [shape=box, fontname=\"monospace\"];\n",
+	);
+	let mut line = |s: String| {
+		writeln!(out, "\t{s}").expect("writing to a String cannot fail");
+	};
+
+	// Visit parents before their children, carrying the length and work of the chain so far.
+	let mut stack: Vec<(usize, u64, u128)> = tree.roots.iter().map(|root| (*root, 0, 0)).collect();
+	while let Some((node, length, work)) = stack.pop() {
+		let (length, work) =
+			(length + 1, work + THRESHOLD.saturating_sub(tree.hashes[node]) as u128);
+		let mut label = tree.label(node);
+		if tree.children[node].is_empty() {
+			label.push_str(&format!("\\nlength {length}, work {work}"));
+		}
+		let mut attributes = format!("label=\"{label}\"");
+		if tree.is_canonical(node) {
+			attributes.push_str(", style=filled, fillcolor=\"#1f6feb\", fontcolor=white");
+		}
+		if tree.is_fork(node) {
+			attributes.push_str(", peripheries=2");
+		}
+		line(format!("\"{}\" [{attributes}];", HexHash(tree.hashes[node])));
+		for child in &tree.children[node] {
+			let color = if tree.is_canonical(*child) { "#1f6feb" } else { "#999" };
+			line(format!(
+				"\"{}\" -> \"{}\" [color=\"{color}\"];",
+				HexHash(tree.hashes[node]),
+				HexHash(tree.hashes[*child])
+			));
+			stack.push((*child, length, work));
+		}
+	}
+	out.push_str("}\n");
+	out
+}
+
+/// A genesis block with two competing children, the first of which has a child of its own.
+#[cfg(test)]
+fn build_fork() -> (Vec<Header>, Vec<Header>) {
+	let g = Header::genesis();
+	let a1 = g.child(1, 1);
+	let a2 = a1.child(2, 3);
+	let b1 = g.child(5, 5);
+	(vec![g.clone(), b1, a1.clone(), a2.clone()], vec![g, a1, a2])
+}
+
+#[test]
+fn bc_viz_ascii_tree() {
+	let (headers, canonical) = build_fork();
+	let ascii = render_ascii(&headers, &canonical);
+	let lines: Vec<&str> = ascii.lines().collect();
+
+	assert_eq!(lines.len(), 4);
+	assert!(lines[0].starts_with("#0 0x") && lines[0].ends_with(" * (fork)"));
+	assert!(lines[1].starts_with("├── #1 ") && lines[1].ends_with(" *"));
+	assert!(lines[2].starts_with("│   └── #2 ") && lines[2].ends_with(" *"));
+	assert!(lines[3].starts_with("└── #1 ") && !lines[3].ends_with('*'));
+}
+
+#[test]
+fn bc_viz_svg_has_every_block_and_link() {
+	let (headers, canonical) = build_fork();
+	let svg = render_svg(&headers, &canonical);
+
+	assert!(svg.starts_with("<svg ") && svg.trim_end().ends_with("</svg>"));
+	assert_eq!(svg.matches("<circle ").count(), 4);
+	assert_eq!(svg.matches("<path ").count(), 3);
+	assert_eq!(svg.matches("fill=\"#1f6feb\"").count(), 3);
+}
+
+#[test]
+fn bc_viz_dot_labels_every_tip() {
+	let (headers, canonical) = build_fork();
+	let dot = render_dot(&headers, &canonical);
+	let work = |chain: &[Header]| -> u128 {
+		chain.iter().map(|header| THRESHOLD.saturating_sub(hash(header)) as u128).sum()
+	};
+
+	assert!(dot.starts_with("digraph blocks {") && dot.ends_with("}\n"));
+	assert_eq!(dot.matches(" -> ").count(), 3);
+	assert_eq!(dot.matches("fillcolor").count(), 3);
+	assert_eq!(dot.matches("peripheries=2").count(), 1);
+	assert_eq!(dot.matches("\\nlength ").count(), 2);
+	assert!(dot.contains(&format!("\\nlength 3, work {}\"", work(&canonical))));
+	assert!(dot.contains(&format!("\\nlength 2, work {}\"", work(&headers[..2]))));
+}
@@ -3,13 +3,16 @@
 //! previous module, then look at PoA, and other consensus engines all implementing the same simple
 //! interface.
 
-mod p1_pow;
+mod mutation;
+pub(crate) mod p1_pow;
 mod p2_dictator;
-mod p3_poa; // exercise: dictator is a special case of poa. Create dictator in terms of PoA.
+pub(crate) mod p3_poa; // exercise: dictator is a special case of poa. Create dictator in terms of PoA.
 mod p4_even_only;
 mod p5_interleave;
 mod p6_forking;
 
+use alloc::string::String;
+
 type Hash = u64;
 
 /// A Block Header similar to prior chapters of this tutorial.
@@ -21,17 +24,19 @@ type Hash = u64;
 /// which means they can operate entirely at the header level. They never need to touch
 /// the complete blocks.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "scale", derive(codec::Encode, codec::Decode))]
 pub struct Header<Digest> {
-	parent: Hash,
-	height: u64,
-	state_root: Hash,
-	extrinsics_root: Hash,
-	consensus_digest: Digest,
+	pub(crate) parent: Hash,
+	pub(crate) height: u64,
+	pub(crate) state_root: Hash,
+	pub(crate) extrinsics_root: Hash,
+	pub(crate) consensus_digest: Digest,
 }
 
 impl Header<()> {
 	//convert a null header into one with a digest
-	fn convert_to_digest<T>(&self, digest: T) -> Header<T> {
+	pub(crate) fn convert_to_digest<T>(&self, digest: T) -> Header<T> {
 		Header::<T> {
 			parent: self.parent,
 			height: self.height,
@@ -47,7 +52,7 @@ impl Header<()> {
 /// Consensus exists independently of execution logic, and therefore operates
 /// only on the block headers.
 pub trait Consensus {
-	type Digest: Clone + core::fmt::Debug + Eq + PartialEq + std::hash::Hash;
+	type Digest: Clone + core::fmt::Debug + Eq + PartialEq + core::hash::Hash;
 
 	/// Validates that a header is valid according to consensus rules. This
 	/// function checks ONLY consensus-related aspects such as the signature
@@ -125,6 +130,8 @@ impl Consensus for () {
 /// A set of consensus authority accounts that can be used in
 /// identity-based consensus algorithms.
 #[derive(Hash, Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "scale", derive(codec::Encode, codec::Decode))]
 pub enum ConsensusAuthority {
 	#[default] Alice,
 	Bob,
@@ -0,0 +1,217 @@
+//! Check that a consensus engine notices when a sealed header is tampered with.
+//!
+//! It is easy to write a `validate` that accepts every header it should, and much harder to be
+//! sure it rejects every header it should not. This harness takes one validly sealed header and
+//! makes many small changes to it, one at a time: every bit of every field is flipped, and the
+//! digest is swapped for each of its alternatives. Then it asks the engine to validate each
+//! mutant and reports the ones it still accepted.
+//!
+//! Not every engine is supposed to reject every mutation. `validate` only checks the consensus
+//! rules, and an engine whose toy signature does not cover the header contents cannot tell that
+//! the state root changed. So the harness asks which fields the engine claims to protect, and only
+//! complains about those.
+//!
+//! For proof of work, a mutant has the same chance of meeting the threshold as any other guess, so
+//! use a hard threshold when testing, or the harness will occasionally report a lucky mutant.
+
+use super::{Consensus, ConsensusAuthority, Header};
+use alloc::{format, string::String, vec::Vec};
+
+/// The parts of a header that can be mutated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+	Parent,
+	Height,
+	StateRoot,
+	ExtrinsicsRoot,
+	Digest,
+}
+
+impl Field {
+	/// Every field.
+	pub const ALL: [Field; 5] =
+		[Field::Parent, Field::Height, Field::StateRoot, Field::ExtrinsicsRoot, Field::Digest];
+}
+
+/// A digest type that knows how to produce slightly different versions of a value.
+pub trait DigestMutations: Sized {
+	/// Every variation of this digest that the harness should try.
+	fn mutations(&self) -> Vec<Self>;
+}
+
+impl DigestMutations for u64 {
+	fn mutations(&self) -> Vec<Self> {
+		(0..64).map(|bit| self ^ (1 << bit)).collect()
+	}
+}
+
+impl DigestMutations for ConsensusAuthority {
+	fn mutations(&self) -> Vec<Self> {
+		(0..3)
+			.map(|i| ConsensusAuthority::from_index(&i))
+			.filter(|other| other != self)
+			.collect()
+	}
+}
+
+impl DigestMutations for () {
+	fn mutations(&self) -> Vec<Self> {
+		Vec::new()
+	}
+}
+
+/// One mutant header, and what was changed to make it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mutant<Digest> {
+	pub field: Field,
+	/// A short description of the change, such as `bit 3` or the new digest.
+	pub change: String,
+	pub header: Header<Digest>,
+}
+
+/// Every mutant of the header, field by field.
+pub fn mutants<D>(header: &Header<D>) -> Vec<Mutant<D>>
+where
+	D: Clone + core::fmt::Debug + DigestMutations,
+{
+	let mut mutants = Vec::new();
+	for field in Field::ALL {
+		if field == Field::Digest {
+			for digest in header.consensus_digest.mutations() {
+				let change = format!("{digest:?}");
+				let header = Header { consensus_digest: digest, ..header.clone() };
+				mutants.push(Mutant { field, change, header });
+			}
+			continue;
+		}
+		for bit in 0..64 {
+			let mut mutant = header.clone();
+			let value = match field {
+				Field::Parent => &mut mutant.parent,
+				Field::Height => &mut mutant.height,
+				Field::StateRoot => &mut mutant.state_root,
+				Field::ExtrinsicsRoot => &mut mutant.extrinsics_root,
+				Field::Digest => unreachable!("digests are mutated above"),
+			};
+			*value ^= 1 << bit;
+			mutants.push(Mutant { field, change: format!("bit {bit}"), header: mutant });
+		}
+	}
+	mutants
+}
+
+/// The mutants of the given fields that the engine still accepts. An engine that checks everything
+/// it should returns an empty list.
+pub fn accepted_mutants<C>(
+	engine: &C,
+	parent_digest: &C::Digest,
+	header: &Header<C::Digest>,
+	protected: &[Field],
+) -> Vec<Mutant<C::Digest>>
+where
+	C: Consensus,
+	C::Digest: DigestMutations,
+{
+	mutants(header)
+		.into_iter()
+		.filter(|mutant| protected.contains(&mutant.field))
+		.filter(|mutant| engine.validate(parent_digest, &mutant.header))
+		.collect()
+}
+
+/// Panic, listing the mutants, if the engine accepts any mutation of the protected fields. The
+/// header itself must be valid.
+pub fn assert_rejects_mutations<C>(
+	engine: &C,
+	parent_digest: &C::Digest,
+	header: &Header<C::Digest>,
+	protected: &[Field],
+) where
+	C: Consensus,
+	C::Digest: DigestMutations,
+{
+	assert!(engine.validate(parent_digest, header), "the unmutated header must be valid");
+	let accepted = accepted_mutants(engine, parent_digest, header, protected);
+	let listed: Vec<String> = accepted
+		.iter()
+		.map(|mutant| format!("{:?} {}", mutant.field, mutant.change))
+		.collect();
+	assert!(
+		accepted.is_empty(),
+		"{} accepted {} mutated headers: {}",
+		C::human_name(),
+		accepted.len(),
+		listed.join(", ")
+	);
+}
+
+#[cfg(test)]
+use super::{p1_pow::PoW, p3_poa::SimplePoa};
+
+#[cfg(test)]
+fn partial_header() -> Header<()> {
+	Header { parent: 7, height: 3, state_root: 10, extrinsics_root: 11, consensus_digest: () }
+}
+
+#[test]
+fn cs_mutation_every_field_and_bit() {
+	let header = partial_header().convert_to_digest(5u64);
+	let mutants = mutants(&header);
+
+	assert_eq!(mutants.len(), 5 * 64);
+	assert!(mutants.iter().all(|mutant| mutant.header != header));
+	assert_eq!(mutants.iter().filter(|mutant| mutant.field == Field::Height).count(), 64);
+}
+
+#[test]
+fn cs_mutation_pow_protects_everything() {
+	// Hard enough that a mutant is very unlikely to meet the threshold by luck.
+	let pow = PoW { threshold: u64::MAX / 100_000 };
+	let header = pow.seal(&0, partial_header()).unwrap();
+
+	assert_rejects_mutations(&pow, &0, &header, &Field::ALL);
+}
+
+#[test]
+fn cs_mutation_poa_protects_only_the_digest() {
+	let poa = SimplePoa { authorities: vec![ConsensusAuthority::Alice] };
+	let header = poa.seal(&ConsensusAuthority::Alice, partial_header()).unwrap();
+
+	assert_rejects_mutations(&poa, &ConsensusAuthority::Alice, &header, &[Field::Digest]);
+	let accepted = accepted_mutants(&poa, &ConsensusAuthority::Alice, &header, &Field::ALL);
+	assert_eq!(accepted.len(), 4 * 64);
+}
+
+/// A proof of work engine with a bug: it forgets to include the state root in the hash.
+#[cfg(test)]
+struct ForgetfulPow {
+	threshold: u64,
+}
+
+#[cfg(test)]
+impl Consensus for ForgetfulPow {
+	type Digest = u64;
+
+	fn validate(&self, _: &u64, header: &Header<u64>) -> bool {
+		let covered =
+			(header.parent, header.height, header.extrinsics_root, header.consensus_digest);
+		crate::hash(&covered) < self.threshold
+	}
+
+	fn seal(&self, _: &u64, partial_header: Header<()>) -> Option<Header<u64>> {
+		let mut header = partial_header.convert_to_digest(0);
+		while !self.validate(&0, &header) {
+			header.consensus_digest += 1;
+		}
+		Some(header)
+	}
+}
+
+#[test]
+#[should_panic(expected = "accepted 64 mutated headers: StateRoot bit 0, StateRoot bit 1")]
+fn cs_mutation_catches_a_missing_check() {
+	let pow = ForgetfulPow { threshold: u64::MAX / 100_000 };
+	let header = pow.seal(&0, partial_header()).unwrap();
+
+	assert_rejects_mutations(&pow, &0, &header, &Field::ALL);
+}
@@ -4,10 +4,13 @@
 //! This is the same logic we implemented previously. Here we re-implement it in the
 //! generic consensus framework that we will use throughout the rest of the chapter.
 
-use std::num::ParseIntError;
+use core::num::ParseIntError;
 
 use super::{Consensus, Header};
 use crate::hash;
+#[cfg(feature = "teaching_log")]
+use crate::hex::HexHash;
+use crate::mining::first_valid_digest;
 
 /// A Proof of Work consensus engine. This is the same consensus logic that we
 /// implemented in the previous chapter. Here we simply re-implement it in the
@@ -23,17 +26,39 @@ impl Consensus for PoW {
 	/// Check that the provided header's hash is below the required threshold.
 	/// This does not rely on the parent digest at all.
 	fn validate(&self, _: &Self::Digest, header: &Header<Self::Digest>) -> bool {
-		hash(&header) < self.threshold
+		let seal_hash = hash(&header);
+		if seal_hash >= self.threshold {
+			teaching_log!(
+				"pow",
+				Detail,
+				"rejected block {} because its hash is above the threshold {}",
+				HexHash(seal_hash),
+				HexHash(self.threshold)
+			);
+			return false;
+		}
+		true
 	}
 
 	/// Mine a new PoW seal for the partial header provided.
 	/// This does not rely on the parent digest at all.
+	///
+	/// The guesses are checked against the threshold directly rather than through `validate`, so
+	/// the failed ones are not each logged as a rejected block.
 	fn seal(&self, _: &Self::Digest, partial_header: Header<()>) -> Option<Header<Self::Digest>> {
-		let mut header: Header<Self::Digest> = partial_header.convert_to_digest(u64::MIN);
-		while !self.validate(&u64::MIN, &header) {
-			header.consensus_digest += 1;
-		}
-		Some(header)
+		let header: Header<Self::Digest> = partial_header.convert_to_digest(u64::MIN);
+		let consensus_digest = first_valid_digest(u64::MIN, |digest| {
+			hash(&Header { consensus_digest: digest, ..header.clone() }) < self.threshold
+		});
+		let sealed = Header { consensus_digest, ..header };
+		teaching_log!(
+			"pow",
+			Events,
+			"sealed block {} with nonce {}",
+			HexHash(hash(&sealed)),
+			consensus_digest
+		);
+		Some(sealed)
 	}
 }
 
@@ -45,3 +70,99 @@ pub fn moderate_difficulty_pow() -> impl Consensus {
 		threshold: u64::max_value() / 100
 	}
 }
+
+/// A proof of work search that only runs when asked to, so you can watch it happen.
+///
+/// `seal` loops until it finds a nonce, which makes mining look like magic: you call it and some
+/// time later there is a valid header. A `SealSearch` tries the same nonces in the same order,
+/// but in batches of whatever size the caller chooses, and remembers the lowest hash it has seen.
+/// Stepping through a search in a test or a REPL shows that mining really is nothing more than
+/// guessing, and that the best hash creeps towards the threshold until one guess finally lands
+/// below it.
+pub struct SealSearch {
+	threshold: u64,
+	header: Header<u64>,
+	attempts: u64,
+	best_hash: u64,
+}
+
+impl SealSearch {
+	/// Try up to `batch` more nonces. Returns the sealed header if one of them is valid, after
+	/// which further steps return the same header without doing any more work.
+	pub fn step(&mut self, batch: u64) -> Option<Header<u64>> {
+		if self.best_hash < self.threshold {
+			return Some(self.header.clone());
+		}
+		for _ in 0..batch {
+			let header_hash = hash(&self.header);
+			self.attempts += 1;
+			self.best_hash = self.best_hash.min(header_hash);
+			if header_hash < self.threshold {
+				return Some(self.header.clone());
+			}
+			self.header.consensus_digest += 1;
+		}
+		None
+	}
+
+	/// How many nonces have been tried so far.
+	pub fn attempts(&self) -> u64 {
+		self.attempts
+	}
+
+	/// The lowest hash found so far, or `u64::MAX` before the first attempt.
+	pub fn best_hash(&self) -> u64 {
+		self.best_hash
+	}
+
+	/// The nonce the next attempt will try, or the winning nonce once the search has succeeded.
+	pub fn nonce(&self) -> u64 {
+		self.header.consensus_digest
+	}
+}
+
+impl PoW {
+	/// Start a search for a seal on the partial header that the caller steps through by hand.
+	pub fn seal_stepwise(&self, partial_header: Header<()>) -> SealSearch {
+		SealSearch {
+			threshold: self.threshold,
+			header: partial_header.convert_to_digest(u64::MIN),
+			attempts: 0,
+			best_hash: u64::MAX,
+		}
+	}
+}
+
+#[cfg(test)]
+fn partial_header() -> Header<()> {
+	Header { parent: 0, height: 1, state_root: 0, extrinsics_root: 0, consensus_digest: () }
+}
+
+#[test]
+fn cs_1_step_through_a_seal() {
+	let pow = PoW { threshold: u64::MAX / 100 };
+	let mut search = pow.seal_stepwise(partial_header());
+	let mut best_hashes = vec![search.best_hash()];
+	let sealed = loop {
+		if let Some(header) = search.step(10) {
+			break header;
+		}
+		assert_eq!(search.attempts() % 10, 0);
+		best_hashes.push(search.best_hash());
+	};
+
+	assert!(pow.validate(&0, &sealed));
+	assert_eq!(sealed.consensus_digest, search.nonce());
+	assert_eq!(search.attempts(), search.nonce() + 1);
+	assert!(best_hashes.windows(2).all(|pair| pair[1] <= pair[0]));
+	assert_eq!(search.step(10), Some(sealed));
+}
+
+#[test]
+fn cs_1_stepwise_seal_matches_seal() {
+	let pow = PoW { threshold: u64::MAX / 100 };
+	let mut search = pow.seal_stepwise(partial_header());
+	let stepped = core::iter::repeat_with(|| search.step(1)).flatten().next();
+
+	assert_eq!(stepped, pow.seal(&0, partial_header()));
+}
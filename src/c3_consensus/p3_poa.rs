@@ -10,6 +10,8 @@
 //! the proof of authority we are writing here.
 
 use super::{Consensus, ConsensusAuthority, Header};
+use crate::c2_blockchain::p5_fork_choice::ForkChoice;
+use alloc::vec::Vec;
 
 /// A Proof of Authority consensus engine. If any of the authorities have signed the block, it is
 /// valid.
@@ -73,6 +75,8 @@ struct PoaRoundRobinBySlot {
 /// signature. In addition to checking that the right signer has signed for the slot, you must check
 /// that the slot is always strictly increasing. But remember that slots may be skipped.
 #[derive(Hash, Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "scale", derive(codec::Encode, codec::Decode))]
 struct SlotDigest {
 	slot: u64,
 	signature: ConsensusAuthority,
@@ -101,3 +105,88 @@ impl Consensus for PoaRoundRobinBySlot {
 		Some(partial_header.convert_to_digest(digest))
 	}
 }
+
+/// Prefer the chain on which more blocks were signed by the author the slot belonged to.
+///
+/// Slot based authoring has a weakness of its own: when the author of a slot is offline, the slot
+/// stays empty. Many chains therefore let a _secondary_ author sign a block when the _primary_
+/// author for the slot does not. Secondary blocks keep the chain moving, but they are easier to
+/// produce, so a chain that is long only because of them should not beat a chain of primary
+/// blocks. This rule counts the blocks whose signer is the slot's primary author,
+/// `ConsensusAuthority::from_index(slot)`, and breaks ties by length.
+pub struct MostPrimarySlotBlocks;
+
+impl MostPrimarySlotBlocks {
+	/// How many of the chain's blocks were signed by the primary author of their slot.
+	fn primary_blocks(chain: &[Header<SlotDigest>]) -> usize {
+		chain
+			.iter()
+			.filter(|header| {
+				let digest = &header.consensus_digest;
+				digest.signature == ConsensusAuthority::from_index(&digest.slot)
+			})
+			.count()
+	}
+}
+
+impl ForkChoice<Header<SlotDigest>> for MostPrimarySlotBlocks {
+	fn first_chain_is_better(
+		chain_1: &[Header<SlotDigest>],
+		chain_2: &[Header<SlotDigest>],
+	) -> bool {
+		let score = |chain: &[Header<SlotDigest>]| (Self::primary_blocks(chain), chain.len());
+		score(chain_1) >= score(chain_2)
+	}
+
+	/// The number of primary blocks. Chains with as many break the tie by length.
+	fn score(chain: &[Header<SlotDigest>]) -> u128 {
+		Self::primary_blocks(chain) as u128
+	}
+}
+
+/// A chain signed in the given slots. Each block is signed by the primary author if `primary` says
+/// so, and otherwise by the author after them.
+#[cfg(test)]
+fn slot_chain(slots: &[(u64, bool)]) -> Vec<Header<SlotDigest>> {
+	let mut parent = 0;
+	slots
+		.iter()
+		.enumerate()
+		.map(|(i, (slot, primary))| {
+			let secondary = if *primary { 0 } else { 1 };
+			let header = Header {
+				parent,
+				height: i as u64 + 1,
+				state_root: 0,
+				extrinsics_root: 0,
+				consensus_digest: SlotDigest {
+					slot: *slot,
+					signature: ConsensusAuthority::from_index(&(slot + secondary)),
+				},
+			};
+			parent = crate::hash(&header);
+			header
+		})
+		.collect()
+}
+
+#[test]
+fn cs_3_primary_blocks_beat_a_longer_secondary_chain() {
+	let primary = slot_chain(&[(1, true), (2, true), (3, true)]);
+	let mostly_secondary = slot_chain(&[(1, true), (2, false), (3, false), (4, true)]);
+
+	assert_eq!(MostPrimarySlotBlocks::primary_blocks(&primary), 3);
+	assert_eq!(MostPrimarySlotBlocks::primary_blocks(&mostly_secondary), 2);
+	assert!(MostPrimarySlotBlocks::first_chain_is_better(&primary, &mostly_secondary));
+	assert_eq!(MostPrimarySlotBlocks::best_chain(&[&mostly_secondary, &primary]), Ok(&primary[..]));
+}
+
+#[test]
+fn cs_3_primary_ties_go_to_the_longer_chain() {
+	let short = slot_chain(&[(1, true), (3, true)]);
+	let long = slot_chain(&[(1, true), (2, false), (4, true)]);
+
+	assert_eq!(MostPrimarySlotBlocks::score(&short), MostPrimarySlotBlocks::score(&long));
+	assert!(!MostPrimarySlotBlocks::first_chain_is_better(&short, &long));
+	assert!(MostPrimarySlotBlocks::first_chain_is_better(&long, &short));
+}
@@ -5,6 +5,7 @@
 
 use super::{Consensus, Header, p1_pow::PoW};
 use crate::hash;
+use alloc::vec::Vec;
 
 /// A Consensus engine that wraps another consensus engine. This engine enforces the requirement
 /// that a block must have an even state root in order to be valid
@@ -8,6 +8,8 @@ use crate::hash;
 
 //the digest for an alternating consensus engine
 #[derive(Hash, Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "scale", derive(codec::Encode, codec::Decode))]
 struct AltDigest {
 	nonce: u64,
 	auth: ConsensusAuthority,
@@ -6,12 +6,13 @@
 //! be enforced before or after the fork, but rather delegates to existing consensus engines
 //! for that. Here we simply write the logic for detecting whether we are before or after the fork.
 
-use std::marker::PhantomData;
+use core::marker::PhantomData;
 
 use super::p1_pow::PoW;
 use super::p3_poa::SimplePoa;
 use super::p4_even_only::EvenOnly;
 use super::{Consensus, ConsensusAuthority, Header};
+use alloc::vec::Vec;
 
 /// A Higher-order consensus engine that represents a change from one set of consensus rules
 /// (Before) to another set (After) at a specific block height
@@ -24,7 +25,7 @@ struct Forked<D, Before, After> {
 
 impl<D, B, A> Consensus for Forked<D, B, A>
 where
-	D: Clone + core::fmt::Debug + Eq + PartialEq + std::hash::Hash,
+	D: Clone + core::fmt::Debug + Eq + PartialEq + core::hash::Hash,
 	B: Consensus,
 	A: Consensus,
 	B::Digest: Into<D> + From<D>,
@@ -158,6 +159,8 @@ fn even_after_given_height<Original: Consensus + Clone>(fork_height: u64, origin
 /// In order to implement a consensus change where even the Digest type changes, we will need an
 /// enum that wraps the two individual digest types
 #[derive(Hash, Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "scale", derive(codec::Encode, codec::Decode))]
 enum PowOrPoaDigest {
 	Pow(u64),
 	Poa(ConsensusAuthority),
@@ -12,6 +12,7 @@
 /// In doing so, we create a blockchain framework
 use crate::c1_state_machine::StateMachine;
 use crate::c3_consensus::{Consensus, Header};
+use alloc::vec::Vec;
 type Hash = u64;
 
 impl<Digest> Header<Digest> {
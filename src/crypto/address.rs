@@ -0,0 +1,209 @@
+//! Human readable account addresses.
+//!
+//! Public keys are awkward to show to people. They are long, they differ between signature
+//! schemes, and a single mistyped character silently produces a different, perfectly valid key.
+//! Chains therefore derive a shorter _address_ from the public key, and print it in an encoding
+//! that includes a network prefix and a checksum.
+//!
+//! The format here is modeled on Substrate's SS58:
+//! - The address is the hash of the public key. Our hashes are 64 bits, so no further truncation is
+//!   needed.
+//! - The encoded bytes are one prefix byte identifying the network, the eight address bytes, and a
+//!   two byte checksum.
+//! - Those bytes are written in base58, which avoids look-alike characters such as `0` and `O`.
+
+use crate::hash;
+use alloc::{string::String, vec::Vec};
+use core::{fmt, hash::Hash as StdHash, str::FromStr};
+
+/// The network prefix used when none is specified, like Substrate's generic prefix 42.
+pub const DEFAULT_PREFIX: u8 = 42;
+
+/// The base58 alphabet used by Bitcoin and Substrate.
+const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// How many bytes of the checksum are included in the encoding.
+const CHECKSUM_LEN: usize = 2;
+
+/// The length of the encoded bytes: prefix, address, and checksum.
+const ENCODED_LEN: usize = 1 + 8 + CHECKSUM_LEN;
+
+/// An account address on a particular network.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Address {
+	/// Which network this address is meant for.
+	pub prefix: u8,
+	/// The hash of the account's public key.
+	pub account: u64,
+}
+
+/// The ways an address string can fail to parse.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddressError {
+	/// The string contains a character that is not in the base58 alphabet.
+	InvalidCharacter(char),
+	/// The string decodes to the wrong number of bytes.
+	BadLength,
+	/// The checksum does not match. Most likely the address was mistyped.
+	BadChecksum,
+}
+
+impl Address {
+	/// Derive the address of the given public key on the default network.
+	pub fn from_public<P: StdHash>(public: &P) -> Self {
+		Self::from_public_with_prefix(public, DEFAULT_PREFIX)
+	}
+
+	/// Derive the address of the given public key on the network with the given prefix.
+	pub fn from_public_with_prefix<P: StdHash>(public: &P, prefix: u8) -> Self {
+		Address { prefix, account: hash(public) }
+	}
+
+	/// The raw bytes that get base58 encoded.
+	fn to_bytes(self) -> [u8; ENCODED_LEN] {
+		let mut bytes = [0; ENCODED_LEN];
+		bytes[0] = self.prefix;
+		bytes[1..9].copy_from_slice(&self.account.to_be_bytes());
+		let checksum = checksum(&bytes[..9]);
+		bytes[9..].copy_from_slice(&checksum);
+		bytes
+	}
+}
+
+/// The checksum over the prefix and address bytes.
+fn checksum(payload: &[u8]) -> [u8; CHECKSUM_LEN] {
+	let full = hash(&(b"ADDRESS", payload)).to_be_bytes();
+	[full[0], full[1]]
+}
+
+/// Encode bytes in base58.
+fn base58_encode(bytes: &[u8]) -> String {
+	// Treat the bytes as one big-endian number and repeatedly divide it by 58.
+	let mut digits: Vec<u8> = Vec::new();
+	for byte in bytes {
+		let mut carry = *byte as u32;
+		for digit in digits.iter_mut() {
+			carry += (*digit as u32) << 8;
+			*digit = (carry % 58) as u8;
+			carry /= 58;
+		}
+		while carry > 0 {
+			digits.push((carry % 58) as u8);
+			carry /= 58;
+		}
+	}
+	// Leading zero bytes would otherwise vanish, so each one is written as the zero digit.
+	let zeros = bytes.iter().take_while(|byte| **byte == 0).count();
+	let mut encoded = String::with_capacity(zeros + digits.len());
+	encoded.extend(core::iter::repeat_n('1', zeros));
+	encoded.extend(digits.iter().rev().map(|digit| ALPHABET[*digit as usize] as char));
+	encoded
+}
+
+/// Decode a base58 string into bytes.
+fn base58_decode(s: &str) -> Result<Vec<u8>, AddressError> {
+	let mut bytes: Vec<u8> = Vec::new();
+	for c in s.chars() {
+		let Some(value) = ALPHABET.iter().position(|a| *a as char == c) else {
+			return Err(AddressError::InvalidCharacter(c));
+		};
+		let mut carry = value as u32;
+		for byte in bytes.iter_mut() {
+			carry += (*byte as u32) * 58;
+			*byte = (carry & 0xff) as u8;
+			carry >>= 8;
+		}
+		while carry > 0 {
+			bytes.push((carry & 0xff) as u8);
+			carry >>= 8;
+		}
+	}
+	let zeros = s.chars().take_while(|c| *c == '1').count();
+	bytes.extend(core::iter::repeat_n(0, zeros));
+	bytes.reverse();
+	Ok(bytes)
+}
+
+impl fmt::Display for Address {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", base58_encode(&self.to_bytes()))
+	}
+}
+
+impl FromStr for Address {
+	type Err = AddressError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let bytes = base58_decode(s)?;
+		if bytes.len() != ENCODED_LEN {
+			return Err(AddressError::BadLength);
+		}
+		if checksum(&bytes[..9]) != bytes[9..] {
+			return Err(AddressError::BadChecksum);
+		}
+		let mut account = [0; 8];
+		account.copy_from_slice(&bytes[1..9]);
+		Ok(Address { prefix: bytes[0], account: u64::from_be_bytes(account) })
+	}
+}
+
+#[cfg(test)]
+use super::sig::{Pair, ToyPair};
+
+#[test]
+fn address_base58_round_trip() {
+	for bytes in [vec![], vec![0], vec![0, 0, 1], vec![255, 254, 0, 7], vec![58], vec![57]] {
+		assert_eq!(base58_decode(&base58_encode(&bytes)), Ok(bytes));
+	}
+}
+
+#[test]
+fn address_base58_known_value() {
+	// Bitcoin's base58 encoding of the string "hello world".
+	assert_eq!(base58_encode(b"hello world"), "StV1DL6CwTryKyV");
+}
+
+#[test]
+fn address_derivation_is_deterministic() {
+	let public = ToyPair::from_seed(&[1; 32]).public();
+
+	assert_eq!(Address::from_public(&public), Address::from_public(&public));
+	assert_ne!(
+		Address::from_public(&public),
+		Address::from_public(&ToyPair::from_seed(&[2; 32]).public())
+	);
+}
+
+#[test]
+fn address_display_round_trip() {
+	let address = Address::from_public(&ToyPair::from_seed(&[1; 32]).public());
+	let encoded = address.to_string();
+
+	assert_eq!(encoded.parse(), Ok(address));
+}
+
+#[test]
+fn address_prefix_changes_encoding() {
+	let public = ToyPair::from_seed(&[1; 32]).public();
+	let generic = Address::from_public(&public);
+	let other = Address::from_public_with_prefix(&public, 0);
+
+	assert_ne!(generic.to_string(), other.to_string());
+	assert_eq!(other.to_string().parse::<Address>().map(|a| a.prefix), Ok(0));
+}
+
+#[test]
+fn address_typo_is_caught() {
+	let encoded = Address::from_public(&ToyPair::from_seed(&[1; 32]).public()).to_string();
+	let mut chars: Vec<char> = encoded.chars().collect();
+	let last = chars.len() - 1;
+	chars[last] = if chars[last] == '2' { '3' } else { '2' };
+	let typo: String = chars.into_iter().collect();
+
+	assert!(typo.parse::<Address>().is_err());
+}
+
+#[test]
+fn address_invalid_character_is_rejected() {
+	assert_eq!("0OIl".parse::<Address>(), Err(AddressError::InvalidCharacter('0')));
+}
@@ -0,0 +1,308 @@
+//! Erasure coding and data availability sampling.
+//!
+//! A light client only downloads headers, so it cannot tell whether the body behind a header was
+//! ever published. A block author could publish a valid looking header and simply withhold the
+//! body. Full nodes would notice, but light clients would happily follow the chain. This is the
+//! _data availability_ problem.
+//!
+//! The fix combines two ideas:
+//! 1. Erasure coding. The body is extended with redundant data so that _any_ half of the resulting
+//!    chunks is enough to rebuild all of it. We use a Reed-Solomon code: the body is read as the
+//!    values of a polynomial at the points `0..k`, and the extension is the same polynomial
+//!    evaluated at `k..2k`. A polynomial of degree less than `k` is fixed by any `k` of its values.
+//! 2. Sampling. The header commits to the Merkle root of the chunks. Each light node downloads a
+//!    handful of randomly chosen chunks together with their Merkle proofs. To make the body
+//!    unrecoverable the author must withhold more than half of the chunks, so each sample has at
+//!    least an even chance of exposing them. After `s` successful samples a light node is
+//!    confident to within `2^-s` that the body is available.
+//!
+//! Real systems use two dimensional codes and KZG commitments so that incorrectly encoded chunks
+//! can be detected too. Here we assume the encoding was done honestly.
+
+use super::merkle::{verify_proof, MerkleProof, MerkleTree};
+use crate::hash;
+use alloc::vec::Vec;
+
+type Hash = u64;
+
+/// The field modulus, the Mersenne prime `2^61 - 1`. Body words are split into 32 bit halves so
+/// that every piece fits in the field.
+pub const P: u64 = (1 << 61) - 1;
+
+fn add(a: u64, b: u64) -> u64 {
+	(a + b) % P
+}
+
+fn sub(a: u64, b: u64) -> u64 {
+	(a + P - b) % P
+}
+
+fn mul(a: u64, b: u64) -> u64 {
+	((a as u128 * b as u128) % P as u128) as u64
+}
+
+fn pow(mut base: u64, mut exp: u64) -> u64 {
+	let mut result = 1;
+	while exp > 0 {
+		if exp & 1 == 1 {
+			result = mul(result, base);
+		}
+		base = mul(base, base);
+		exp >>= 1;
+	}
+	result
+}
+
+/// The multiplicative inverse, by Fermat's little theorem.
+fn inv(a: u64) -> u64 {
+	pow(a, P - 2)
+}
+
+/// Evaluate, at `x`, the unique polynomial of lowest degree passing through the given points.
+fn interpolate(points: &[(u64, u64)], x: u64) -> u64 {
+	points.iter().enumerate().fold(0, |acc, (i, (xi, yi))| {
+		let (numerator, denominator) = points
+			.iter()
+			.enumerate()
+			.filter(|(j, _)| *j != i)
+			.fold((1, 1), |(num, den), (_, (xj, _))| {
+				(mul(num, sub(x, *xj)), mul(den, sub(*xi, *xj)))
+			});
+		add(acc, mul(*yi, mul(numerator, inv(denominator))))
+	})
+}
+
+/// Extend `k` field elements to `2k` so that any `k` of the results can rebuild the original. The
+/// first `k` outputs are the input itself.
+pub fn encode(data: &[u64]) -> Vec<u64> {
+	let points: Vec<(u64, u64)> =
+		data.iter().enumerate().map(|(i, value)| (i as u64, *value)).collect();
+	let k = data.len() as u64;
+	data.iter()
+		.copied()
+		.chain((k..2 * k).map(|x| interpolate(&points, x)))
+		.collect()
+}
+
+/// Rebuild the original `k` field elements from any `k` of the encoded chunks. Returns `None` if
+/// there are not enough distinct chunks.
+pub fn reconstruct(k: usize, chunks: &[Chunk]) -> Option<Vec<u64>> {
+	let mut points: Vec<(u64, u64)> = Vec::with_capacity(k);
+	for chunk in chunks {
+		if points.len() == k {
+			break;
+		}
+		if !points.iter().any(|(x, _)| *x == chunk.index as u64) {
+			points.push((chunk.index as u64, chunk.value));
+		}
+	}
+	if points.len() < k {
+		return None;
+	}
+	Some((0..k as u64).map(|x| interpolate(&points, x)).collect())
+}
+
+/// Split each body word into two field elements.
+fn body_to_elements(body: &[u64]) -> Vec<u64> {
+	body.iter().flat_map(|word| [word >> 32, word & 0xffff_ffff]).collect()
+}
+
+/// The inverse of `body_to_elements`.
+fn elements_to_body(elements: &[u64]) -> Vec<u64> {
+	elements.chunks(2).map(|pair| (pair[0] << 32) | pair[1]).collect()
+}
+
+/// One piece of an erasure coded body.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Chunk {
+	/// The position of this chunk among all chunks.
+	pub index: usize,
+	/// The encoded field element.
+	pub value: u64,
+}
+
+/// What a header commits to so that light nodes can sample the body.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AvailabilityCommitment {
+	/// The Merkle root over all chunk values.
+	pub chunks_root: Hash,
+	/// How many chunks there are.
+	pub chunk_count: usize,
+}
+
+/// A block body together with its erasure coding. Held by the block author and full nodes, who
+/// serve chunks to light nodes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ErasureCodedBody {
+	chunks: Vec<u64>,
+	tree: MerkleTree,
+}
+
+impl ErasureCodedBody {
+	/// Erasure code a block body.
+	pub fn new(body: &[u64]) -> Self {
+		let chunks = encode(&body_to_elements(body));
+		let tree = MerkleTree::new(&chunks);
+		ErasureCodedBody { chunks, tree }
+	}
+
+	/// The commitment to put in the header.
+	pub fn commitment(&self) -> AvailabilityCommitment {
+		AvailabilityCommitment { chunks_root: self.tree.root(), chunk_count: self.chunks.len() }
+	}
+
+	/// Serve a chunk along with the proof that it belongs under the commitment.
+	pub fn chunk(&self, index: usize) -> Option<(Chunk, MerkleProof)> {
+		let value = *self.chunks.get(index)?;
+		Some((Chunk { index, value }, self.tree.proof(index)?))
+	}
+}
+
+/// Rebuild a block body from any half of its chunks.
+///
+/// Returns `None` if there are too few chunks, or if no body could have the commitment's chunk
+/// count. Each body word is two field elements and encoding doubles them, so an honest count is a
+/// multiple of four. The commitment comes from a header, so it may not be honest.
+pub fn recover_body(commitment: &AvailabilityCommitment, chunks: &[Chunk]) -> Option<Vec<u64>> {
+	if !commitment.chunk_count.is_multiple_of(4) {
+		return None;
+	}
+	Some(elements_to_body(&reconstruct(commitment.chunk_count / 2, chunks)?))
+}
+
+/// Check that a served chunk really is the chunk at its index under the commitment.
+pub fn verify_chunk(
+	commitment: &AvailabilityCommitment,
+	chunk: &Chunk,
+	proof: &MerkleProof,
+) -> bool {
+	proof.leaf_index == chunk.index
+		&& proof.leaf_count == commitment.chunk_count
+		&& verify_proof(commitment.chunks_root, &chunk.value, proof)
+}
+
+/// The probability that a body is available after this many successful samples.
+pub fn confidence(samples: u32) -> f64 {
+	// Each sample misses a withheld chunk with probability at most one half.
+	1.0 - (0..samples).fold(1.0, |miss, _| miss * 0.5)
+}
+
+/// A light node that checks availability by sampling.
+pub struct LightNode {
+	/// Each light node picks different chunks, so the author cannot serve exactly the chunks
+	/// everyone asks for and nothing else.
+	pub seed: u64,
+	/// How many chunks to sample per block.
+	pub samples: u32,
+}
+
+impl LightNode {
+	/// Sample random chunks through `fetch`, which returns `None` if the chunk is withheld. The
+	/// body is considered available only if every sampled chunk is served with a valid proof.
+	pub fn is_available(
+		&self,
+		commitment: &AvailabilityCommitment,
+		fetch: impl Fn(usize) -> Option<(Chunk, MerkleProof)>,
+	) -> bool {
+		if commitment.chunk_count == 0 {
+			return true;
+		}
+		(0..self.samples).all(|sample| {
+			let index =
+				(hash(&(self.seed, commitment, sample)) % commitment.chunk_count as u64) as usize;
+			match fetch(index) {
+				Some((chunk, proof)) => {
+					chunk.index == index && verify_chunk(commitment, &chunk, &proof)
+				},
+				None => false,
+			}
+		})
+	}
+}
+
+#[test]
+fn erasure_any_half_reconstructs() {
+	let data = vec![5, 17, 0, 99];
+	let encoded = encode(&data);
+	assert_eq!(encoded.len(), 8);
+	assert_eq!(&encoded[..4], data.as_slice());
+
+	for subset in [[0, 1, 2, 3], [4, 5, 6, 7], [1, 3, 5, 7], [7, 0, 6, 2]] {
+		let chunks: Vec<Chunk> = subset
+			.iter()
+			.map(|index| Chunk { index: *index, value: encoded[*index] })
+			.collect();
+		assert_eq!(reconstruct(4, &chunks), Some(data.clone()));
+	}
+}
+
+#[test]
+fn erasure_too_few_chunks_fails() {
+	let encoded = encode(&[5, 17, 0, 99]);
+	let chunks: Vec<Chunk> =
+		[0, 5, 5].iter().map(|i| Chunk { index: *i, value: encoded[*i] }).collect();
+
+	assert_eq!(reconstruct(4, &chunks), None);
+}
+
+#[test]
+fn erasure_body_round_trip() {
+	let body = vec![u64::MAX, 0, 1234567890123, 42];
+	let coded = ErasureCodedBody::new(&body);
+	let commitment = coded.commitment();
+	let chunks: Vec<Chunk> = (8..16).map(|i| coded.chunk(i).unwrap().0).collect();
+
+	assert_eq!(recover_body(&commitment, &chunks), Some(body));
+}
+
+#[test]
+fn erasure_malformed_commitment_recovers_nothing() {
+	let coded = ErasureCodedBody::new(&[1, 2, 3]);
+	let chunks: Vec<Chunk> = (0..12).map(|i| coded.chunk(i).unwrap().0).collect();
+
+	// Half of six chunks is three field elements, which is not a whole number of body words.
+	for chunk_count in [6, 10, 13] {
+		let malformed = AvailabilityCommitment { chunk_count, ..coded.commitment() };
+		assert_eq!(recover_body(&malformed, &chunks), None);
+	}
+}
+
+#[test]
+fn erasure_chunk_proofs_verify() {
+	let coded = ErasureCodedBody::new(&[1, 2, 3]);
+	let commitment = coded.commitment();
+	let (chunk, proof) = coded.chunk(4).unwrap();
+
+	assert!(verify_chunk(&commitment, &chunk, &proof));
+	assert!(!verify_chunk(&commitment, &Chunk { value: chunk.value + 1, ..chunk }, &proof));
+	assert!(!verify_chunk(&commitment, &Chunk { index: 3, ..chunk }, &proof));
+}
+
+#[test]
+fn erasure_sampling_accepts_available_body() {
+	let coded = ErasureCodedBody::new(&[1, 2, 3, 4, 5]);
+	let node = LightNode { seed: 7, samples: 16 };
+
+	assert!(node.is_available(&coded.commitment(), |index| coded.chunk(index)));
+}
+
+#[test]
+fn erasure_sampling_catches_withheld_body() {
+	// The author withholds just over half of the chunks, so the body cannot be recovered.
+	let coded = ErasureCodedBody::new(&[1, 2, 3, 4, 5, 6, 7, 8]);
+	let commitment = coded.commitment();
+	let withheld = commitment.chunk_count / 2 + 1;
+	let fetch = |index: usize| if index < withheld { None } else { coded.chunk(index) };
+
+	let caught = (0..10)
+		.filter(|seed| !LightNode { seed: *seed, samples: 16 }.is_available(&commitment, fetch))
+		.count();
+	assert_eq!(caught, 10);
+}
+
+#[test]
+fn erasure_confidence_grows_with_samples() {
+	assert_eq!(confidence(0), 0.0);
+	assert!(confidence(10) > 0.999);
+	assert!(confidence(10) < confidence(11));
+}
@@ -0,0 +1,130 @@
+//! Hash functions that blocks can be built with.
+//!
+//! The chapters hash everything with `crate::hash`, a 64 bit hash that is quick and easy to print,
+//! which is all the early lessons need. It is not collision resistant though, so a chain that
+//! wants its parent hashes and state roots to mean something needs a real hash function. The
+//! `Hasher` trait lets a block type be written once and built with either.
+//!
+//! So far only the blocks of chapter 2 part 6, the state store and signed extrinsics built on
+//! them, and the Merkle tree are generic over a `Hasher`. The earlier parts of chapter 2 and the
+//! consensus engines of chapter 3 still hash with `crate::hash` directly.
+//!
+//! Both hashers hash the same bytes: whatever a value's `Hash` implementation writes, as described
+//! in `crate::vectors`. Only the function applied to those bytes differs.
+
+use super::sha256::sha256;
+use alloc::vec::Vec;
+use core::{fmt::Debug, hash::Hash};
+
+/// A hash function for block headers and state. Hashers are unit structs, and the traits they
+/// derive let the types generic over them derive the same traits.
+pub trait Hasher: Clone + Debug + Eq + Hash {
+	/// The hash itself.
+	type Output: Copy + Debug + Default + Eq + Ord + Hash + Send + Sync;
+
+	/// Hash a value.
+	fn digest<T: Hash>(t: &T) -> Self::Output;
+
+	/// The first eight bytes of the hash as a number. Proof of work compares this against its
+	/// threshold, so the same threshold means the same difficulty whichever hasher is used.
+	fn leading_u64(output: &Self::Output) -> u64;
+}
+
+/// The 64 bit hash the tutorial uses everywhere, `crate::hash`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "scale", derive(codec::Encode, codec::Decode))]
+pub struct ToyHasher;
+
+impl Hasher for ToyHasher {
+	type Output = u64;
+
+	fn digest<T: Hash>(t: &T) -> u64 {
+		crate::hash(t)
+	}
+
+	fn leading_u64(output: &u64) -> u64 {
+		*output
+	}
+}
+
+/// SHA-256 of the bytes `crate::hash` would hash.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "scale", derive(codec::Encode, codec::Decode))]
+pub struct Sha256;
+
+impl Hasher for Sha256 {
+	type Output = [u8; 32];
+
+	fn digest<T: Hash>(t: &T) -> [u8; 32] {
+		sha256(&encoded(t))
+	}
+
+	fn leading_u64(output: &[u8; 32]) -> u64 {
+		let mut leading = [0; 8];
+		leading.copy_from_slice(&output[..8]);
+		u64::from_be_bytes(leading)
+	}
+}
+
+/// A `core::hash::Hasher` that keeps the bytes written to it instead of hashing them.
+#[derive(Default)]
+struct RecordingHasher(Vec<u8>);
+
+impl core::hash::Hasher for RecordingHasher {
+	fn write(&mut self, bytes: &[u8]) {
+		self.0.extend_from_slice(bytes);
+	}
+
+	// Integers are recorded the way `crate::hash` pins them, so the bytes are the same on every
+	// platform.
+	fn write_u16(&mut self, i: u16) {
+		self.write(&i.to_le_bytes());
+	}
+
+	fn write_u32(&mut self, i: u32) {
+		self.write(&i.to_le_bytes());
+	}
+
+	fn write_u64(&mut self, i: u64) {
+		self.write(&i.to_le_bytes());
+	}
+
+	fn write_u128(&mut self, i: u128) {
+		self.write(&i.to_le_bytes());
+	}
+
+	fn write_usize(&mut self, i: usize) {
+		self.write_u64(i as u64);
+	}
+
+	fn finish(&self) -> u64 {
+		unreachable!("the recording hasher is only used to collect bytes")
+	}
+}
+
+/// The bytes `crate::hash` hashes when given this value.
+pub fn encoded<T: Hash>(t: &T) -> Vec<u8> {
+	let mut recorder = RecordingHasher::default();
+	t.hash(&mut recorder);
+	recorder.0
+}
+
+#[test]
+fn hasher_both_hash_the_same_bytes() {
+	let value = (7u64, vec![1u64, 2, 3]);
+
+	assert_eq!(ToyHasher::digest(&value), crate::hash(&value));
+	assert_eq!(Sha256::digest(&value), sha256(&encoded(&value)));
+	assert_ne!(Sha256::digest(&1u64), Sha256::digest(&2u64));
+}
+
+#[test]
+fn hasher_leading_u64_is_big_endian() {
+	let mut output = [0xff; 32];
+	output[..8].copy_from_slice(&[0, 0, 0, 0, 0, 0, 1, 2]);
+
+	assert_eq!(Sha256::leading_u64(&output), 0x0102);
+	assert_eq!(ToyHasher::leading_u64(&42), 42);
+}
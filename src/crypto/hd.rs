@@ -0,0 +1,153 @@
+//! Hierarchical deterministic (HD) key derivation.
+//!
+//! Managing many unrelated secret keys is a chore, and backing them up is worse. HD derivation
+//! instead starts from a single secret, usually written down as a seed phrase, and derives any
+//! number of child keys from it along named paths. As long as you keep the phrase, every key can be
+//! recreated.
+//!
+//! We follow Substrate's notation for paths. A path is a list of _junctions_, each written as
+//! `//name`, so the dev account Alice lives at `//Alice` and Alice's stash account at
+//! `//Alice//stash`. The double slash marks a _hard_ junction: the child seed is a hash of the
+//! parent seed and the junction name, so knowing a child key reveals nothing about its parent or
+//! its siblings.
+//! Substrate also supports soft junctions (a single slash), which allow deriving child _public_
+//! keys from a parent public key. Those need algebra on the keys themselves, so we only support
+//! hard junctions here.
+//!
+//! The derivation itself is a toy. It expands our 64 bit hash into 32 bytes, which is fine for
+//! reproducible test accounts but not for protecting real funds.
+
+use super::sig::Pair;
+use crate::hash;
+use alloc::vec::Vec;
+use core::hash::Hash as StdHash;
+
+/// The well known development phrase. Every dev account (`//Alice`, `//Bob`, ...) is derived from
+/// it, so tests, chain specs, and the simulator all agree on the same keys. It is public, so never
+/// use it for anything of value.
+pub const DEV_PHRASE: &str =
+	"bottom drive obey lake curtain smoke basket hold race lonely fit walk";
+
+/// The ways a derivation path can be invalid.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeriveError {
+	/// A junction has no name, as in `//Alice////stash`.
+	EmptyJunction,
+	/// The path contains a soft junction (a single slash), which this module does not support.
+	SoftJunction,
+}
+
+/// Expand some hashable input into 32 bytes of seed material by hashing it with a counter.
+fn expand<T: StdHash>(input: &T) -> [u8; 32] {
+	let mut seed = [0; 32];
+	for (i, chunk) in seed.chunks_mut(8).enumerate() {
+		chunk.copy_from_slice(&hash(&(input, i)).to_be_bytes());
+	}
+	seed
+}
+
+/// Turn a seed phrase into the master seed at the root of the derivation tree.
+pub fn master_seed(phrase: &str) -> [u8; 32] {
+	// Normalize whitespace so that a phrase copied with extra spaces still gives the same keys.
+	let words: Vec<&str> = phrase.split_whitespace().collect();
+	expand(&(b"MASTER", words))
+}
+
+/// Derive the child seed for a single hard junction.
+pub fn derive_hard(parent: &[u8; 32], junction: &str) -> [u8; 32] {
+	expand(&(b"HARD", parent, junction))
+}
+
+/// Split a path such as `//Alice//stash` into its junction names.
+pub fn parse_path(path: &str) -> Result<Vec<&str>, DeriveError> {
+	if path.is_empty() {
+		return Ok(Vec::new());
+	}
+	let Some(rest) = path.strip_prefix("//") else {
+		return Err(DeriveError::SoftJunction);
+	};
+	rest.split("//")
+		.map(|junction| {
+			if junction.is_empty() {
+				Err(DeriveError::EmptyJunction)
+			} else if junction.contains('/') {
+				Err(DeriveError::SoftJunction)
+			} else {
+				Ok(junction)
+			}
+		})
+		.collect()
+}
+
+/// Derive the seed at the given path below the parent seed.
+pub fn derive_path(parent: &[u8; 32], path: &str) -> Result<[u8; 32], DeriveError> {
+	Ok(parse_path(path)?
+		.into_iter()
+		.fold(*parent, |seed, junction| derive_hard(&seed, junction)))
+}
+
+/// Derive the seed described by a secret URI: a seed phrase followed by a path, such as
+/// `"<phrase>//Alice"`. If the phrase is left out, as in `"//Alice"`, the dev phrase is used.
+pub fn seed_from_uri(uri: &str) -> Result<[u8; 32], DeriveError> {
+	let (phrase, path) = match uri.find('/') {
+		Some(index) => uri.split_at(index),
+		None => (uri, ""),
+	};
+	let phrase = if phrase.trim().is_empty() { DEV_PHRASE } else { phrase };
+	derive_path(&master_seed(phrase), path)
+}
+
+/// Create the key pair described by a secret URI in any signature scheme.
+pub fn pair_from_uri<P: Pair>(uri: &str) -> Result<P, DeriveError> {
+	Ok(P::from_seed(&seed_from_uri(uri)?))
+}
+
+/// The dev account with the given name, such as `"Alice"`, derived from the dev phrase.
+pub fn dev_pair<P: Pair>(name: &str) -> P {
+	pair_from_uri(&format!("//{name}")).expect("dev account names are valid junctions")
+}
+
+#[cfg(test)]
+use super::sig::ToyPair;
+
+#[test]
+fn hd_derivation_is_deterministic() {
+	assert_eq!(seed_from_uri("//Alice"), seed_from_uri("//Alice"));
+	assert_ne!(seed_from_uri("//Alice"), seed_from_uri("//Bob"));
+}
+
+#[test]
+fn hd_dev_phrase_is_the_default() {
+	assert_eq!(seed_from_uri("//Alice"), seed_from_uri(&format!("{DEV_PHRASE}//Alice")));
+	assert_eq!(
+		dev_pair::<ToyPair>("Alice").public(),
+		pair_from_uri::<ToyPair>("//Alice").unwrap().public()
+	);
+}
+
+#[test]
+fn hd_phrase_whitespace_is_normalized() {
+	assert_eq!(master_seed("a b  c"), master_seed(" a b c\n"));
+	assert_ne!(master_seed("a b c"), master_seed("a c b"));
+}
+
+#[test]
+fn hd_paths_compose() {
+	let alice = seed_from_uri("//Alice").unwrap();
+
+	assert_eq!(derive_path(&alice, "//stash"), seed_from_uri("//Alice//stash"));
+	assert_eq!(derive_path(&alice, ""), Ok(alice));
+}
+
+#[test]
+fn hd_different_phrases_give_different_keys() {
+	assert_ne!(seed_from_uri("//Alice"), seed_from_uri("some other phrase//Alice"));
+}
+
+#[test]
+fn hd_invalid_paths_are_rejected() {
+	assert_eq!(parse_path("//Alice////stash"), Err(DeriveError::EmptyJunction));
+	assert_eq!(parse_path("/Alice"), Err(DeriveError::SoftJunction));
+	assert_eq!(parse_path("//Alice/stash"), Err(DeriveError::SoftJunction));
+	assert_eq!(parse_path("//Alice//stash"), Ok(vec!["Alice", "stash"]));
+}
@@ -0,0 +1,142 @@
+//! A keyring of well known development identities.
+//!
+//! The chapters name their play accounts Alice, Bob, and Charlie, but as bare enum variants they
+//! cannot sign anything. The `Keyring` gives each of those names real keys, derived from the dev
+//! phrase along the `//Alice`, `//Bob`, ... paths, exactly like Substrate's dev accounts. Anything
+//! that needs a key for a well known identity, from PoA engines to chain specs to tests, should get
+//! it here so that every part of the tutorial agrees on who Alice is.
+
+use super::{address::Address, hd, secret::SecretSeed, sig::Pair};
+use crate::{c1_state_machine::User, c3_consensus::ConsensusAuthority};
+
+/// The well known development identities.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Keyring {
+	Alice,
+	Bob,
+	Charlie,
+	Dave,
+	Eve,
+	Ferdie,
+}
+
+impl Keyring {
+	/// Every identity in the keyring.
+	pub const ALL: [Keyring; 6] = [
+		Keyring::Alice,
+		Keyring::Bob,
+		Keyring::Charlie,
+		Keyring::Dave,
+		Keyring::Eve,
+		Keyring::Ferdie,
+	];
+
+	/// The name of the identity, which is also its derivation junction.
+	pub fn name(self) -> &'static str {
+		match self {
+			Keyring::Alice => "Alice",
+			Keyring::Bob => "Bob",
+			Keyring::Charlie => "Charlie",
+			Keyring::Dave => "Dave",
+			Keyring::Eve => "Eve",
+			Keyring::Ferdie => "Ferdie",
+		}
+	}
+
+	/// The secret seed of the identity. It is derived from the public dev phrase, so it is not
+	/// secret at all.
+	pub fn seed(self) -> SecretSeed {
+		let seed = hd::seed_from_uri(&format!("//{}", self.name()))
+			.expect("keyring names are valid junctions");
+		SecretSeed::new(seed)
+	}
+
+	/// The key pair of the identity in the given signature scheme.
+	pub fn pair<P: Pair>(self) -> P {
+		self.seed().pair()
+	}
+
+	/// The public key of the identity in the given signature scheme.
+	pub fn public<P: Pair>(self) -> P::Public {
+		self.pair::<P>().public()
+	}
+
+	/// Sign a message as this identity.
+	pub fn sign<P: Pair>(self, message: &[u8]) -> P::Signature {
+		self.pair::<P>().sign(message)
+	}
+
+	/// The account address of the identity on the default network.
+	pub fn address<P: Pair>(self) -> Address {
+		Address::from_public(&self.public::<P>())
+	}
+
+	/// Find the identity that owns the given public key, if it belongs to the keyring at all.
+	pub fn from_public<P: Pair>(public: &P::Public) -> Option<Self> {
+		Self::ALL.into_iter().find(|identity| identity.public::<P>() == *public)
+	}
+}
+
+impl From<User> for Keyring {
+	fn from(user: User) -> Self {
+		match user {
+			User::Alice => Keyring::Alice,
+			User::Bob => Keyring::Bob,
+			User::Charlie => Keyring::Charlie,
+		}
+	}
+}
+
+impl From<ConsensusAuthority> for Keyring {
+	fn from(authority: ConsensusAuthority) -> Self {
+		match authority {
+			ConsensusAuthority::Alice => Keyring::Alice,
+			ConsensusAuthority::Bob => Keyring::Bob,
+			ConsensusAuthority::Charlie => Keyring::Charlie,
+		}
+	}
+}
+
+#[cfg(test)]
+use super::sig::ToyPair;
+
+#[test]
+fn keyring_identities_are_distinct() {
+	for (i, first) in Keyring::ALL.iter().enumerate() {
+		for second in &Keyring::ALL[i + 1..] {
+			assert_ne!(first.public::<ToyPair>(), second.public::<ToyPair>());
+		}
+	}
+}
+
+#[test]
+fn keyring_matches_dev_derivation() {
+	assert_eq!(Keyring::Alice.public::<ToyPair>(), hd::dev_pair::<ToyPair>("Alice").public());
+}
+
+#[test]
+fn keyring_signatures_verify() {
+	let signature = Keyring::Bob.sign::<ToyPair>(b"hello");
+
+	assert!(ToyPair::verify(&signature, b"hello", &Keyring::Bob.public::<ToyPair>()));
+	assert!(!ToyPair::verify(&signature, b"hello", &Keyring::Alice.public::<ToyPair>()));
+}
+
+#[test]
+fn keyring_reverse_lookup() {
+	assert_eq!(
+		Keyring::from_public::<ToyPair>(&Keyring::Charlie.public::<ToyPair>()),
+		Some(Keyring::Charlie)
+	);
+	assert_eq!(Keyring::from_public::<ToyPair>(&ToyPair::from_seed(&[0; 32]).public()), None);
+}
+
+#[test]
+fn keyring_converts_from_chapter_identities() {
+	assert_eq!(Keyring::from(User::Bob), Keyring::Bob);
+	assert_eq!(Keyring::from(ConsensusAuthority::Charlie), Keyring::Charlie);
+	assert_eq!(
+		Keyring::from(ConsensusAuthority::Alice).address::<ToyPair>(),
+		Keyring::Alice.address::<ToyPair>()
+	);
+}
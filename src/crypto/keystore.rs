@@ -0,0 +1,318 @@
+//! An encrypted file format for secret key seeds.
+//!
+//! Anyone who can read a secret key can sign as its owner, so even a teaching project should not
+//! leave keys lying around in plaintext. A keystore file holds a key seed encrypted under a key
+//! derived from a password:
+//! 1. The password is stretched with scrypt. scrypt is deliberately slow and memory hungry, so an
+//!    attacker who steals the file cannot cheaply guess passwords. A random salt ensures two files
+//!    with the same password still use different encryption keys.
+//! 2. The seed is encrypted with XChaCha20-Poly1305. Besides hiding the seed, the Poly1305 tag lets
+//!    us tell a wrong password apart from a correct one, because decryption fails authentication.
+//!
+//! This module is only compiled with the `keystore` feature.
+
+use super::secret::{zeroize, Secret, SecretSeed};
+use crate::hex;
+use chacha20poly1305::{
+	aead::{Aead, KeyInit},
+	Key, XChaCha20Poly1305, XNonce,
+};
+use std::{fs, io, path::Path};
+
+/// The first line of every keystore file. Bump the version if the format ever changes.
+const HEADER: &str = "bfs-keystore v1";
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// The largest `log_n` we accept. scrypt needs `128 * r * 2^log_n` bytes of memory, so a file that
+/// asks for much more than this could make `unlock` exhaust the machine before the password is
+/// even checked.
+const MAX_LOG_N: u8 = 20;
+
+/// The largest block size and parallelization factor we accept. The work scrypt does grows with
+/// both.
+const MAX_R: u32 = 32;
+const MAX_P: u32 = 16;
+
+/// The most memory scrypt may use, in bytes.
+const MAX_MEMORY: u64 = 1 << 30;
+
+/// The cost parameters for scrypt. They are stored in the file so they can be raised later without
+/// breaking old files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KdfParams {
+	/// Log base two of the CPU/memory cost.
+	pub log_n: u8,
+	/// The block size.
+	pub r: u32,
+	/// The parallelization factor.
+	pub p: u32,
+}
+
+impl KdfParams {
+	/// Whether the parameters are cheap enough to run. The limits are well above the defaults, so
+	/// they only reject files that were crafted or corrupted.
+	fn within_limits(&self) -> bool {
+		(1..=MAX_LOG_N).contains(&self.log_n)
+			&& (1..=MAX_R).contains(&self.r)
+			&& (1..=MAX_P).contains(&self.p)
+			&& (128 * u64::from(self.r)) << self.log_n <= MAX_MEMORY
+	}
+}
+
+impl Default for KdfParams {
+	/// The parameters recommended by the scrypt authors for interactive logins.
+	fn default() -> Self {
+		KdfParams { log_n: 15, r: 8, p: 1 }
+	}
+}
+
+/// An encrypted key seed, ready to be written to disk.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Keystore {
+	kdf: KdfParams,
+	salt: [u8; SALT_LEN],
+	nonce: [u8; NONCE_LEN],
+	ciphertext: Vec<u8>,
+}
+
+/// The ways a keystore operation can fail.
+#[derive(Debug)]
+pub enum KeystoreError {
+	/// Reading or writing the file failed.
+	Io(io::Error),
+	/// The file is not a valid keystore. The message says which part is wrong.
+	Malformed(String),
+	/// The password does not decrypt this keystore.
+	WrongPassword,
+	/// The scrypt parameters are out of range, or too expensive to run.
+	InvalidKdfParams,
+	/// The operating system could not provide randomness for the salt and nonce.
+	NoRandomness,
+}
+
+impl From<io::Error> for KeystoreError {
+	fn from(e: io::Error) -> Self {
+		KeystoreError::Io(e)
+	}
+}
+
+/// Stretch the password into a 32 byte encryption key.
+fn derive_key(password: &str, salt: &[u8], kdf: KdfParams) -> Result<Secret<32>, KeystoreError> {
+	if !kdf.within_limits() {
+		return Err(KeystoreError::InvalidKdfParams);
+	}
+	let params = scrypt::Params::new(kdf.log_n, kdf.r, kdf.p, 32)
+		.map_err(|_| KeystoreError::InvalidKdfParams)?;
+	let mut key = Secret::new([0; 32]);
+	scrypt::scrypt(password.as_bytes(), salt, &params, key.expose_mut())
+		.map_err(|_| KeystoreError::InvalidKdfParams)?;
+	Ok(key)
+}
+
+impl Keystore {
+	/// Encrypt the seed under the password using the default scrypt parameters.
+	pub fn create(seed: &[u8; 32], password: &str) -> Result<Self, KeystoreError> {
+		Self::create_with_params(seed, password, KdfParams::default())
+	}
+
+	/// Encrypt the seed under the password using the given scrypt parameters.
+	pub fn create_with_params(
+		seed: &[u8; 32],
+		password: &str,
+		kdf: KdfParams,
+	) -> Result<Self, KeystoreError> {
+		let mut salt = [0; SALT_LEN];
+		let mut nonce = [0; NONCE_LEN];
+		getrandom::getrandom(&mut salt).map_err(|_| KeystoreError::NoRandomness)?;
+		getrandom::getrandom(&mut nonce).map_err(|_| KeystoreError::NoRandomness)?;
+
+		let key = derive_key(password, &salt, kdf)?;
+		let cipher = XChaCha20Poly1305::new(Key::from_slice(key.expose()));
+		let ciphertext = cipher
+			.encrypt(XNonce::from_slice(&nonce), seed.as_slice())
+			.expect("encrypting 32 bytes in memory cannot fail");
+		Ok(Keystore { kdf, salt, nonce, ciphertext })
+	}
+
+	/// Decrypt the seed with the given password.
+	pub fn unlock(&self, password: &str) -> Result<SecretSeed, KeystoreError> {
+		let key = derive_key(password, &self.salt, self.kdf)?;
+		let cipher = XChaCha20Poly1305::new(Key::from_slice(key.expose()));
+		let mut plaintext = cipher
+			.decrypt(XNonce::from_slice(&self.nonce), self.ciphertext.as_slice())
+			.map_err(|_| KeystoreError::WrongPassword)?;
+		let seed = plaintext.as_slice().try_into().map(SecretSeed::new);
+		// The plaintext buffer holds a copy of the seed, so wipe it before it is freed.
+		zeroize(&mut plaintext);
+		seed.map_err(|_| KeystoreError::Malformed("seed is not 32 bytes".into()))
+	}
+
+	/// Re-encrypt the same seed under a new password. A fresh salt and nonce are chosen, so the
+	/// old file reveals nothing about the new one.
+	pub fn rotate_password(&self, old: &str, new: &str) -> Result<Self, KeystoreError> {
+		let seed = self.unlock(old)?;
+		Self::create_with_params(seed.expose(), new, self.kdf)
+	}
+
+	/// Render the keystore in its on-disk text format.
+	pub fn to_file_contents(&self) -> String {
+		format!(
+			"{HEADER}\nscrypt {} {} {}\nsalt {}\nnonce {}\nciphertext {}\n",
+			self.kdf.log_n,
+			self.kdf.r,
+			self.kdf.p,
+			hex::encode(&self.salt),
+			hex::encode(&self.nonce),
+			hex::encode(&self.ciphertext),
+		)
+	}
+
+	/// Parse a keystore from its on-disk text format.
+	pub fn from_file_contents(contents: &str) -> Result<Self, KeystoreError> {
+		let mut lines = contents.lines();
+		if lines.next() != Some(HEADER) {
+			return Err(KeystoreError::Malformed("missing keystore header".into()));
+		}
+		let kdf = field(lines.next(), "scrypt")?;
+		let kdf: Vec<&str> = kdf.split(' ').collect();
+		let [log_n, r, p] = kdf[..] else {
+			return Err(KeystoreError::Malformed("scrypt needs three parameters".into()));
+		};
+		let number = |s: &str| {
+			s.parse::<u32>()
+				.map_err(|_| KeystoreError::Malformed("bad scrypt parameter".into()))
+		};
+		let kdf = KdfParams {
+			log_n: number(log_n)?
+				.try_into()
+				.map_err(|_| KeystoreError::Malformed("bad scrypt parameter".into()))?,
+			r: number(r)?,
+			p: number(p)?,
+		};
+		if !kdf.within_limits() {
+			return Err(KeystoreError::InvalidKdfParams);
+		}
+		let salt = from_hex(field(lines.next(), "salt")?)?
+			.try_into()
+			.map_err(|_| KeystoreError::Malformed("salt has the wrong length".into()))?;
+		let nonce = from_hex(field(lines.next(), "nonce")?)?
+			.try_into()
+			.map_err(|_| KeystoreError::Malformed("nonce has the wrong length".into()))?;
+		let ciphertext = from_hex(field(lines.next(), "ciphertext")?)?;
+		Ok(Keystore { kdf, salt, nonce, ciphertext })
+	}
+
+	/// Write the keystore to a file.
+	pub fn save(&self, path: &Path) -> Result<(), KeystoreError> {
+		Ok(fs::write(path, self.to_file_contents())?)
+	}
+
+	/// Read a keystore from a file.
+	pub fn load(path: &Path) -> Result<Self, KeystoreError> {
+		Self::from_file_contents(&fs::read_to_string(path)?)
+	}
+}
+
+/// Take the value out of a `name value` line.
+fn field<'a>(line: Option<&'a str>, name: &str) -> Result<&'a str, KeystoreError> {
+	line.and_then(|line| line.strip_prefix(name))
+		.and_then(|rest| rest.strip_prefix(' '))
+		.ok_or_else(|| KeystoreError::Malformed(format!("missing {name} line")))
+}
+
+fn from_hex(s: &str) -> Result<Vec<u8>, KeystoreError> {
+	hex::decode(s).map_err(|e| KeystoreError::Malformed(format!("invalid hex: {e:?}")))
+}
+
+/// Cheap scrypt parameters so the tests run quickly. Never use these for real keys.
+#[cfg(test)]
+const TEST_KDF: KdfParams = KdfParams { log_n: 4, r: 8, p: 1 };
+
+#[test]
+fn keystore_unlock_with_correct_password() {
+	let keystore = Keystore::create_with_params(&[7; 32], "hunter2", TEST_KDF).unwrap();
+
+	assert_eq!(keystore.unlock("hunter2").unwrap().expose(), &[7; 32]);
+}
+
+#[test]
+fn keystore_wrong_password_fails() {
+	let keystore = Keystore::create_with_params(&[7; 32], "hunter2", TEST_KDF).unwrap();
+
+	assert!(matches!(keystore.unlock("hunter3"), Err(KeystoreError::WrongPassword)));
+}
+
+#[test]
+fn keystore_does_not_contain_plaintext_seed() {
+	let keystore = Keystore::create_with_params(&[7; 32], "hunter2", TEST_KDF).unwrap();
+
+	assert!(!keystore.to_file_contents().contains(&hex::encode(&[7; 32])[2..]));
+}
+
+#[test]
+fn keystore_rotate_password() {
+	let keystore = Keystore::create_with_params(&[7; 32], "hunter2", TEST_KDF).unwrap();
+	let rotated = keystore.rotate_password("hunter2", "correct horse").unwrap();
+
+	assert_eq!(rotated.unlock("correct horse").unwrap().expose(), &[7; 32]);
+	assert!(matches!(rotated.unlock("hunter2"), Err(KeystoreError::WrongPassword)));
+	assert!(matches!(
+		keystore.rotate_password("wrong", "correct horse"),
+		Err(KeystoreError::WrongPassword)
+	));
+}
+
+#[test]
+fn keystore_file_round_trip() {
+	let keystore = Keystore::create_with_params(&[7; 32], "hunter2", TEST_KDF).unwrap();
+	let path = std::env::temp_dir().join(format!("bfs-keystore-test-{}", std::process::id()));
+	keystore.save(&path).unwrap();
+	let loaded = Keystore::load(&path).unwrap();
+	fs::remove_file(&path).unwrap();
+
+	assert_eq!(loaded, keystore);
+	assert_eq!(loaded.unlock("hunter2").unwrap().expose(), &[7; 32]);
+}
+
+#[test]
+fn keystore_tampered_ciphertext_fails() {
+	let mut keystore = Keystore::create_with_params(&[7; 32], "hunter2", TEST_KDF).unwrap();
+	keystore.ciphertext[0] ^= 1;
+
+	assert!(matches!(keystore.unlock("hunter2"), Err(KeystoreError::WrongPassword)));
+}
+
+#[test]
+fn keystore_malformed_file_is_rejected() {
+	assert!(matches!(
+		Keystore::from_file_contents("not a keystore"),
+		Err(KeystoreError::Malformed(_))
+	));
+}
+
+#[test]
+fn keystore_expensive_kdf_params_are_rejected() {
+	let too_costly = [
+		KdfParams { log_n: MAX_LOG_N + 1, r: 1, p: 1 },
+		KdfParams { log_n: MAX_LOG_N, r: 9, p: 1 },
+		KdfParams { log_n: 4, r: MAX_R + 1, p: 1 },
+		KdfParams { log_n: 4, r: 8, p: MAX_P + 1 },
+		KdfParams { log_n: 4, r: 0, p: 1 },
+	];
+	let keystore = Keystore::create_with_params(&[7; 32], "hunter2", TEST_KDF).unwrap();
+
+	for kdf in too_costly {
+		assert!(matches!(
+			Keystore::create_with_params(&[7; 32], "hunter2", kdf),
+			Err(KeystoreError::InvalidKdfParams)
+		));
+		let file = Keystore { kdf, ..keystore.clone() }.to_file_contents();
+		assert!(matches!(
+			Keystore::from_file_contents(&file),
+			Err(KeystoreError::InvalidKdfParams)
+		));
+	}
+	assert!(KdfParams::default().within_limits());
+}
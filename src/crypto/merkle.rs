@@ -0,0 +1,356 @@
+//! A binary Merkle tree over an ordered list of leaves.
+//!
+//! So far every commitment in this tutorial has been a flat hash of an entire collection. That is
+//! a perfectly good commitment, but the only way to check that a single item belongs to it is to
+//! re-hash the whole collection. A Merkle tree commits to the same data, but also lets us prove
+//! that one particular leaf is included by revealing only a logarithmic number of sibling hashes.
+//!
+//! Unbalanced trees are supported. When a layer has an odd number of nodes, the last node is
+//! promoted to the next layer unchanged rather than being paired with a copy of itself.
+//!
+//! Leaves and inner nodes are hashed with different tags in front. Without them, a pair of child
+//! hashes hashes to the same value whether it is an inner node or a leaf that happens to be a pair
+//! of numbers. Then the tree over `[a, b, c, d]` and the tree over the two leaves `(hash(a),
+//! hash(b))` and `(hash(c), hash(d))` have the same root, and a proof for one is a proof for the
+//! other.
+//!
+//! Like the blocks of chapter 2 part 6, the tree is generic over the `Hasher` that computes its
+//! hashes. `MerkleTree` and `MerkleProof` use the toy hash.
+
+use super::hasher::{Hasher, ToyHasher};
+use alloc::vec::Vec;
+use core::hash::Hash as StdHash;
+
+type Hash = u64;
+
+/// The root of a tree with no leaves at all. This matches the convention from chapter 2 that a
+/// block with no extrinsics has an extrinsics root of zero.
+pub const EMPTY_ROOT: Hash = 0;
+
+/// Written before a leaf when hashing it.
+pub(crate) const LEAF_TAG: u8 = 0;
+
+/// Written before two child hashes when hashing them into their parent.
+pub(crate) const NODE_TAG: u8 = 1;
+
+/// Hash a leaf.
+fn hash_leaf<H: Hasher>(leaf: &impl StdHash) -> H::Output {
+	H::digest(&(LEAF_TAG, leaf))
+}
+
+/// Combine two child hashes into their parent hash.
+fn hash_pair<H: Hasher>(left: H::Output, right: H::Output) -> H::Output {
+	H::digest(&(NODE_TAG, left, right))
+}
+
+/// A fully built Merkle tree. Every layer is stored so that proofs can be generated for any leaf
+/// without re-hashing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GenericMerkleTree<H: Hasher> {
+	/// The first layer is the leaf hashes and the last layer holds only the root.
+	layers: Vec<Vec<H::Output>>,
+}
+
+/// A Merkle tree hashed with the toy hash.
+pub type MerkleTree = GenericMerkleTree<ToyHasher>;
+
+/// Everything a verifier needs, in addition to the leaf itself and the root, to confirm that the
+/// leaf is included in the tree.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GenericMerkleProof<H: Hasher> {
+	/// The position of the leaf in the original list.
+	pub leaf_index: usize,
+	/// How many leaves the tree has. This tells the verifier on which layers the path was
+	/// promoted without a sibling.
+	pub leaf_count: usize,
+	/// The sibling hashes from the bottom of the tree to the top.
+	pub siblings: Vec<H::Output>,
+}
+
+/// A proof for a tree hashed with the toy hash.
+pub type MerkleProof = GenericMerkleProof<ToyHasher>;
+
+impl<H: Hasher> GenericMerkleTree<H> {
+	/// Build a tree over the given leaves.
+	pub fn new<T: StdHash>(leaves: &[T]) -> Self {
+		let mut layers = vec![leaves.iter().map(hash_leaf::<H>).collect::<Vec<_>>()];
+		while layers[layers.len() - 1].len() > 1 {
+			let next = layers[layers.len() - 1]
+				.chunks(2)
+				.map(|pair| match pair {
+					[left, right] => hash_pair::<H>(*left, *right),
+					[single] => *single,
+					_ => unreachable!("chunks(2) yields one or two items"),
+				})
+				.collect();
+			layers.push(next);
+		}
+		GenericMerkleTree { layers }
+	}
+
+	/// The number of leaves in the tree.
+	pub fn len(&self) -> usize {
+		self.layers[0].len()
+	}
+
+	/// Whether the tree has no leaves.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// The root hash committing to every leaf.
+	pub fn root(&self) -> H::Output {
+		match self.layers.last().and_then(|layer| layer.first()) {
+			Some(root) => *root,
+			None => H::Output::default(),
+		}
+	}
+
+	/// Generate an inclusion proof for the leaf at the given index, or `None` if there is no such
+	/// leaf.
+	pub fn proof(&self, leaf_index: usize) -> Option<GenericMerkleProof<H>> {
+		if leaf_index >= self.len() {
+			return None;
+		}
+		let mut siblings = Vec::new();
+		let mut index = leaf_index;
+		for layer in &self.layers[..self.layers.len() - 1] {
+			let sibling = index ^ 1;
+			if sibling < layer.len() {
+				siblings.push(layer[sibling]);
+			}
+			index /= 2;
+		}
+		Some(GenericMerkleProof { leaf_index, leaf_count: self.len(), siblings })
+	}
+}
+
+/// Compute the Merkle root of the given leaves without keeping the tree around.
+pub fn merkle_root<T: StdHash>(leaves: &[T]) -> Hash {
+	MerkleTree::new(leaves).root()
+}
+
+/// Check that the given leaf is included under the given root according to the proof.
+pub fn verify_proof<H: Hasher, T: StdHash>(
+	root: H::Output,
+	leaf: &T,
+	proof: &GenericMerkleProof<H>,
+) -> bool {
+	if proof.leaf_index >= proof.leaf_count {
+		return false;
+	}
+	let mut siblings = proof.siblings.iter();
+	let mut current = hash_leaf::<H>(leaf);
+	let mut index = proof.leaf_index;
+	let mut width = proof.leaf_count;
+	while width > 1 {
+		if index % 2 == 1 {
+			match siblings.next() {
+				Some(left) => current = hash_pair::<H>(*left, current),
+				None => return false,
+			}
+		} else if index + 1 < width {
+			match siblings.next() {
+				Some(right) => current = hash_pair::<H>(current, *right),
+				None => return false,
+			}
+		}
+		// Otherwise this node is the odd one out and is promoted unchanged.
+		index /= 2;
+		width = width.div_ceil(2);
+	}
+	siblings.next().is_none() && current == root
+}
+
+/// Builds a Merkle root one leaf at a time, such as while a block author adds transactions.
+///
+/// Rebuilding the tree after every insertion costs `O(n)` each time and `O(n²)` over a whole
+/// block. Instead, notice that the leaves pushed so far always split into perfect subtrees whose
+/// sizes are the binary digits of the leaf count, largest first. Those subtrees never change again,
+/// so we only keep their roots, called peaks. Pushing a leaf works like incrementing a binary
+/// counter: two peaks of equal size merge into one of twice the size. The root of the whole tree is
+/// the peaks hashed together from right to left, which is exactly the shape `MerkleTree` produces
+/// by promoting odd nodes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IncrementalMerkle {
+	/// The roots of the perfect subtrees, with the height of each, largest first.
+	peaks: Vec<(Hash, u32)>,
+	leaf_count: usize,
+}
+
+impl IncrementalMerkle {
+	/// A builder with no leaves yet.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Append a leaf. Amortized, this hashes only a constant number of pairs.
+	pub fn push<T: StdHash>(&mut self, leaf: &T) {
+		let mut node = (hash_leaf::<ToyHasher>(leaf), 0);
+		while let Some(&(peak, height)) = self.peaks.last() {
+			if height != node.1 {
+				break;
+			}
+			self.peaks.pop();
+			node = (hash_pair::<ToyHasher>(peak, node.0), height + 1);
+		}
+		self.peaks.push(node);
+		self.leaf_count += 1;
+	}
+
+	/// The number of leaves pushed so far.
+	pub fn len(&self) -> usize {
+		self.leaf_count
+	}
+
+	/// Whether no leaves have been pushed.
+	pub fn is_empty(&self) -> bool {
+		self.leaf_count == 0
+	}
+
+	/// The root over every leaf pushed so far, equal to `merkle_root` of the same leaves. This
+	/// costs one hash per peak, which is at most the logarithm of the leaf count.
+	pub fn root(&self) -> Hash {
+		self.peaks
+			.iter()
+			.rev()
+			.map(|(peak, _)| *peak)
+			.reduce(|right, left| hash_pair::<ToyHasher>(left, right))
+			.unwrap_or(EMPTY_ROOT)
+	}
+}
+
+/// `hash_leaf` with the toy hash.
+#[cfg(test)]
+fn toy_leaf(leaf: &impl StdHash) -> Hash {
+	hash_leaf::<ToyHasher>(leaf)
+}
+
+/// `hash_pair` with the toy hash.
+#[cfg(test)]
+fn toy_pair(left: Hash, right: Hash) -> Hash {
+	hash_pair::<ToyHasher>(left, right)
+}
+
+#[test]
+fn merkle_empty_tree_has_empty_root() {
+	let leaves: [u64; 0] = [];
+	assert_eq!(merkle_root(&leaves), EMPTY_ROOT);
+	assert_eq!(MerkleTree::new(&leaves).proof(0), None);
+}
+
+#[test]
+fn merkle_single_leaf_root_is_leaf_hash() {
+	assert_eq!(merkle_root(&[7u64]), toy_leaf(&7u64));
+}
+
+#[test]
+fn merkle_two_leaves() {
+	let root = merkle_root(&[1u64, 2]);
+	assert_eq!(root, toy_pair(toy_leaf(&1u64), toy_leaf(&2u64)));
+}
+
+#[test]
+fn merkle_unbalanced_tree_promotes_odd_node() {
+	let root = merkle_root(&[1u64, 2, 3]);
+	let left = toy_pair(toy_leaf(&1u64), toy_leaf(&2u64));
+	assert_eq!(root, toy_pair(left, toy_leaf(&3u64)));
+}
+
+#[test]
+fn merkle_inner_nodes_are_not_leaves() {
+	let leaves = [1u64, 2, 3, 4];
+	let tree = MerkleTree::new(&leaves);
+	let level_1: Vec<(Hash, Hash)> =
+		tree.layers[0].chunks(2).map(|pair| (pair[0], pair[1])).collect();
+
+	assert_ne!(merkle_root(&level_1), tree.root());
+	assert_ne!(toy_leaf(&level_1[0]), tree.layers[1][0]);
+}
+
+#[test]
+fn merkle_order_matters() {
+	assert_ne!(merkle_root(&[1u64, 2, 3]), merkle_root(&[3u64, 2, 1]));
+}
+
+#[test]
+fn merkle_every_proof_verifies() {
+	for n in 1..=9u64 {
+		let leaves: Vec<u64> = (0..n).collect();
+		let tree = MerkleTree::new(&leaves);
+		for (i, leaf) in leaves.iter().enumerate() {
+			let proof = tree.proof(i).expect("leaf exists");
+			assert!(verify_proof(tree.root(), leaf, &proof));
+		}
+	}
+}
+
+#[test]
+fn merkle_proof_rejects_wrong_leaf() {
+	let leaves = [10u64, 20, 30, 40, 50];
+	let tree = MerkleTree::new(&leaves);
+	let proof = tree.proof(2).expect("leaf exists");
+
+	assert!(!verify_proof(tree.root(), &31u64, &proof));
+}
+
+#[test]
+fn merkle_proof_rejects_wrong_position() {
+	let leaves = [10u64, 20, 30, 40, 50];
+	let tree = MerkleTree::new(&leaves);
+	let mut proof = tree.proof(2).expect("leaf exists");
+	proof.leaf_index = 3;
+
+	assert!(!verify_proof(tree.root(), &30u64, &proof));
+}
+
+#[test]
+fn merkle_proof_rejects_extra_siblings() {
+	let leaves = [10u64, 20, 30, 40];
+	let tree = MerkleTree::new(&leaves);
+	let mut proof = tree.proof(0).expect("leaf exists");
+	proof.siblings.push(0);
+
+	assert!(!verify_proof(tree.root(), &10u64, &proof));
+}
+
+#[test]
+fn merkle_incremental_matches_full_tree() {
+	let mut builder = IncrementalMerkle::new();
+	assert_eq!(builder.root(), EMPTY_ROOT);
+	for n in 1..=40u64 {
+		builder.push(&n);
+		let leaves: Vec<u64> = (1..=n).collect();
+		assert_eq!(builder.root(), merkle_root(&leaves));
+	}
+	assert_eq!(builder.len(), 40);
+}
+
+/// Compare keeping the extrinsics root current while building a 2000 extrinsic block by
+/// recomputing from scratch and incrementally. Run it with
+/// `cargo test --release merkle_incremental_speedup -- --ignored --nocapture`.
+#[cfg(feature = "std")]
+#[test]
+#[ignore]
+fn merkle_incremental_speedup() {
+	use std::time::Instant;
+
+	let extrinsics: Vec<u64> = (0..2000).collect();
+
+	let started = Instant::now();
+	let mut full = EMPTY_ROOT;
+	for n in 1..=extrinsics.len() {
+		full = merkle_root(&extrinsics[..n]);
+	}
+	println!("recompute: {:?}", started.elapsed());
+
+	let started = Instant::now();
+	let mut builder = IncrementalMerkle::new();
+	for extrinsic in &extrinsics {
+		builder.push(extrinsic);
+		builder.root();
+	}
+	println!("incremental: {:?}", started.elapsed());
+
+	assert_eq!(builder.root(), full);
+}
@@ -0,0 +1,22 @@
+//! Cryptographic building blocks shared by the later chapters.
+//!
+//! The chapters themselves deliberately keep cryptography out of the way so that the blockchain
+//! and consensus logic stays front and center. The utilities here are the pieces that several
+//! chapters need in the same shape, so they live in one place instead of being re-written in each
+//! lesson.
+
+pub mod address;
+pub mod erasure;
+pub mod hasher;
+pub mod hd;
+pub mod keyring;
+#[cfg(feature = "keystore")]
+pub mod keystore;
+pub mod merkle;
+pub mod poseidon;
+pub mod schnorr;
+pub mod secret;
+pub mod sha256;
+pub mod sig;
+pub mod sparse_merkle;
+pub mod vrf;
@@ -0,0 +1,208 @@
+//! An arithmetic-friendly hash function in the style of Poseidon.
+//!
+//! Validity proofs (zk-SNARKs, STARKs) let a chain prove that a state transition was executed
+//! correctly without everyone re-executing it. The prover expresses the computation as arithmetic
+//! over a prime field, and the cost of the proof grows with the number of field operations. Hash
+//! functions like SHA-2 or the `DefaultHasher` used throughout this tutorial are built from bit
+//! shuffling, which is extremely expensive to express as field arithmetic. Poseidon is designed
+//! the other way around: its only operations are field additions and multiplications.
+//!
+//! The structure is a sponge over a small state of field elements. Each round adds constants,
+//! raises elements to the seventh power (the S-box), and mixes the state with a matrix
+//! multiplication. Full rounds apply the S-box to every element, while the cheaper partial rounds
+//! in the middle apply it to only one.
+//!
+//! This is a toy instance. The round constants are derived from our own hash rather than the
+//! published generation procedure, and the parameters have not been analyzed. It is good enough to
+//! build the "zk-friendly state commitment" exercise at the bottom of this file, and to plug into
+//! anything expecting a `core::hash::Hasher`.
+
+use crate::hash;
+use alloc::vec::Vec;
+use core::hash::{Hash as StdHash, Hasher};
+
+type Hash = u64;
+
+/// The Goldilocks prime, `2^64 - 2^32 + 1`. Elements fit in a `u64`, and `x^7` is a permutation
+/// because 7 does not divide `P - 1`.
+pub const P: u64 = 0xffff_ffff_0000_0001;
+
+/// The number of field elements in the state.
+const WIDTH: usize = 3;
+
+/// How many elements are absorbed per permutation. The remaining element is the capacity.
+const RATE: usize = 2;
+
+const FULL_ROUNDS: usize = 8;
+const PARTIAL_ROUNDS: usize = 22;
+
+fn add(a: u64, b: u64) -> u64 {
+	((a as u128 + b as u128) % P as u128) as u64
+}
+
+fn mul(a: u64, b: u64) -> u64 {
+	((a as u128 * b as u128) % P as u128) as u64
+}
+
+/// The S-box, `x^7`.
+fn sbox(x: u64) -> u64 {
+	let x2 = mul(x, x);
+	let x4 = mul(x2, x2);
+	mul(mul(x4, x2), x)
+}
+
+/// Mix the state with the matrix `[[2, 1, 1], [1, 2, 1], [1, 1, 2]]`, which is MDS over this field.
+/// Each output is the sum of the state plus one extra copy of its own element.
+fn mix(state: &mut [u64; WIDTH]) {
+	let sum = state.iter().fold(0, |acc, x| add(acc, *x));
+	for x in state.iter_mut() {
+		*x = add(sum, *x);
+	}
+}
+
+/// The round constant for the given round and position.
+fn round_constant(round: usize, position: usize) -> u64 {
+	hash(&(b"POSEIDON", round, position)) % P
+}
+
+/// The Poseidon permutation.
+fn permute(state: &mut [u64; WIDTH]) {
+	let half = FULL_ROUNDS / 2;
+	for round in 0..FULL_ROUNDS + PARTIAL_ROUNDS {
+		for (position, x) in state.iter_mut().enumerate() {
+			*x = add(*x, round_constant(round, position));
+		}
+		let full = round < half || round >= half + PARTIAL_ROUNDS;
+		if full {
+			for x in state.iter_mut() {
+				*x = sbox(*x);
+			}
+		} else {
+			state[0] = sbox(state[0]);
+		}
+		mix(state);
+	}
+}
+
+/// Hash a sequence of field elements. This is the function a circuit would compute, since it
+/// never has to break its inputs into bits. Inputs must already be reduced modulo `P`.
+pub fn hash_elements(elements: &[u64]) -> Hash {
+	// The capacity starts at the input length, so inputs of different lengths never collide by
+	// padding.
+	let mut state = [0, 0, elements.len() as u64 % P];
+	for chunk in elements.chunks(RATE) {
+		for (x, element) in state.iter_mut().zip(chunk) {
+			*x = add(*x, *element);
+		}
+		permute(&mut state);
+	}
+	if elements.is_empty() {
+		permute(&mut state);
+	}
+	state[0]
+}
+
+/// A `core::hash::Hasher` backed by Poseidon, so any `Hash` type can be hashed with it just like
+/// with `crate::hash`.
+#[derive(Clone, Debug, Default)]
+pub struct PoseidonHasher {
+	bytes: Vec<u8>,
+}
+
+impl Hasher for PoseidonHasher {
+	fn write(&mut self, bytes: &[u8]) {
+		self.bytes.extend_from_slice(bytes);
+	}
+
+	fn finish(&self) -> u64 {
+		// Pack seven bytes into each element so that every element is below `P`.
+		let elements: Vec<u64> = self
+			.bytes
+			.chunks(7)
+			.map(|chunk| chunk.iter().fold(0, |acc, byte| (acc << 8) | *byte as u64))
+			.collect();
+		hash_elements(&[&[self.bytes.len() as u64], elements.as_slice()].concat())
+	}
+}
+
+/// Hash any hashable type with Poseidon. The drop-in counterpart of `crate::hash`.
+pub fn poseidon_hash<T: StdHash>(t: &T) -> Hash {
+	let mut hasher = PoseidonHasher::default();
+	t.hash(&mut hasher);
+	hasher.finish()
+}
+
+/// A Merkle root over field elements where every node is hashed with `hash_elements`. Odd nodes
+/// are promoted unchanged, as in `crypto::merkle`.
+pub fn merkle_root(leaves: &[u64]) -> Hash {
+	if leaves.is_empty() {
+		return hash_elements(&[]);
+	}
+	let mut layer = leaves.to_vec();
+	while layer.len() > 1 {
+		layer = layer
+			.chunks(2)
+			.map(|pair| match pair {
+				[left, right] => hash_elements(&[*left, *right]),
+				[single] => *single,
+				_ => unreachable!("chunks of two"),
+			})
+			.collect();
+	}
+	layer[0]
+}
+
+/// Exercise: a zk-friendly state commitment.
+///
+/// The state is a set of account balances. Each account becomes one leaf, the Poseidon hash of
+/// its id and balance, and the leaves are sorted by id so that the commitment does not depend on
+/// the order the accounts are given in. A circuit proving a balance transfer only needs field
+/// arithmetic to recompute this root, unlike a root built with `crate::hash`.
+pub fn state_commitment(balances: &[(u64, u64)]) -> Hash {
+	let mut balances = balances.to_vec();
+	balances.sort();
+	let leaves: Vec<u64> = balances
+		.iter()
+		.map(|(account, balance)| hash_elements(&[account % P, balance % P]))
+		.collect();
+	merkle_root(&leaves)
+}
+
+#[test]
+fn poseidon_sbox_is_a_permutation_exponent() {
+	// x^7 is only invertible when 7 does not divide P - 1.
+	assert_ne!((P - 1) % 7, 0);
+}
+
+#[test]
+fn poseidon_hash_elements_is_deterministic() {
+	assert_eq!(hash_elements(&[1, 2, 3]), hash_elements(&[1, 2, 3]));
+	assert_ne!(hash_elements(&[1, 2, 3]), hash_elements(&[1, 2, 4]));
+	assert_ne!(hash_elements(&[1, 2, 3]), hash_elements(&[3, 2, 1]));
+}
+
+#[test]
+fn poseidon_length_is_bound() {
+	assert_ne!(hash_elements(&[]), hash_elements(&[0]));
+	assert_ne!(hash_elements(&[0]), hash_elements(&[0, 0]));
+}
+
+#[test]
+fn poseidon_hasher_works_with_hash_types() {
+	assert_eq!(poseidon_hash(&"hello"), poseidon_hash(&"hello"));
+	assert_ne!(poseidon_hash(&"hello"), poseidon_hash(&"world"));
+	assert_ne!(poseidon_hash(&(1u64, 2u64)), poseidon_hash(&(2u64, 1u64)));
+}
+
+#[test]
+fn poseidon_state_commitment_ignores_order() {
+	assert_eq!(
+		state_commitment(&[(1, 100), (2, 50), (3, 0)]),
+		state_commitment(&[(3, 0), (1, 100), (2, 50)])
+	);
+}
+
+#[test]
+fn poseidon_state_commitment_changes_with_balance() {
+	assert_ne!(state_commitment(&[(1, 100), (2, 50)]), state_commitment(&[(1, 100), (2, 51)]));
+}
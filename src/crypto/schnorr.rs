@@ -0,0 +1,356 @@
+//! Schnorr signatures and a simplified MuSig aggregation scheme.
+//!
+//! When a committee of authorities seals a block together, the naive approach is to attach one
+//! signature per member. That works, but the seal grows with the committee and verifiers must check
+//! every signature. Schnorr signatures have a useful property: signatures and public keys can be
+//! added together. With a little care, n signers can jointly produce a _single_ ordinary looking
+//! signature that verifies against a single aggregate public key.
+//!
+//! The care is needed in two places:
+//! - Key aggregation. Simply adding public keys lets a malicious member choose their key as a
+//!   function of the others and cancel them out (a rogue key attack). MuSig multiplies each key by a
+//!   coefficient that depends on the whole set of keys, so nobody can pick their key that way.
+//! - Nonces. Each signer contributes a random nonce, and the nonces are combined. If signers saw
+//!   the other nonces before choosing their own they could steer the combined nonce, so everyone
+//!   first commits to their nonce by publishing its hash, and only then reveals it.
+//!
+//! A single signer can derive their nonce from their secret and the message, as `sign` does. A
+//! MuSig signer cannot. A dishonest member can start two sessions for the same message and pick a
+//! different nonce of their own each time. The combined nonce and so the challenge change, while
+//! an honest signer with a derived nonce would use the same one twice, and two responses with one
+//! nonce and different challenges give away the secret key. So every signer mixes fresh
+//! randomness into each session's nonce.
+//!
+//! That second point is the big trade-off compared to BLS signatures. BLS aggregation is
+//! non-interactive: each member signs alone and anyone can add the signatures up afterwards. MuSig
+//! needs several rounds of communication between the signers before the signature exists. Here
+//! those rounds are simulated in-process by `musig_sign`.
+//!
+//! The group is the subgroup of squares modulo a 63 bit safe prime. It is far too small to be
+//! secure, but it is large enough that the arithmetic behaves like the real thing.
+
+use super::{
+	secret::Secret,
+	sig::{Pair, Public, Signature},
+};
+use crate::hash;
+use alloc::vec::Vec;
+use core::{fmt, hash::Hash as StdHash};
+
+/// The modulus of the group. It is a safe prime, `P = 2Q + 1`.
+pub const P: u64 = 9_223_372_036_854_771_239;
+
+/// The order of the group, and therefore the modulus for secret keys, nonces, and challenges.
+pub const Q: u64 = 4_611_686_018_427_385_619;
+
+/// The generator of the group. Any square other than one generates the subgroup of order `Q`.
+pub const G: u64 = 4;
+
+/// Multiply modulo `m` without overflowing.
+fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+	((a as u128 * b as u128) % m as u128) as u64
+}
+
+/// Raise `base` to the power `exp` in the group.
+fn pow(mut base: u64, mut exp: u64) -> u64 {
+	let mut result = 1;
+	while exp > 0 {
+		if exp & 1 == 1 {
+			result = mul_mod(result, base, P);
+		}
+		base = mul_mod(base, base, P);
+		exp >>= 1;
+	}
+	result
+}
+
+/// Hash anything to a scalar, that is, a number modulo `Q`.
+fn scalar<T: StdHash>(input: &T) -> u64 {
+	hash(input) % Q
+}
+
+/// The Fiat-Shamir challenge binding a signature to its public key, nonce, and message.
+fn challenge(public: SchnorrPublic, nonce: u64, message: &[u8]) -> u64 {
+	scalar(&(b"CHALLENGE", public, nonce, message))
+}
+
+/// A Schnorr public key, `G^secret`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SchnorrPublic(pub u64);
+
+/// A Schnorr signature: the public nonce `r = G^k` and the response `s = k + c * secret`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SchnorrSignature {
+	pub r: u64,
+	pub s: u64,
+}
+
+/// A Schnorr key pair. The secret is kept as little endian bytes in a `Secret`, so it is zeroed
+/// when dropped and never printed.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SchnorrPair {
+	secret: Secret<8>,
+	public: SchnorrPublic,
+}
+
+impl SchnorrPair {
+	/// The secret key as a scalar.
+	fn secret(&self) -> u64 {
+		u64::from_le_bytes(*self.secret.expose())
+	}
+}
+
+impl fmt::Debug for SchnorrPair {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("SchnorrPair")
+			.field("public", &self.public)
+			.finish_non_exhaustive()
+	}
+}
+
+impl Public for SchnorrPublic {}
+impl Signature for SchnorrSignature {}
+
+impl Pair for SchnorrPair {
+	type Public = SchnorrPublic;
+	type Signature = SchnorrSignature;
+
+	fn from_seed(seed: &[u8; 32]) -> Self {
+		// A secret of zero would give the identity as the public key, so skip it.
+		let secret = scalar(&(b"SECRET", seed)).max(1);
+		SchnorrPair {
+			secret: Secret::new(secret.to_le_bytes()),
+			public: SchnorrPublic(pow(G, secret)),
+		}
+	}
+
+	fn public(&self) -> SchnorrPublic {
+		self.public
+	}
+
+	fn sign(&self, message: &[u8]) -> SchnorrSignature {
+		// Deriving the nonce from the secret and message makes signing deterministic. Reusing a
+		// nonce for two different messages would reveal the secret key.
+		let k = scalar(&(b"NONCE", self.secret(), message));
+		let r = pow(G, k);
+		let c = challenge(self.public, r, message);
+		SchnorrSignature { r, s: (k + mul_mod(c, self.secret(), Q)) % Q }
+	}
+
+	fn verify(signature: &SchnorrSignature, message: &[u8], public: &SchnorrPublic) -> bool {
+		let c = challenge(*public, signature.r, message);
+		pow(G, signature.s) == mul_mod(signature.r, pow(public.0, c), P)
+	}
+}
+
+/// The key aggregation coefficient of one member of a committee.
+fn coefficient(committee: &[SchnorrPublic], member: SchnorrPublic) -> u64 {
+	scalar(&(b"KEYAGG", committee, member))
+}
+
+/// Sort and deduplicate a committee so that its aggregate key does not depend on the order in which
+/// members are listed.
+fn canonical(committee: &[SchnorrPublic]) -> Vec<SchnorrPublic> {
+	let mut committee = committee.to_vec();
+	committee.sort();
+	committee.dedup();
+	committee
+}
+
+/// The aggregate public key of a committee. A MuSig signature by the whole committee is an
+/// ordinary Schnorr signature under this key.
+pub fn aggregate_public(committee: &[SchnorrPublic]) -> SchnorrPublic {
+	let committee = canonical(committee);
+	let aggregate = committee
+		.iter()
+		.fold(1, |acc, member| mul_mod(acc, pow(member.0, coefficient(&committee, *member)), P));
+	SchnorrPublic(aggregate)
+}
+
+/// The ways a MuSig session can fail.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MuSigError {
+	/// There was nobody to sign.
+	NoSigners,
+	/// The same signer was listed twice.
+	DuplicateSigner,
+	/// The signer at this index revealed a nonce that does not match their commitment.
+	NonceMismatch(usize),
+}
+
+/// One signer's view of a MuSig session. In a real deployment each signer runs on a different
+/// machine, and only the public values are sent over the network.
+struct Signer<'a> {
+	pair: &'a SchnorrPair,
+	/// The secret nonce. Never leaves the signer.
+	k: u64,
+	/// The public nonce `G^k`, revealed in round two.
+	r: u64,
+}
+
+/// Jointly sign a message with every pair in the committee, simulating the rounds of the MuSig
+/// protocol in-process.
+///
+/// Each signer calls `randomness` once for their nonce. It must return a fresh, unpredictable
+/// number on every call, for example one read from the operating system. Never pass a
+/// `DeterministicRng` outside of tests.
+pub fn musig_sign(
+	signers: &[SchnorrPair],
+	message: &[u8],
+	randomness: impl FnMut() -> u64,
+) -> Result<SchnorrSignature, MuSigError> {
+	musig_sign_with(signers, message, randomness, |_, r| r)
+}
+
+/// Like `musig_sign`, but lets a test tamper with the nonce each signer reveals in round two.
+fn musig_sign_with(
+	signers: &[SchnorrPair],
+	message: &[u8],
+	mut randomness: impl FnMut() -> u64,
+	reveal: impl Fn(usize, u64) -> u64,
+) -> Result<SchnorrSignature, MuSigError> {
+	if signers.is_empty() {
+		return Err(MuSigError::NoSigners);
+	}
+	let publics: Vec<SchnorrPublic> = signers.iter().map(|pair| pair.public).collect();
+	let committee = canonical(&publics);
+	if committee.len() != publics.len() {
+		return Err(MuSigError::DuplicateSigner);
+	}
+	let aggregate = aggregate_public(&committee);
+
+	// Round one: every signer picks a nonce and publishes a commitment to it. The secret and
+	// message are mixed in as well, so a weak source of randomness alone does not expose the key.
+	let session: Vec<Signer> = signers
+		.iter()
+		.map(|pair| {
+			let fresh = randomness();
+			let k = scalar(&(b"MUSIG_NONCE", pair.secret(), &committee, message, fresh));
+			Signer { pair, k, r: pow(G, k) }
+		})
+		.collect();
+	let commitments: Vec<u64> = session.iter().map(|signer| hash(&signer.r)).collect();
+
+	// Round two: every signer reveals their nonce, and everybody checks it against the commitment.
+	let mut r = 1;
+	for (index, signer) in session.iter().enumerate() {
+		let revealed = reveal(index, signer.r);
+		if hash(&revealed) != commitments[index] {
+			return Err(MuSigError::NonceMismatch(index));
+		}
+		r = mul_mod(r, revealed, P);
+	}
+
+	// Round three: every signer produces a partial signature, and the partials are added up.
+	let c = challenge(aggregate, r, message);
+	let s = session.iter().fold(0, |s, signer| {
+		let weight = mul_mod(c, coefficient(&committee, signer.pair.public), Q);
+		(s + signer.k + mul_mod(weight, signer.pair.secret(), Q)) % Q
+	});
+	Ok(SchnorrSignature { r, s })
+}
+
+#[cfg(test)]
+fn committee(size: u8) -> Vec<SchnorrPair> {
+	(0..size).map(|i| SchnorrPair::from_seed(&[i; 32])).collect()
+}
+
+/// Predictable randomness, which is only acceptable in tests.
+#[cfg(test)]
+fn test_randomness(seed: u64) -> impl FnMut() -> u64 {
+	let mut rng = crate::rng::DeterministicRng::new(seed);
+	move || rng.next_u64()
+}
+
+#[test]
+fn schnorr_sign_and_verify() {
+	let alice = SchnorrPair::from_seed(&[1; 32]);
+	let bob = SchnorrPair::from_seed(&[2; 32]);
+	let signature = alice.sign(b"hello");
+
+	assert!(SchnorrPair::verify(&signature, b"hello", &alice.public()));
+	assert!(!SchnorrPair::verify(&signature, b"goodbye", &alice.public()));
+	assert!(!SchnorrPair::verify(&signature, b"hello", &bob.public()));
+}
+
+#[test]
+fn schnorr_group_has_expected_order() {
+	assert_eq!(P, 2 * Q + 1);
+	assert_eq!(pow(G, Q), 1);
+}
+
+#[test]
+fn schnorr_musig_verifies_under_aggregate_key() {
+	let signers = committee(4);
+	let publics: Vec<_> = signers.iter().map(|pair| pair.public()).collect();
+	let signature = musig_sign(&signers, b"block 7", test_randomness(1)).unwrap();
+
+	assert!(SchnorrPair::verify(&signature, b"block 7", &aggregate_public(&publics)));
+	assert!(!SchnorrPair::verify(&signature, b"block 8", &aggregate_public(&publics)));
+}
+
+#[test]
+fn schnorr_musig_needs_every_signer() {
+	let signers = committee(4);
+	let publics: Vec<_> = signers.iter().map(|pair| pair.public()).collect();
+	let signature = musig_sign(&signers[..3], b"block 7", test_randomness(1)).unwrap();
+
+	assert!(!SchnorrPair::verify(&signature, b"block 7", &aggregate_public(&publics)));
+}
+
+#[test]
+fn schnorr_aggregate_key_ignores_order() {
+	let publics: Vec<_> = committee(3).iter().map(|pair| pair.public()).collect();
+	let reversed: Vec<_> = publics.iter().rev().copied().collect();
+
+	assert_eq!(aggregate_public(&publics), aggregate_public(&reversed));
+}
+
+#[test]
+fn schnorr_musig_rejects_changed_nonce() {
+	let signers = committee(3);
+
+	assert_eq!(
+		musig_sign_with(&signers, b"block 7", test_randomness(1), |index, r| {
+			if index == 1 {
+				r + 1
+			} else {
+				r
+			}
+		}),
+		Err(MuSigError::NonceMismatch(1))
+	);
+}
+
+#[test]
+fn schnorr_musig_rejects_bad_committees() {
+	let signers = committee(2);
+
+	assert_eq!(musig_sign(&[], b"block 7", test_randomness(1)), Err(MuSigError::NoSigners));
+	let repeated = [signers[0].clone(), signers[1].clone(), signers[0].clone()];
+	assert_eq!(
+		musig_sign(&repeated, b"block 7", test_randomness(1)),
+		Err(MuSigError::DuplicateSigner)
+	);
+}
+
+#[test]
+fn schnorr_musig_uses_a_fresh_nonce_every_session() {
+	let signers = committee(3);
+	let publics: Vec<_> = signers.iter().map(|pair| pair.public()).collect();
+	let first = musig_sign(&signers, b"block 7", test_randomness(1)).unwrap();
+	let second = musig_sign(&signers, b"block 7", test_randomness(2)).unwrap();
+
+	assert_ne!(first.r, second.r);
+	assert!(SchnorrPair::verify(&first, b"block 7", &aggregate_public(&publics)));
+	assert!(SchnorrPair::verify(&second, b"block 7", &aggregate_public(&publics)));
+}
+
+#[test]
+fn schnorr_pair_debug_hides_the_secret() {
+	let pair = SchnorrPair::from_seed(&[1; 32]);
+	let printed = alloc::format!("{pair:?}");
+
+	assert!(printed.contains(&alloc::format!("{}", pair.public().0)));
+	assert!(!printed.contains(&alloc::format!("{}", pair.secret())));
+}
@@ -0,0 +1,140 @@
+//! Basic hygiene for secret key material.
+//!
+//! Getting the cryptography right is only half the job. Secrets also leak through the way ordinary
+//! code handles them:
+//! - When a value is dropped its bytes stay in memory until something else overwrites them. A
+//!   crash dump, a swapped out page, or a later memory bug can then reveal the key. Secrets should
+//!   be overwritten with zeros as soon as they are no longer needed.
+//! - Comparing bytes with `==` stops at the first difference, so the time a comparison takes
+//!   tells an attacker how many leading bytes they guessed correctly. Secrets should be compared in
+//!   constant time, always looking at every byte.
+//! - Deriving `Debug` on a struct holding a key happily prints the key into logs.
+//!
+//! `Secret` wraps a fixed size byte array and takes care of all three. Getting at the bytes
+//! requires calling `expose`, which makes every use of the secret easy to find.
+
+use super::sig::Pair;
+use core::{
+	fmt,
+	hint::black_box,
+	sync::atomic::{compiler_fence, Ordering},
+};
+
+/// A fixed size secret that is zeroed when dropped and compared in constant time.
+pub struct Secret<const N: usize>([u8; N]);
+
+/// The 32 byte seed that key pairs are derived from.
+pub type SecretSeed = Secret<32>;
+
+impl<const N: usize> Secret<N> {
+	/// Take ownership of the secret bytes.
+	pub fn new(bytes: [u8; N]) -> Self {
+		Secret(bytes)
+	}
+
+	/// Borrow the secret bytes. Keep the borrow as short as possible, and avoid copying them.
+	pub fn expose(&self) -> &[u8; N] {
+		&self.0
+	}
+
+	/// Mutably borrow the secret bytes, for filling them in place.
+	pub fn expose_mut(&mut self) -> &mut [u8; N] {
+		&mut self.0
+	}
+}
+
+impl SecretSeed {
+	/// Derive a key pair from this seed without copying the seed out of its wrapper.
+	pub fn pair<P: Pair>(&self) -> P {
+		P::from_seed(&self.0)
+	}
+}
+
+impl<const N: usize> Clone for Secret<N> {
+	fn clone(&self) -> Self {
+		Secret(self.0)
+	}
+}
+
+/// Overwrite the bytes with zeros in a way the compiler will not optimize away.
+pub fn zeroize(bytes: &mut [u8]) {
+	for byte in bytes.iter_mut() {
+		// A plain assignment to memory that is about to be freed is a dead store, and the compiler
+		// is allowed to remove it. Volatile writes are never removed.
+		// SAFETY: `byte` is a valid, aligned, exclusive reference.
+		unsafe { core::ptr::write_volatile(byte, 0) };
+	}
+	// Stop the compiler from moving later memory accesses before the zeroing.
+	compiler_fence(Ordering::SeqCst);
+}
+
+impl<const N: usize> Drop for Secret<N> {
+	fn drop(&mut self) {
+		zeroize(&mut self.0);
+	}
+}
+
+/// Compare two byte slices in constant time. The running time depends only on the length.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+	if a.len() != b.len() {
+		return false;
+	}
+	// Accumulate every difference instead of returning early. `black_box` keeps the optimizer from
+	// turning this back into an early exit.
+	let difference = a.iter().zip(b).fold(0u8, |acc, (x, y)| black_box(acc | (x ^ y)));
+	difference == 0
+}
+
+impl<const N: usize> PartialEq for Secret<N> {
+	fn eq(&self, other: &Self) -> bool {
+		constant_time_eq(&self.0, &other.0)
+	}
+}
+
+impl<const N: usize> Eq for Secret<N> {}
+
+impl<const N: usize> fmt::Debug for Secret<N> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "Secret<{N}>(<redacted>)")
+	}
+}
+
+#[test]
+fn secret_constant_time_eq() {
+	assert!(constant_time_eq(b"same", b"same"));
+	assert!(!constant_time_eq(b"same", b"sane"));
+	assert!(!constant_time_eq(b"same", b"same!"));
+	assert!(constant_time_eq(b"", b""));
+}
+
+#[test]
+fn secret_equality() {
+	assert_eq!(Secret::new([1; 32]), Secret::new([1; 32]));
+	assert_ne!(Secret::new([1; 32]), Secret::new([2; 32]));
+}
+
+#[test]
+fn secret_debug_is_redacted() {
+	let secret = Secret::new([0xab; 4]);
+
+	assert_eq!(format!("{secret:?}"), "Secret<4>(<redacted>)");
+}
+
+#[test]
+fn secret_is_zeroed_on_drop() {
+	let mut secret = std::mem::ManuallyDrop::new(Secret::new([0xab; 32]));
+	// SAFETY: the secret is dropped exactly once and never used again afterwards, except to read
+	// its bytes, which are still owned by the `ManuallyDrop`.
+	unsafe { std::mem::ManuallyDrop::drop(&mut secret) };
+
+	assert_eq!(secret.0, [0; 32]);
+}
+
+#[test]
+fn secret_seed_derives_pairs() {
+	use super::sig::ToyPair;
+
+	let seed = SecretSeed::new([7; 32]);
+
+	assert_eq!(seed.pair::<ToyPair>().public(), ToyPair::from_seed(&[7; 32]).public());
+}
@@ -0,0 +1,111 @@
+//! SHA-256, the hash function of Bitcoin.
+//!
+//! The SipHash used throughout this tutorial produces 64 bits, so anyone can find two
+//! inputs with the same hash by trying a few billion of them. A commitment such as a state root
+//! only means something if nobody can find a second state with the same root, which takes a hash
+//! with a much larger output. SHA-256 produces 256 bits, and no collision has ever been found.
+//!
+//! This is a straightforward implementation of FIPS 180-4, written for reading rather than speed.
+//! The message is padded to a multiple of 64 bytes, and each 64 byte block is mixed into eight 32
+//! bit words of state over 64 rounds.
+
+use alloc::vec::Vec;
+
+/// The first 32 bits of the fractional parts of the cube roots of the first 64 primes.
+const K: [u32; 64] = [
+	0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+	0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+	0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+	0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+	0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+	0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+	0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+	0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// The first 32 bits of the fractional parts of the square roots of the first 8 primes.
+const INITIAL_STATE: [u32; 8] = [
+	0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// Mix one 64 byte block into the state.
+fn compress(state: &mut [u32; 8], block: &[u8]) {
+	let mut w = [0u32; 64];
+	for (i, word) in block.chunks_exact(4).enumerate() {
+		w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+	}
+	for i in 16..64 {
+		let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+		let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+		w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+	}
+
+	let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+	for i in 0..64 {
+		let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+		let choose = (e & f) ^ (!e & g);
+		let t1 = h.wrapping_add(s1).wrapping_add(choose).wrapping_add(K[i]).wrapping_add(w[i]);
+		let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+		let majority = (a & b) ^ (a & c) ^ (b & c);
+		let t2 = s0.wrapping_add(majority);
+		h = g;
+		g = f;
+		f = e;
+		e = d.wrapping_add(t1);
+		d = c;
+		c = b;
+		b = a;
+		a = t1.wrapping_add(t2);
+	}
+	for (word, new) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+		*word = word.wrapping_add(new);
+	}
+}
+
+/// The SHA-256 hash of the bytes.
+pub fn sha256(bytes: &[u8]) -> [u8; 32] {
+	// Append a single 1 bit, then zeros up to 8 bytes short of a whole block, then the message
+	// length in bits.
+	let mut message: Vec<u8> = bytes.to_vec();
+	message.push(0x80);
+	while message.len() % 64 != 56 {
+		message.push(0);
+	}
+	message.extend_from_slice(&((bytes.len() as u64) * 8).to_be_bytes());
+
+	let mut state = INITIAL_STATE;
+	for block in message.chunks_exact(64) {
+		compress(&mut state, block);
+	}
+	let mut digest = [0u8; 32];
+	for (chunk, word) in digest.chunks_exact_mut(4).zip(state) {
+		chunk.copy_from_slice(&word.to_be_bytes());
+	}
+	digest
+}
+
+#[cfg(test)]
+fn hex(digest: [u8; 32]) -> alloc::string::String {
+	crate::hex::encode(&digest)
+}
+
+#[test]
+fn sha256_standard_vectors() {
+	assert_eq!(
+		hex(sha256(b"")),
+		"0xe3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+	);
+	assert_eq!(
+		hex(sha256(b"abc")),
+		"0xba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+	);
+	// Long enough that the padding needs a second block.
+	assert_eq!(
+		hex(sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+		"0x248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+	);
+	assert_eq!(
+		hex(sha256(&[b'a'; 1_000])),
+		"0x41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3"
+	);
+}
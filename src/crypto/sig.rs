@@ -0,0 +1,284 @@
+//! A small signing interface shared by everything that needs to authorize data.
+//!
+//! Until now identities have been modeled by attaching a `ConsensusAuthority` or a `User` to the
+//! data directly. That is fine for learning the surrounding logic, but PoA seals, transactions, and
+//! finality votes all eventually need the same three operations: derive a key pair, sign a message,
+//! and verify a signature against a public key. These traits describe those operations once so the
+//! rest of the code does not care which scheme is in use.
+//!
+//! Two schemes are provided:
+//! - `ToyPair` is deterministic and extremely cheap, but anyone can forge its signatures. It exists
+//!   so that tests exercising the interface run instantly.
+//! - `Ed25519Pair` is a real signature scheme backed by `ed25519-dalek`. It is only compiled with
+//!   the `ed25519` feature so that the rest of the tutorial has no dependencies.
+
+use crate::hash;
+#[cfg(test)]
+use alloc::vec::Vec;
+use core::{fmt::Debug, hash::Hash};
+
+/// A public key that signatures can be checked against. Keys are ordered so that they can index a
+/// `BTreeMap`, such as the nonce of each account.
+pub trait Public: Clone + Debug + Ord + Hash {}
+
+/// A signature over some message.
+pub trait Signature: Clone + Debug + Eq + Hash {}
+
+/// A key pair able to produce signatures.
+pub trait Pair {
+	type Public: Public;
+	type Signature: Signature;
+
+	/// Deterministically derive a key pair from a 32 byte seed.
+	fn from_seed(seed: &[u8; 32]) -> Self;
+
+	/// The public half of this pair.
+	fn public(&self) -> Self::Public;
+
+	/// Sign the given message.
+	fn sign(&self, message: &[u8]) -> Self::Signature;
+
+	/// Check that the signature was produced over the message by the owner of the public key.
+	fn verify(signature: &Self::Signature, message: &[u8], public: &Self::Public) -> bool;
+
+	/// Check many signatures at once, such as every transaction in a block. Returns `true` only if
+	/// every signature is valid, but does not say which one failed, so fall back to `verify` to
+	/// find the culprit.
+	///
+	/// The provided implementation just checks them one by one. Schemes with a faster way to
+	/// check many signatures together should override it.
+	fn verify_batch(batch: &[(&Self::Signature, &[u8], &Self::Public)]) -> bool {
+		batch
+			.iter()
+			.all(|(signature, message, public)| Self::verify(signature, message, public))
+	}
+
+	/// Sign the SCALE encoding of a value rather than the bytes its `Hash` implementation writes.
+	/// SCALE is a documented format, so tools outside this crate can reproduce the signed bytes.
+	#[cfg(feature = "scale")]
+	fn sign_encoded<T: codec::Encode>(&self, value: &T) -> Self::Signature {
+		self.sign(&value.encode())
+	}
+
+	/// Check a signature produced by `sign_encoded`.
+	#[cfg(feature = "scale")]
+	fn verify_encoded<T: codec::Encode>(
+		signature: &Self::Signature,
+		value: &T,
+		public: &Self::Public,
+	) -> bool {
+		Self::verify(signature, &value.encode(), public)
+	}
+}
+
+/// The public key of the toy scheme. It is just a hash of the seed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ToyPublic(pub u64);
+
+/// A toy signature. It is a hash of the public key and the message, which means that anyone who
+/// knows the public key can produce it. Never use this outside of tests.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ToySignature(pub u64);
+
+/// A key pair in the toy scheme.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ToyPair {
+	public: ToyPublic,
+}
+
+impl Public for ToyPublic {}
+impl Signature for ToySignature {}
+
+impl Pair for ToyPair {
+	type Public = ToyPublic;
+	type Signature = ToySignature;
+
+	fn from_seed(seed: &[u8; 32]) -> Self {
+		ToyPair { public: ToyPublic(hash(seed)) }
+	}
+
+	fn public(&self) -> ToyPublic {
+		self.public
+	}
+
+	fn sign(&self, message: &[u8]) -> ToySignature {
+		ToySignature(hash(&(self.public, message)))
+	}
+
+	fn verify(signature: &ToySignature, message: &[u8], public: &ToyPublic) -> bool {
+		signature.0 == hash(&(public, message))
+	}
+}
+
+/// The real thing. Only available with the `ed25519` feature.
+#[cfg(feature = "ed25519")]
+pub mod ed25519 {
+	use super::{Pair, Public, Signature};
+	use alloc::vec::Vec;
+	use ed25519_dalek::{Signer, SigningKey, Verifier, VerifyingKey};
+
+	/// An ed25519 public key, stored as its 32 byte encoding.
+	#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+	pub struct Ed25519Public(pub [u8; 32]);
+
+	/// An ed25519 signature, stored as its 64 byte encoding.
+	#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+	pub struct Ed25519Signature(pub [u8; 64]);
+
+	/// An ed25519 key pair.
+	#[derive(Clone, Debug)]
+	pub struct Ed25519Pair(SigningKey);
+
+	impl Public for Ed25519Public {}
+	impl Signature for Ed25519Signature {}
+
+	impl Pair for Ed25519Pair {
+		type Public = Ed25519Public;
+		type Signature = Ed25519Signature;
+
+		fn from_seed(seed: &[u8; 32]) -> Self {
+			Ed25519Pair(SigningKey::from_bytes(seed))
+		}
+
+		fn public(&self) -> Ed25519Public {
+			Ed25519Public(self.0.verifying_key().to_bytes())
+		}
+
+		fn sign(&self, message: &[u8]) -> Ed25519Signature {
+			Ed25519Signature(self.0.sign(message).to_bytes())
+		}
+
+		fn verify(signature: &Ed25519Signature, message: &[u8], public: &Ed25519Public) -> bool {
+			let Ok(key) = VerifyingKey::from_bytes(&public.0) else {
+				return false;
+			};
+			let signature = ed25519_dalek::Signature::from_bytes(&signature.0);
+			key.verify(message, &signature).is_ok()
+		}
+
+		/// Batch verification checks a random linear combination of all the signature equations
+		/// with a single multiscalar multiplication, which is roughly twice as fast as checking
+		/// each signature alone.
+		fn verify_batch(batch: &[(&Ed25519Signature, &[u8], &Ed25519Public)]) -> bool {
+			let mut messages = Vec::with_capacity(batch.len());
+			let mut signatures = Vec::with_capacity(batch.len());
+			let mut keys = Vec::with_capacity(batch.len());
+			for (signature, message, public) in batch {
+				let Ok(key) = VerifyingKey::from_bytes(&public.0) else {
+					return false;
+				};
+				messages.push(*message);
+				signatures.push(ed25519_dalek::Signature::from_bytes(&signature.0));
+				keys.push(key);
+			}
+			ed25519_dalek::verify_batch(&messages, &signatures, &keys).is_ok()
+		}
+	}
+}
+
+/// Sign and verify a message with any scheme. Used by the tests below for both schemes.
+#[cfg(test)]
+fn sign_and_verify<P: Pair>() {
+	let alice = P::from_seed(&[1; 32]);
+	let bob = P::from_seed(&[2; 32]);
+	let signature = alice.sign(b"hello");
+
+	assert!(P::verify(&signature, b"hello", &alice.public()));
+	assert!(!P::verify(&signature, b"goodbye", &alice.public()));
+	assert!(!P::verify(&signature, b"hello", &bob.public()));
+}
+
+/// Batch verify with any scheme, including a batch with one bad signature in it.
+#[cfg(test)]
+fn batch_verify<P: Pair>() {
+	let pairs: Vec<P> = (0..8).map(|i| P::from_seed(&[i; 32])).collect();
+	let messages: Vec<[u8; 1]> = (0..8).map(|i| [i]).collect();
+	let signatures: Vec<P::Signature> =
+		pairs.iter().zip(&messages).map(|(pair, message)| pair.sign(message)).collect();
+	let publics: Vec<P::Public> = pairs.iter().map(P::public).collect();
+	let mut batch: Vec<(&P::Signature, &[u8], &P::Public)> = signatures
+		.iter()
+		.zip(&messages)
+		.zip(&publics)
+		.map(|((signature, message), public)| (signature, &message[..], public))
+		.collect();
+
+	assert!(P::verify_batch(&[]));
+	assert!(P::verify_batch(&batch));
+	batch[3].1 = b"forged";
+	assert!(!P::verify_batch(&batch));
+}
+
+#[test]
+fn sig_toy_sign_and_verify() {
+	sign_and_verify::<ToyPair>();
+}
+
+#[test]
+fn sig_toy_verify_batch() {
+	batch_verify::<ToyPair>();
+}
+
+#[test]
+fn sig_toy_is_deterministic() {
+	let first = ToyPair::from_seed(&[7; 32]);
+	let second = ToyPair::from_seed(&[7; 32]);
+
+	assert_eq!(first.public(), second.public());
+	assert_eq!(first.sign(b"block"), second.sign(b"block"));
+}
+
+#[cfg(feature = "ed25519")]
+#[test]
+fn sig_ed25519_sign_and_verify() {
+	sign_and_verify::<ed25519::Ed25519Pair>();
+}
+
+#[cfg(feature = "ed25519")]
+#[test]
+fn sig_ed25519_verify_batch() {
+	batch_verify::<ed25519::Ed25519Pair>();
+}
+
+/// Compare checking a block's worth of signatures one at a time and as a batch. Run it with
+/// `cargo test --release --features ed25519 sig_ed25519_batch_speedup -- --ignored --nocapture`.
+#[cfg(all(feature = "ed25519", feature = "std"))]
+#[test]
+#[ignore]
+fn sig_ed25519_batch_speedup() {
+	use ed25519::Ed25519Pair;
+	use std::time::Instant;
+
+	let signed: Vec<_> = (0..1000u32)
+		.map(|i| {
+			let mut seed = [0; 32];
+			seed[..4].copy_from_slice(&i.to_le_bytes());
+			let pair = Ed25519Pair::from_seed(&seed);
+			let message = i.to_le_bytes();
+			(pair.sign(&message), message, pair.public())
+		})
+		.collect();
+	let batch: Vec<_> = signed
+		.iter()
+		.map(|(signature, message, public)| (signature, &message[..], public))
+		.collect();
+
+	let started = Instant::now();
+	assert!(batch.iter().all(|(s, m, p)| Ed25519Pair::verify(s, m, p)));
+	println!("one by one: {:?}", started.elapsed());
+
+	let started = Instant::now();
+	assert!(Ed25519Pair::verify_batch(&batch));
+	println!("batched: {:?}", started.elapsed());
+}
+
+#[cfg(feature = "scale")]
+#[test]
+fn sig_toy_sign_encoded() {
+	let alice = ToyPair::from_seed(&[1; 32]);
+	let signature = alice.sign_encoded(&(7u64, 8u32));
+
+	assert_eq!(signature, alice.sign(&[7, 0, 0, 0, 0, 0, 0, 0, 8, 0, 0, 0]));
+	assert!(ToyPair::verify_encoded(&signature, &(7u64, 8u32), &alice.public()));
+	assert!(!ToyPair::verify_encoded(&signature, &(7u64, 9u32), &alice.public()));
+}
@@ -0,0 +1,255 @@
+//! A sparse Merkle tree for key-value state such as account balances.
+//!
+//! An ordinary Merkle tree commits to a list, so the position of each item depends on everything
+//! that came before it. For state we want something different: each key should always live in the
+//! same place no matter what else is stored. A sparse Merkle tree achieves this by hashing the key
+//! to pick a path through a tree with one leaf for every possible path. With our 64 bit hashes that
+//! is 2^64 leaves, almost all of them empty.
+//!
+//! Empty subtrees all hash to the same well known values, so they never need to be stored. Only the
+//! occupied leaves are kept, and the hashes of the empty subtrees are precomputed once per level.
+//!
+//! Because every key has a fixed place, we can prove that a key is _absent_ just as easily as we
+//! can prove that it is present: the proof simply shows an empty leaf at the key's path.
+
+use crate::hash;
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::hash::Hash as StdHash;
+
+type Hash = u64;
+
+/// An occupied leaf as the pair of its path and its leaf hash.
+type Leaf = (u64, Hash);
+
+/// How many levels the tree has. One per bit of the hashed key.
+pub const DEPTH: usize = 64;
+
+/// The hash of a leaf with nothing in it.
+pub const EMPTY_LEAF: Hash = 0;
+
+/// Combine two child hashes into their parent hash.
+fn hash_pair(left: Hash, right: Hash) -> Hash {
+	hash(&(left, right))
+}
+
+/// The hashes of completely empty subtrees, indexed by their height above the leaves.
+fn empty_subtree_hashes() -> [Hash; DEPTH + 1] {
+	let mut hashes = [EMPTY_LEAF; DEPTH + 1];
+	for height in 1..=DEPTH {
+		hashes[height] = hash_pair(hashes[height - 1], hashes[height - 1]);
+	}
+	hashes
+}
+
+/// The path through the tree at which a key is stored.
+fn path_of<K: StdHash>(key: &K) -> u64 {
+	hash(key)
+}
+
+/// The hash stored at an occupied leaf.
+fn leaf_hash<K: StdHash, V: StdHash>(key: &K, value: &V) -> Hash {
+	hash(&(key, value))
+}
+
+/// A sparse Merkle tree mapping keys to values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SparseMerkleTree<K, V> {
+	/// Occupied leaves keyed by their path. The key is kept alongside the value so the leaf hash
+	/// can be recomputed and so the tree can be iterated.
+	leaves: BTreeMap<u64, (K, V)>,
+	empty: [Hash; DEPTH + 1],
+}
+
+/// A proof that a key holds a particular value, or holds nothing at all.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SparseMerkleProof {
+	/// The sibling hashes from the leaf up to just below the root. There are always `DEPTH` of
+	/// them.
+	pub siblings: Vec<Hash>,
+}
+
+impl<K: StdHash, V: StdHash> Default for SparseMerkleTree<K, V> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<K: StdHash, V: StdHash> SparseMerkleTree<K, V> {
+	/// Create an empty tree.
+	pub fn new() -> Self {
+		SparseMerkleTree { leaves: BTreeMap::new(), empty: empty_subtree_hashes() }
+	}
+
+	/// Store a value under the given key, returning the previous value if there was one.
+	pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+		self.leaves.insert(path_of(&key), (key, value)).map(|(_, old)| old)
+	}
+
+	/// Remove the value stored under the given key, returning it if there was one.
+	pub fn remove(&mut self, key: &K) -> Option<V> {
+		self.leaves.remove(&path_of(key)).map(|(_, old)| old)
+	}
+
+	/// Look up the value stored under the given key.
+	pub fn get(&self, key: &K) -> Option<&V> {
+		self.leaves.get(&path_of(key)).map(|(_, value)| value)
+	}
+
+	/// The number of occupied leaves.
+	pub fn len(&self) -> usize {
+		self.leaves.len()
+	}
+
+	/// Whether every leaf is empty.
+	pub fn is_empty(&self) -> bool {
+		self.leaves.is_empty()
+	}
+
+	/// The root hash committing to the entire key-value mapping.
+	pub fn root(&self) -> Hash {
+		let entries: Vec<Leaf> = self.leaf_hashes();
+		self.subtree_root(&entries, DEPTH)
+	}
+
+	/// Produce a proof for the given key. It proves membership if the key is present and
+	/// non-membership otherwise.
+	pub fn proof(&self, key: &K) -> SparseMerkleProof {
+		let path = path_of(key);
+		let mut entries: &[Leaf] = &self.leaf_hashes();
+		let mut siblings = Vec::with_capacity(DEPTH);
+		for height in (1..=DEPTH).rev() {
+			let (left, right) = split(entries, height);
+			if bit(path, height - 1) {
+				siblings.push(self.subtree_root(left, height - 1));
+				entries = right;
+			} else {
+				siblings.push(self.subtree_root(right, height - 1));
+				entries = left;
+			}
+		}
+		// We collected the siblings from the top down, but verification walks from the leaf up.
+		siblings.reverse();
+		SparseMerkleProof { siblings }
+	}
+
+	/// The leaf hash of every occupied leaf, ordered by path.
+	fn leaf_hashes(&self) -> Vec<Leaf> {
+		self.leaves
+			.iter()
+			.map(|(path, (key, value))| (*path, leaf_hash(key, value)))
+			.collect()
+	}
+
+	/// The root of the subtree at the given height containing exactly the given (sorted) leaves.
+	fn subtree_root(&self, entries: &[Leaf], height: usize) -> Hash {
+		match entries {
+			[] => self.empty[height],
+			[(_, leaf)] if height == 0 => *leaf,
+			_ => {
+				let (left, right) = split(entries, height);
+				hash_pair(self.subtree_root(left, height - 1), self.subtree_root(right, height - 1))
+			},
+		}
+	}
+}
+
+/// Whether the given bit of a path is set. Set bits go right.
+fn bit(path: u64, index: usize) -> bool {
+	(path >> index) & 1 == 1
+}
+
+/// Split sorted leaves of a subtree at the given height into its left and right children.
+fn split(entries: &[Leaf], height: usize) -> (&[Leaf], &[Leaf]) {
+	let middle = entries.partition_point(|(path, _)| !bit(*path, height - 1));
+	entries.split_at(middle)
+}
+
+/// Check a proof that `key` holds `value` (or holds nothing, if `value` is `None`) under `root`.
+pub fn verify_proof<K: StdHash, V: StdHash>(
+	root: Hash,
+	key: &K,
+	value: Option<&V>,
+	proof: &SparseMerkleProof,
+) -> bool {
+	if proof.siblings.len() != DEPTH {
+		return false;
+	}
+	let path = path_of(key);
+	let mut current = match value {
+		Some(value) => leaf_hash(key, value),
+		None => EMPTY_LEAF,
+	};
+	for (index, sibling) in proof.siblings.iter().enumerate() {
+		current = if bit(path, index) {
+			hash_pair(*sibling, current)
+		} else {
+			hash_pair(current, *sibling)
+		};
+	}
+	current == root
+}
+
+#[cfg(test)]
+use crate::c1_state_machine::User;
+
+#[test]
+fn smt_empty_root_is_default() {
+	let tree = SparseMerkleTree::<User, u64>::new();
+	assert_eq!(tree.root(), empty_subtree_hashes()[DEPTH]);
+}
+
+#[test]
+fn smt_root_is_independent_of_insertion_order() {
+	let mut first = SparseMerkleTree::new();
+	first.insert(User::Alice, 100u64);
+	first.insert(User::Bob, 50);
+
+	let mut second = SparseMerkleTree::new();
+	second.insert(User::Bob, 50u64);
+	second.insert(User::Alice, 100);
+
+	assert_eq!(first.root(), second.root());
+}
+
+#[test]
+fn smt_root_changes_with_value() {
+	let mut tree = SparseMerkleTree::new();
+	tree.insert(User::Alice, 100u64);
+	let before = tree.root();
+	tree.insert(User::Alice, 101);
+
+	assert_ne!(before, tree.root());
+}
+
+#[test]
+fn smt_removing_everything_restores_empty_root() {
+	let mut tree = SparseMerkleTree::new();
+	let empty_root = tree.root();
+	tree.insert(User::Alice, 100u64);
+	tree.remove(&User::Alice);
+
+	assert_eq!(tree.root(), empty_root);
+}
+
+#[test]
+fn smt_membership_proof() {
+	let mut tree = SparseMerkleTree::new();
+	tree.insert(User::Alice, 100u64);
+	tree.insert(User::Bob, 50);
+	let proof = tree.proof(&User::Bob);
+
+	assert!(verify_proof(tree.root(), &User::Bob, Some(&50u64), &proof));
+	assert!(!verify_proof(tree.root(), &User::Bob, Some(&51u64), &proof));
+	assert!(!verify_proof(tree.root(), &User::Bob, None::<&u64>, &proof));
+}
+
+#[test]
+fn smt_non_membership_proof() {
+	let mut tree = SparseMerkleTree::new();
+	tree.insert(User::Alice, 100u64);
+	tree.insert(User::Bob, 50);
+	let proof = tree.proof(&User::Charlie);
+
+	assert!(verify_proof(tree.root(), &User::Charlie, None::<&u64>, &proof));
+	assert!(!verify_proof(tree.root(), &User::Charlie, Some(&0u64), &proof));
+}
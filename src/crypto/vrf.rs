@@ -0,0 +1,103 @@
+//! A verifiable random function (VRF).
+//!
+//! A VRF lets the holder of a secret key produce a pseudo-random output for any input, along with a
+//! proof that anyone holding the public key can check. The output is unpredictable to everyone else
+//! before it is revealed, but the key holder cannot choose it either, because there is only one
+//! valid output per input. This is exactly what a slot lottery needs: each authority privately
+//! learns whether it won a slot, and can later convince everyone else that it really did.
+//!
+//! Real chains use dedicated constructions such as schnorrkel's VRF. Here we build a stand-in on
+//! top of the signature schemes from `crypto::sig`: the proof is a signature over the input, and
+//! the output is the hash of that signature. That is only a VRF if each key has exactly one valid
+//! signature per message, and only the key holder can compute it. Neither scheme in this crate
+//! has both properties:
+//! - A `ToyPair` signature is a hash of the public key and the message, so anyone can compute the
+//!   output of any key for any input. Nothing about it is unpredictable.
+//! - `sign` makes Ed25519 signatures deterministically, but the signer may pick any nonce and still
+//!   get a valid signature. Each nonce gives a different output, so the key holder can try nonces
+//!   until it gets an output it likes, such as one that wins the slot.
+//!
+//! So with these schemes this module is _not_ a VRF. It shows the shape of the interface a slot
+//! lottery needs, and must not be used where the outputs have to be unpredictable or unbiased.
+
+use super::sig::Pair;
+use crate::hash;
+
+/// The random output of the VRF.
+pub type VrfOutput = u64;
+
+/// Evaluate the VRF on the given input, returning the output and a proof of its correctness. See
+/// the module documentation for why this is not a real VRF with the schemes of this crate.
+pub fn prove<P: Pair>(pair: &P, input: &[u8]) -> (VrfOutput, P::Signature) {
+	let proof = pair.sign(input);
+	(hash(&proof), proof)
+}
+
+/// Check that `output` is the correct VRF output for `input` under the given public key.
+pub fn verify<P: Pair>(
+	public: &P::Public,
+	input: &[u8],
+	output: VrfOutput,
+	proof: &P::Signature,
+) -> bool {
+	P::verify(proof, input, public) && hash(proof) == output
+}
+
+#[cfg(test)]
+use super::sig::ToyPair;
+
+#[test]
+fn vrf_output_verifies() {
+	let pair = ToyPair::from_seed(&[1; 32]);
+	let (output, proof) = prove(&pair, b"slot 7");
+
+	assert!(verify::<ToyPair>(&pair.public(), b"slot 7", output, &proof));
+}
+
+#[test]
+fn vrf_output_is_deterministic() {
+	let pair = ToyPair::from_seed(&[1; 32]);
+
+	assert_eq!(prove(&pair, b"slot 7"), prove(&pair, b"slot 7"));
+	assert_ne!(prove(&pair, b"slot 7").0, prove(&pair, b"slot 8").0);
+}
+
+#[test]
+fn vrf_wrong_output_is_rejected() {
+	let pair = ToyPair::from_seed(&[1; 32]);
+	let (output, proof) = prove(&pair, b"slot 7");
+
+	assert!(!verify::<ToyPair>(&pair.public(), b"slot 7", output + 1, &proof));
+}
+
+#[test]
+fn vrf_wrong_key_is_rejected() {
+	let alice = ToyPair::from_seed(&[1; 32]);
+	let bob = ToyPair::from_seed(&[2; 32]);
+	let (output, proof) = prove(&alice, b"slot 7");
+
+	assert!(!verify::<ToyPair>(&bob.public(), b"slot 7", output, &proof));
+}
+
+#[test]
+fn vrf_toy_output_is_public() {
+	// Anyone who knows the public key computes the same output, without the key pair.
+	let pair = ToyPair::from_seed(&[1; 32]);
+	let (output, _) = prove(&pair, b"slot 7");
+	let forged = super::sig::ToySignature(hash(&(pair.public(), &b"slot 7"[..])));
+
+	assert_eq!(hash(&forged), output);
+	assert!(verify::<ToyPair>(&pair.public(), b"slot 7", output, &forged));
+}
+
+#[cfg(feature = "ed25519")]
+#[test]
+fn vrf_ed25519_output_verifies() {
+	use super::sig::ed25519::Ed25519Pair;
+
+	let pair = Ed25519Pair::from_seed(&[1; 32]);
+	let (output, proof) = prove(&pair, b"slot 7");
+
+	assert!(verify::<Ed25519Pair>(&pair.public(), b"slot 7", output, &proof));
+	assert!(!verify::<Ed25519Pair>(&pair.public(), b"slot 8", output, &proof));
+}
@@ -0,0 +1,130 @@
+//! Hex encoding for hashes and encoded data.
+//!
+//! Printing a hash with `{:?}` gives a decimal `u64`, which is hard to compare with the `0x…` hex
+//! strings that every block explorer and RPC uses. These helpers give one canonical text form for
+//! raw bytes and hashes so that every tool prints the same thing.
+
+use alloc::{string::String, vec::Vec};
+use core::{fmt, str::FromStr};
+
+/// The ways a hex string can fail to parse.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HexError {
+	/// The string does not start with `0x`.
+	MissingPrefix,
+	/// The string has an odd number of digits, so it does not describe whole bytes.
+	OddLength,
+	/// The string contains a character that is not a hex digit.
+	InvalidCharacter(char),
+	/// The string has the wrong number of bytes for the type being parsed.
+	WrongLength,
+	/// The bytes are valid hex but could not be decoded as the requested type.
+	#[cfg(feature = "scale")]
+	Decode,
+}
+
+/// Encode bytes as a `0x`-prefixed lowercase hex string.
+pub fn encode(bytes: &[u8]) -> String {
+	let mut out = String::with_capacity(2 + 2 * bytes.len());
+	out.push_str("0x");
+	for byte in bytes {
+		out.push_str(&format!("{byte:02x}"));
+	}
+	out
+}
+
+/// Decode a `0x`-prefixed hex string into bytes. Both upper and lower case digits are accepted.
+pub fn decode(s: &str) -> Result<Vec<u8>, HexError> {
+	let digits = s.strip_prefix("0x").ok_or(HexError::MissingPrefix)?;
+	if !digits.len().is_multiple_of(2) {
+		return Err(HexError::OddLength);
+	}
+	let nibble = |c: char| c.to_digit(16).ok_or(HexError::InvalidCharacter(c));
+	let chars: Vec<char> = digits.chars().collect();
+	chars
+		.chunks(2)
+		.map(|pair| Ok((nibble(pair[0])? << 4 | nibble(pair[1])?) as u8))
+		.collect()
+}
+
+/// A hash that displays as hex. The chapters use plain `u64` hashes, so wrap one in this type
+/// whenever it is shown to a person.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct HexHash(pub u64);
+
+impl From<u64> for HexHash {
+	fn from(hash: u64) -> Self {
+		HexHash(hash)
+	}
+}
+
+impl fmt::LowerHex for HexHash {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		fmt::LowerHex::fmt(&self.0, f)
+	}
+}
+
+impl fmt::Display for HexHash {
+	/// Always the full sixteen digits, so hashes line up when printed in columns.
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{:#018x}", self.0)
+	}
+}
+
+impl FromStr for HexHash {
+	type Err = HexError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let bytes: [u8; 8] = decode(s)?.try_into().map_err(|_| HexError::WrongLength)?;
+		Ok(HexHash(u64::from_be_bytes(bytes)))
+	}
+}
+
+/// Hex encode the SCALE encoding of a value, such as a block or an extrinsic.
+#[cfg(feature = "scale")]
+pub fn encode_scale<T: codec::Encode>(value: &T) -> String {
+	encode(&value.encode())
+}
+
+/// Decode a value from the hex of its SCALE encoding.
+#[cfg(feature = "scale")]
+pub fn decode_scale<T: codec::Decode>(s: &str) -> Result<T, HexError> {
+	let bytes = decode(s)?;
+	T::decode(&mut &bytes[..]).map_err(|_| HexError::Decode)
+}
+
+#[test]
+fn hex_round_trip() {
+	for bytes in [vec![], vec![0], vec![0xde, 0xad, 0xbe, 0xef], vec![255; 20]] {
+		assert_eq!(decode(&encode(&bytes)), Ok(bytes));
+	}
+	assert_eq!(encode(&[0xde, 0xad]), "0xdead");
+}
+
+#[test]
+fn hex_decode_errors() {
+	assert_eq!(decode("dead"), Err(HexError::MissingPrefix));
+	assert_eq!(decode("0xdea"), Err(HexError::OddLength));
+	assert_eq!(decode("0xdeag"), Err(HexError::InvalidCharacter('g')));
+	assert_eq!(decode("0xDEAD"), Ok(vec![0xde, 0xad]));
+}
+
+#[test]
+fn hex_hash_display_and_parse() {
+	let hash = HexHash(0xabc);
+
+	assert_eq!(hash.to_string(), "0x0000000000000abc");
+	assert_eq!(format!("{hash:x}"), "abc");
+	assert_eq!(hash.to_string().parse(), Ok(hash));
+	assert_eq!("0xabc0".parse::<HexHash>(), Err(HexError::WrongLength));
+}
+
+#[cfg(feature = "scale")]
+#[test]
+fn hex_scale_round_trip() {
+	let extrinsics: Vec<u64> = vec![1, 2, 3];
+	let encoded = encode_scale(&extrinsics);
+
+	assert!(encoded.starts_with("0x0c"));
+	assert_eq!(decode_scale::<Vec<u64>>(&encoded), Ok(extrinsics));
+}
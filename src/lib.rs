@@ -1,18 +1,157 @@
 //! Learn the fundamentals of blockchain by building it from scratch.
+//!
+//! The core data structures only need `alloc`, so the crate builds under `no_std` when the default
+//! `std` feature is disabled. That lets the same headers, blocks, and state machines be reused
+//! inside a WASM runtime. Pieces that genuinely need an operating system, such as the keystore or
+//! the `HashMap` based state machines, are only compiled with `std`.
 
-use std::{
-	collections::hash_map::DefaultHasher,
-	hash::{Hash, Hasher},
-};
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[macro_use]
+extern crate alloc;
+
+use core::hash::{Hash, Hasher};
+
+/// Narrate what the code is doing, for demos. Takes a topic, a `Verbosity` variant, and a format
+/// string with its arguments. Without the `teaching_log` feature it expands to nothing.
+///
+/// ```ignore
+/// teaching_log!("pow", Detail, "rejected block {} because its hash is above the threshold", h);
+/// ```
+macro_rules! teaching_log {
+	($topic:literal, $level:ident, $($arg:tt)+) => {
+		#[cfg(feature = "teaching_log")]
+		{
+			if $crate::teaching_log::enabled($topic, $crate::teaching_log::Verbosity::$level) {
+				$crate::teaching_log::emit($topic, format_args!($($arg)+));
+			}
+		}
+	};
+}
 
 mod c1_state_machine;
 mod c2_blockchain;
 mod c3_consensus;
 mod c4_framework;
+mod crypto;
+mod hex;
+mod mining;
+#[cfg(feature = "rlp")]
+mod rlp;
+mod rng;
+#[cfg(feature = "teaching_log")]
+mod teaching_log;
+pub mod vectors;
+
+// The chapters stay private, but the `bfs-repl` binary needs a way in to chapter 2, and the
+// `bfs-vectors` binary to the test vectors.
+pub use c2_blockchain::repl;
 
 // Simple helper to do some hashing.
 fn hash<T: Hash>(t: &T) -> u64 {
-	let mut s = DefaultHasher::new();
+	let mut s = SipHasher13::new();
 	t.hash(&mut s);
 	s.finish()
 }
+
+/// SipHash-1-3 with both keys zero, the algorithm behind the standard library's `DefaultHasher`
+/// today. The standard library is free to change that algorithm, and it is missing without `std`,
+/// so we write it ourselves. Then every build, including `no_std` and WASM ones, computes exactly
+/// the same header hashes, state roots, and proof of work seals.
+#[derive(Clone, Debug)]
+struct SipHasher13 {
+	v: [u64; 4],
+	/// Bytes written since the last full eight byte word, in the low bytes.
+	tail: u64,
+	tail_len: usize,
+	length: usize,
+}
+
+impl SipHasher13 {
+	fn new() -> Self {
+		SipHasher13 {
+			v: [0x736f6d6570736575, 0x646f72616e646f6d, 0x6c7967656e657261, 0x7465646279746573],
+			tail: 0,
+			tail_len: 0,
+			length: 0,
+		}
+	}
+
+	fn round(&mut self) {
+		let [v0, v1, v2, v3] = &mut self.v;
+		*v0 = v0.wrapping_add(*v1);
+		*v1 = v1.rotate_left(13) ^ *v0;
+		*v0 = v0.rotate_left(32);
+		*v2 = v2.wrapping_add(*v3);
+		*v3 = v3.rotate_left(16) ^ *v2;
+		*v0 = v0.wrapping_add(*v3);
+		*v3 = v3.rotate_left(21) ^ *v0;
+		*v2 = v2.wrapping_add(*v1);
+		*v1 = v1.rotate_left(17) ^ *v2;
+		*v2 = v2.rotate_left(32);
+	}
+
+	/// Mix one eight byte word into the state, with a single compression round.
+	fn compress(&mut self, word: u64) {
+		self.v[3] ^= word;
+		self.round();
+		self.v[0] ^= word;
+	}
+}
+
+impl Hasher for SipHasher13 {
+	fn write(&mut self, bytes: &[u8]) {
+		self.length += bytes.len();
+		for byte in bytes {
+			self.tail |= (*byte as u64) << (8 * self.tail_len);
+			self.tail_len += 1;
+			if self.tail_len == 8 {
+				self.compress(self.tail);
+				self.tail = 0;
+				self.tail_len = 0;
+			}
+		}
+	}
+
+	// Integers are written little-endian and `usize` as eight bytes, which is what a 64 bit
+	// little-endian machine writes anyway. Pinning it means a 32 bit WASM runtime, where `usize` is
+	// four bytes, hashes a `Vec`'s length prefix the same way as the native node.
+	fn write_u16(&mut self, i: u16) {
+		self.write(&i.to_le_bytes());
+	}
+
+	fn write_u32(&mut self, i: u32) {
+		self.write(&i.to_le_bytes());
+	}
+
+	fn write_u64(&mut self, i: u64) {
+		self.write(&i.to_le_bytes());
+	}
+
+	fn write_u128(&mut self, i: u128) {
+		self.write(&i.to_le_bytes());
+	}
+
+	fn write_usize(&mut self, i: usize) {
+		self.write_u64(i as u64);
+	}
+
+	fn finish(&self) -> u64 {
+		let mut state = self.clone();
+		state.compress(((self.length as u64 & 0xff) << 56) | self.tail);
+		state.v[2] ^= 0xff;
+		for _ in 0..3 {
+			state.round();
+		}
+		state.v.iter().fold(0, |acc, v| acc ^ v)
+	}
+}
+
+#[test]
+fn hash_is_pinned_siphash_1_3() {
+	// These are the values the standard library's `DefaultHasher` produces, so chains built before
+	// the hasher moved into the crate still verify.
+	assert_eq!(hash(&0u64), 0xbd60acb658c79e45);
+	assert_eq!(hash(&"hello world, this is longer"), 0x5a95166dd617e51c);
+	assert_eq!(hash(&(1u64, vec![2u8, 3, 4])), 0xdcce5760658995ba);
+}
@@ -0,0 +1,116 @@
+//! The search for a proof of work seal.
+//!
+//! Every miner in this tutorial does the same thing: starting from some digest, try the digests in
+//! order until the header's hash is low enough. `first_valid_digest` is that search. Headers are
+//! cheap to hash but a hard threshold takes many tries, so with `std` the search is spread over
+//! every available core.
+//!
+//! Splitting the search must not change its answer. Otherwise two nodes mining the same header
+//! would produce different blocks, and a test that mines a block would see a different hash on
+//! every run. So the parallel search finds the same digest the serial one would, the lowest valid
+//! digest counting up from the start.
+
+/// Digests tried on the calling thread before any workers are started. At the chapters' usual
+/// threshold a search almost always ends within a few hundred tries, where starting threads would
+/// cost more than it saves.
+#[cfg(feature = "std")]
+const SERIAL_TRIES: u64 = 1_000;
+
+/// The first digest, counting up from `start`, that `is_valid` accepts.
+pub(crate) fn first_valid_digest(start: u64, is_valid: impl Fn(u64) -> bool + Sync) -> u64 {
+	#[cfg(feature = "std")]
+	{
+		if let Some(offset) = (0..SERIAL_TRIES).find(|offset| is_valid(start.wrapping_add(*offset)))
+		{
+			return start.wrapping_add(offset);
+		}
+		let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+		search_parallel(start.wrapping_add(SERIAL_TRIES), workers, &is_valid)
+	}
+	#[cfg(not(feature = "std"))]
+	{
+		let mut digest = start;
+		while !is_valid(digest) {
+			digest = digest.wrapping_add(1);
+		}
+		digest
+	}
+}
+
+/// The first digest, counting up from `start`, that `is_valid` accepts, searched by several
+/// threads.
+///
+/// Worker `w` of `n` tries the digests `start + w`, `start + w + n`, `start + w + 2n`, ... so no
+/// two workers ever hash the same candidate. When a worker finds a valid digest it lowers a shared
+/// bound to it. Every worker stops once its next candidate is past the bound, but not before, so
+/// all the digests below the bound are tried and the result is the lowest valid one, however the
+/// threads were scheduled.
+#[cfg(feature = "std")]
+pub(crate) fn search_parallel(
+	start: u64,
+	workers: usize,
+	is_valid: &(impl Fn(u64) -> bool + Sync),
+) -> u64 {
+	use std::sync::atomic::{AtomicU64, Ordering};
+
+	let workers = workers.max(1) as u64;
+	// The offset from `start` of the lowest valid digest found so far.
+	let bound = AtomicU64::new(u64::MAX);
+
+	std::thread::scope(|scope| {
+		for worker in 0..workers {
+			let bound = &bound;
+			scope.spawn(move || {
+				let mut offset = worker;
+				while offset < bound.load(Ordering::Relaxed) {
+					if is_valid(start.wrapping_add(offset)) {
+						bound.fetch_min(offset, Ordering::Relaxed);
+						return;
+					}
+					match offset.checked_add(workers) {
+						Some(next) => offset = next,
+						None => return,
+					}
+				}
+			});
+		}
+	});
+
+	start.wrapping_add(bound.into_inner())
+}
+
+#[cfg(all(test, feature = "std"))]
+use crate::hash;
+
+/// Whether the pair's hash is below the threshold, as a stand-in for a header.
+#[cfg(all(test, feature = "std"))]
+fn valid_pair(payload: u64, threshold: u64) -> impl Fn(u64) -> bool + Sync {
+	move |digest| hash(&(payload, digest)) <= threshold
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn mining_parallel_search_matches_serial() {
+	let threshold = u64::MAX / 1000;
+	for payload in 0..5 {
+		let is_valid = valid_pair(payload, threshold);
+		let serial = (0..).find(|digest| is_valid(*digest)).unwrap();
+
+		for workers in [1, 2, 4, 7] {
+			assert_eq!(search_parallel(0, workers, &is_valid), serial);
+		}
+		assert_eq!(first_valid_digest(0, &is_valid), serial);
+	}
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn mining_search_counts_up_from_the_start() {
+	let is_valid = valid_pair(1, u64::MAX / 100);
+	let first = first_valid_digest(0, &is_valid);
+	let second = first_valid_digest(first + 1, &is_valid);
+
+	assert!(second > first);
+	assert!((first + 1..second).all(|digest| !is_valid(digest)));
+	assert_eq!(search_parallel(first + 1, 3, &is_valid), second);
+}
@@ -0,0 +1,309 @@
+//! Recursive Length Prefix (RLP) encoding, as used by Ethereum.
+//!
+//! SCALE and RLP solve the same problem, turning structured data into bytes to hash, sign, and send
+//! around, but make different trade-offs:
+//! - SCALE is not self-describing. You must know the type to decode the bytes, and in return
+//!   integers are stored at a fixed width with no framing at all.
+//! - RLP only knows about two things: byte strings and lists. Every item carries its own length, so
+//!   any RLP blob can be split into its parts without knowing what it means. Integers are big-endian
+//!   byte strings with the leading zeros removed.
+//!
+//! This module is only compiled with the `rlp` feature.
+
+use crate::{
+	c2_blockchain::p4_batched_extrinsics::{Block, Header},
+	hash,
+};
+use alloc::vec::Vec;
+
+/// The two kinds of RLP item.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Item {
+	Bytes(Vec<u8>),
+	List(Vec<Item>),
+}
+
+/// The ways RLP bytes can fail to decode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RlpError {
+	/// The input ended before the item did.
+	UnexpectedEnd,
+	/// There are bytes left over after the item.
+	TrailingBytes,
+	/// The item is not in its shortest possible form. RLP requires exactly one encoding per value
+	/// so that hashes of the encoding are unique.
+	NonCanonical,
+	/// The item has the wrong shape for the type being decoded.
+	UnexpectedShape,
+}
+
+/// Write the prefix for a byte string (`offset` 0x80) or a list (`offset` 0xc0) of this length.
+fn encode_length(length: usize, offset: u8, out: &mut Vec<u8>) {
+	if length < 56 {
+		out.push(offset + length as u8);
+	} else {
+		let bytes = trimmed_be_bytes(length as u64);
+		out.push(offset + 55 + bytes.len() as u8);
+		out.extend_from_slice(&bytes);
+	}
+}
+
+/// The big-endian bytes of a number without leading zeros. Zero is the empty string.
+fn trimmed_be_bytes(value: u64) -> Vec<u8> {
+	let bytes = value.to_be_bytes();
+	let zeros = bytes.iter().take_while(|byte| **byte == 0).count();
+	bytes[zeros..].to_vec()
+}
+
+/// Encode an item.
+pub fn encode(item: &Item) -> Vec<u8> {
+	let mut out = Vec::new();
+	encode_into(item, &mut out);
+	out
+}
+
+fn encode_into(item: &Item, out: &mut Vec<u8>) {
+	match item {
+		// A single byte below 0x80 is its own encoding.
+		Item::Bytes(bytes) if bytes.len() == 1 && bytes[0] < 0x80 => out.push(bytes[0]),
+		Item::Bytes(bytes) => {
+			encode_length(bytes.len(), 0x80, out);
+			out.extend_from_slice(bytes);
+		},
+		Item::List(items) => {
+			let mut payload = Vec::new();
+			for item in items {
+				encode_into(item, &mut payload);
+			}
+			encode_length(payload.len(), 0xc0, out);
+			out.extend_from_slice(&payload);
+		},
+	}
+}
+
+/// Decode exactly one item, with nothing left over.
+pub fn decode(bytes: &[u8]) -> Result<Item, RlpError> {
+	let (item, rest) = decode_prefix(bytes)?;
+	if !rest.is_empty() {
+		return Err(RlpError::TrailingBytes);
+	}
+	Ok(item)
+}
+
+/// Split `count` bytes off the front of the input.
+fn take(bytes: &[u8], count: usize) -> Result<(&[u8], &[u8]), RlpError> {
+	if bytes.len() < count {
+		return Err(RlpError::UnexpectedEnd);
+	}
+	Ok(bytes.split_at(count))
+}
+
+/// Read a long-form length made of `count` big-endian bytes.
+fn decode_length(bytes: &[u8], count: usize) -> Result<(usize, &[u8]), RlpError> {
+	let (length, rest) = take(bytes, count)?;
+	if count > 8 || length.first() == Some(&0) {
+		return Err(RlpError::NonCanonical);
+	}
+	let length = length.iter().fold(0u64, |acc, byte| (acc << 8) | *byte as u64) as usize;
+	if length < 56 {
+		return Err(RlpError::NonCanonical);
+	}
+	Ok((length, rest))
+}
+
+/// Decode one item from the front of the input, returning it and the remaining bytes.
+fn decode_prefix(bytes: &[u8]) -> Result<(Item, &[u8]), RlpError> {
+	let (&first, rest) = bytes.split_first().ok_or(RlpError::UnexpectedEnd)?;
+	match first {
+		0x00..=0x7f => Ok((Item::Bytes(vec![first]), rest)),
+		0x80..=0xb7 => {
+			let (payload, rest) = take(rest, (first - 0x80) as usize)?;
+			if payload.len() == 1 && payload[0] < 0x80 {
+				return Err(RlpError::NonCanonical);
+			}
+			Ok((Item::Bytes(payload.to_vec()), rest))
+		},
+		0xb8..=0xbf => {
+			let (length, rest) = decode_length(rest, (first - 0xb7) as usize)?;
+			let (payload, rest) = take(rest, length)?;
+			Ok((Item::Bytes(payload.to_vec()), rest))
+		},
+		0xc0..=0xf7 => {
+			let (payload, rest) = take(rest, (first - 0xc0) as usize)?;
+			Ok((Item::List(decode_list(payload)?), rest))
+		},
+		0xf8..=0xff => {
+			let (length, rest) = decode_length(rest, (first - 0xf7) as usize)?;
+			let (payload, rest) = take(rest, length)?;
+			Ok((Item::List(decode_list(payload)?), rest))
+		},
+	}
+}
+
+fn decode_list(mut payload: &[u8]) -> Result<Vec<Item>, RlpError> {
+	let mut items = Vec::new();
+	while !payload.is_empty() {
+		let (item, rest) = decode_prefix(payload)?;
+		items.push(item);
+		payload = rest;
+	}
+	Ok(items)
+}
+
+/// A type with an RLP representation.
+pub trait Rlp: Sized {
+	/// Describe this value as an RLP item.
+	fn to_item(&self) -> Item;
+
+	/// Rebuild a value from its RLP item.
+	fn from_item(item: &Item) -> Result<Self, RlpError>;
+
+	/// The RLP encoding of this value.
+	fn rlp_encode(&self) -> Vec<u8> {
+		encode(&self.to_item())
+	}
+
+	/// Decode a value from its RLP encoding.
+	fn rlp_decode(bytes: &[u8]) -> Result<Self, RlpError> {
+		Self::from_item(&decode(bytes)?)
+	}
+}
+
+/// The hash of a value's RLP encoding. Ethereum identifies blocks and transactions the same way,
+/// with Keccak-256 in place of our toy hash.
+pub fn rlp_hash<T: Rlp>(value: &T) -> u64 {
+	hash(&value.rlp_encode())
+}
+
+impl Rlp for u64 {
+	fn to_item(&self) -> Item {
+		Item::Bytes(trimmed_be_bytes(*self))
+	}
+
+	fn from_item(item: &Item) -> Result<Self, RlpError> {
+		let Item::Bytes(bytes) = item else {
+			return Err(RlpError::UnexpectedShape);
+		};
+		if bytes.len() > 8 {
+			return Err(RlpError::UnexpectedShape);
+		}
+		if bytes.first() == Some(&0) {
+			return Err(RlpError::NonCanonical);
+		}
+		Ok(bytes.iter().fold(0, |acc, byte| (acc << 8) | *byte as u64))
+	}
+}
+
+impl<T: Rlp> Rlp for Vec<T> {
+	fn to_item(&self) -> Item {
+		Item::List(self.iter().map(Rlp::to_item).collect())
+	}
+
+	fn from_item(item: &Item) -> Result<Self, RlpError> {
+		let Item::List(items) = item else {
+			return Err(RlpError::UnexpectedShape);
+		};
+		items.iter().map(T::from_item).collect()
+	}
+}
+
+impl Rlp for Header {
+	fn to_item(&self) -> Item {
+		vec![self.parent, self.height, self.extrinsics_root, self.state, self.consensus_digest]
+			.to_item()
+	}
+
+	fn from_item(item: &Item) -> Result<Self, RlpError> {
+		let fields = Vec::<u64>::from_item(item)?;
+		let [parent, height, extrinsics_root, state, consensus_digest] = fields[..] else {
+			return Err(RlpError::UnexpectedShape);
+		};
+		Ok(Header { parent, height, extrinsics_root, state, consensus_digest })
+	}
+}
+
+impl Rlp for Block {
+	fn to_item(&self) -> Item {
+		Item::List(vec![self.header.to_item(), self.body.to_item()])
+	}
+
+	fn from_item(item: &Item) -> Result<Self, RlpError> {
+		let Item::List(items) = item else {
+			return Err(RlpError::UnexpectedShape);
+		};
+		let [header, body] = &items[..] else {
+			return Err(RlpError::UnexpectedShape);
+		};
+		Ok(Block { header: Header::from_item(header)?, body: Vec::from_item(body)? })
+	}
+}
+
+#[cfg(test)]
+fn bytes(s: &str) -> Item {
+	Item::Bytes(s.as_bytes().to_vec())
+}
+
+#[test]
+fn rlp_known_vectors() {
+	// Examples from the Ethereum wiki's RLP page.
+	assert_eq!(encode(&bytes("dog")), [0x83, b'd', b'o', b'g']);
+	assert_eq!(
+		encode(&Item::List(vec![bytes("cat"), bytes("dog")])),
+		[0xc8, 0x83, b'c', b'a', b't', 0x83, b'd', b'o', b'g']
+	);
+	assert_eq!(encode(&bytes("")), [0x80]);
+	assert_eq!(encode(&Item::List(vec![])), [0xc0]);
+	assert_eq!(0u64.rlp_encode(), [0x80]);
+	assert_eq!(15u64.rlp_encode(), [0x0f]);
+	assert_eq!(1024u64.rlp_encode(), [0x82, 0x04, 0x00]);
+}
+
+#[test]
+fn rlp_long_string() {
+	let long = Item::Bytes(vec![b'a'; 60]);
+	let encoded = encode(&long);
+
+	assert_eq!(&encoded[..2], &[0xb8, 60]);
+	assert_eq!(decode(&encoded), Ok(long));
+}
+
+#[test]
+fn rlp_nested_round_trip() {
+	let item = Item::List(vec![
+		Item::List(vec![]),
+		Item::List(vec![Item::List(vec![])]),
+		Item::Bytes(vec![0x7f]),
+		Item::Bytes(vec![0x80]),
+		Item::List(vec![Item::Bytes(vec![1; 100])]),
+	]);
+
+	assert_eq!(decode(&encode(&item)), Ok(item));
+}
+
+#[test]
+fn rlp_rejects_bad_input() {
+	assert_eq!(decode(&[0x83, b'd', b'o']), Err(RlpError::UnexpectedEnd));
+	assert_eq!(decode(&[0x0f, 0x0f]), Err(RlpError::TrailingBytes));
+	// A single small byte must be encoded as itself, not with a length prefix.
+	assert_eq!(decode(&[0x81, 0x0f]), Err(RlpError::NonCanonical));
+	assert_eq!(u64::rlp_decode(&[0x82, 0x00, 0x01]), Err(RlpError::NonCanonical));
+}
+
+#[test]
+fn rlp_block_round_trip() {
+	let block = Block::genesis().child(vec![1, 2, 300]);
+
+	assert_eq!(Block::rlp_decode(&block.rlp_encode()), Ok(block.clone()));
+	assert_eq!(Header::rlp_decode(&block.header.rlp_encode()), Ok(block.header.clone()));
+	assert_ne!(rlp_hash(&block.header), rlp_hash(&Block::genesis().header));
+}
+
+#[cfg(feature = "scale")]
+#[test]
+fn rlp_compared_with_scale() {
+	use codec::Encode;
+
+	// Small numbers are a single byte in RLP, but SCALE always spends eight bytes on a `u64`.
+	let header = Block::genesis().header;
+	assert!(header.rlp_encode().len() < header.encode().len());
+}
@@ -0,0 +1,128 @@
+//! A small seedable random number generator, so that randomized tools are reproducible.
+//!
+//! Anything in this crate that makes random choices, such as which block an exercise generator
+//! corrupts, takes a seed and draws from a `DeterministicRng`. Running again with the same seed
+//! gives exactly the same choices, on every machine, which makes a surprising result possible to
+//! investigate.
+//!
+//! When several components share one seed, each should `fork` its own generator with a label
+//! rather than share a single stream. Otherwise adding one extra draw in one component would
+//! silently change every choice made after it by all the others.
+//!
+//! The generator is SplitMix64. It is fast and statistically good enough for tests and teaching,
+//! but it is completely predictable, so never use it for keys or anything else secret.
+
+#[cfg(test)]
+use alloc::vec::Vec;
+
+/// A seedable SplitMix64 generator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeterministicRng {
+	state: u64,
+}
+
+impl DeterministicRng {
+	/// A generator whose whole stream is determined by the seed.
+	pub fn new(seed: u64) -> Self {
+		DeterministicRng { state: seed }
+	}
+
+	/// An independent generator for the component named `label`. It depends only on this
+	/// generator's current state and the label, and does not advance this generator.
+	pub fn fork(&self, label: &str) -> Self {
+		// FNV-1a of the label, so the result does not depend on the standard library's hasher.
+		let label = label.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+			(hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+		});
+		DeterministicRng::new(mix(self.state ^ mix(label)))
+	}
+
+	/// The next number in the stream.
+	pub fn next_u64(&mut self) -> u64 {
+		self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+		mix(self.state)
+	}
+
+	/// A number in `0..bound`. Panics if `bound` is zero.
+	///
+	/// Multiplying by the bound and keeping the high half avoids the bias of `next_u64() % bound`
+	/// towards small numbers. What bias remains is far too small to matter for bounds this crate
+	/// uses.
+	pub fn below(&mut self, bound: u64) -> u64 {
+		assert!(bound > 0, "cannot choose a number below zero");
+		((self.next_u64() as u128 * bound as u128) >> 64) as u64
+	}
+
+	/// `true` with the given probability.
+	pub fn chance(&mut self, probability: f64) -> bool {
+		// The top 53 bits fill an `f64` mantissa exactly, giving a uniform number in `0..1`.
+		((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < probability
+	}
+
+	/// Put the items in a random order.
+	pub fn shuffle<T>(&mut self, items: &mut [T]) {
+		for i in (1..items.len()).rev() {
+			let j = self.below(i as u64 + 1) as usize;
+			items.swap(i, j);
+		}
+	}
+}
+
+/// The SplitMix64 output function.
+fn mix(mut z: u64) -> u64 {
+	z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+	z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+	z ^ (z >> 31)
+}
+
+#[test]
+fn rng_known_stream() {
+	// The first outputs of SplitMix64 seeded with zero, as given by the reference implementation.
+	let mut rng = DeterministicRng::new(0);
+	assert_eq!(rng.next_u64(), 0xe220_a839_7b1d_cdaf);
+	assert_eq!(rng.next_u64(), 0x6e78_9e6a_a1b9_65f4);
+	assert_eq!(rng.next_u64(), 0x06c4_5d18_8009_454f);
+}
+
+#[test]
+fn rng_same_seed_same_stream() {
+	let mut a = DeterministicRng::new(42);
+	let mut b = DeterministicRng::new(42);
+	let mut c = DeterministicRng::new(43);
+	let a: Vec<u64> = (0..8).map(|_| a.next_u64()).collect();
+
+	assert_eq!(a, (0..8).map(|_| b.next_u64()).collect::<Vec<_>>());
+	assert_ne!(a, (0..8).map(|_| c.next_u64()).collect::<Vec<_>>());
+}
+
+#[test]
+fn rng_forks_are_independent() {
+	let root = DeterministicRng::new(7);
+	let mut mining = root.fork("mining");
+	let mut network = root.fork("network");
+
+	assert_eq!(root, DeterministicRng::new(7));
+	assert_eq!(mining, root.fork("mining"));
+	assert_ne!(mining.next_u64(), network.next_u64());
+}
+
+#[test]
+fn rng_below_and_shuffle() {
+	let mut rng = DeterministicRng::new(1);
+	assert!((0..1_000).all(|_| rng.below(6) < 6));
+
+	let mut items: Vec<u32> = (0..20).collect();
+	rng.shuffle(&mut items);
+	assert_ne!(items, (0..20).collect::<Vec<_>>());
+	items.sort();
+	assert_eq!(items, (0..20).collect::<Vec<_>>());
+}
+
+#[test]
+fn rng_chance() {
+	let mut rng = DeterministicRng::new(2);
+	assert!((0..100).all(|_| !rng.chance(0.0) && rng.chance(1.0)));
+
+	let hits = (0..10_000).filter(|_| rng.chance(0.3)).count();
+	assert!((2_800..3_200).contains(&hits));
+}
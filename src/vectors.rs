@@ -0,0 +1,270 @@
+//! Test vectors for implementing these exercises in other languages.
+//!
+//! Someone following this tutorial in Go or Python needs canonical answers to check their code
+//! against. This module writes them as one JSON document: a chain of blocks from chapter 2, headers
+//! sealed by each working engine from chapter 3, and Merkle proofs. The `bfs-vectors` binary prints
+//! it.
+//!
+//! Every vector is hashed with SHA-256, through `crypto::hasher::Sha256`, so another
+//! implementation can use any standard SHA-256 library. The blocks are part 6 blocks built with
+//! that hasher, so their parent hashes, state roots, and extrinsics roots are SHA-256 too, and each
+//! block's proof of work compares the first eight bytes of its hash, read big-endian, against the
+//! threshold. The chapter 3 engines still hash with the toy hash, so a sealed `pow` header meets
+//! its threshold under `crate::hash`, not SHA-256, and its other fields are plain numbers.
+//!
+//! Every hash in this crate is Rust's `Hash` trait feeding a `Hasher`, so the "encoding" of a value
+//! is whatever bytes its `Hash` implementation writes. Each vector records those bytes in hex as
+//! `encoded`, so another implementation only has to reproduce them and run SHA-256 over them. The
+//! rules are short:
+//! - A `u64` is eight little-endian bytes and a `u8` is one byte. Struct fields and tuple elements
+//!   are written in order.
+//! - A `Vec` writes its length as a `u64` first, then each element. A 32 byte hash is an array,
+//!   which is written the same way: the length 32, then the bytes.
+//! - A fieldless enum such as `ConsensusAuthority` writes its variant index as a `u64`.
+//! - `()` writes nothing.
+//! - A Merkle leaf hashes the tag `0` followed by the leaf, and a node hashes the tag `1`
+//!   followed by its children's hashes. Proof siblings are listed from the leaves up, as in
+//!   `crypto::merkle`.
+//!
+//! Rust itself writes integers in the machine's byte order, and lengths as a `usize`. These rules
+//! are what a 64 bit little-endian machine writes, and the crate's hashers pin them, so the vectors
+//! are the same whichever platform generates them.
+//!
+//! Hashes are written as hex strings, because many JSON parsers read numbers as doubles and would
+//! round a 64 bit number. Other integers are small enough to stay numbers.
+
+use crate::{
+	c1_state_machine::StateMachine,
+	c2_blockchain::{
+		p4_batched_extrinsics::{Block, THRESHOLD},
+		p6_rich_state::{GenericBlock, State, SumAndProduct},
+	},
+	c3_consensus::{
+		p1_pow::PoW, p3_poa::SimplePoa, Consensus, ConsensusAuthority, Header as ConsensusHeader,
+	},
+	crypto::{
+		hasher::{encoded, Hasher, Sha256},
+		merkle::{GenericMerkleTree, LEAF_TAG},
+	},
+	hash,
+	hex::{self, HexHash},
+};
+use alloc::{string::String, vec::Vec};
+use core::hash::Hash;
+
+/// A part 6 block hashed with SHA-256.
+type Sha256Block = GenericBlock<Sha256, SumAndProduct>;
+
+/// A consensus digest written as JSON.
+trait JsonDigest {
+	fn json(&self) -> String;
+}
+
+impl JsonDigest for u64 {
+	fn json(&self) -> String {
+		format!("{self}")
+	}
+}
+
+impl JsonDigest for ConsensusAuthority {
+	fn json(&self) -> String {
+		format!("\"{self:?}\"")
+	}
+}
+
+impl JsonDigest for () {
+	fn json(&self) -> String {
+		String::from("null")
+	}
+}
+
+/// The `encoded` and `hash` fields every vector ends with.
+fn encoding_fields<T: Hash>(t: &T) -> String {
+	format!(
+		"\"encoded\":\"{}\",\"hash\":\"{}\"",
+		hex::encode(&encoded(t)),
+		hex::encode(&Sha256::digest(t))
+	)
+}
+
+/// A short chain of part 6 blocks, with the state after each. The extrinsics root is the hash of
+/// the body, and the state root the hash of the state.
+fn block_vectors() -> Vec<String> {
+	let mut state = State { sum: 0, product: 1 };
+	let mut blocks = vec![(Sha256Block::genesis(&state), state.clone())];
+	for body in [vec![1, 2, 3], vec![]] {
+		let child = blocks[blocks.len() - 1].0.child(&state, body);
+		state = child.body.iter().fold(state, |s, e| SumAndProduct::next_state(&s, e));
+		blocks.push((child, state.clone()));
+	}
+	blocks
+		.iter()
+		.map(|(block, state)| {
+			let header = &block.header;
+			format!(
+				"{{\"height\":{},\"parent\":\"{}\",\"extrinsics_root\":\"{}\",\
+				 \"state_root\":\"{}\",\"consensus_digest\":{},\"body\":{:?},\
+				 \"body_encoded\":\"{}\",\"state\":{{\"sum\":{},\"product\":{}}},\
+				 \"state_encoded\":\"{}\",{}}}",
+				header.height,
+				hex::encode(&header.parent),
+				hex::encode(&header.extrinsics_root),
+				hex::encode(&header.state_root),
+				header.consensus_digest,
+				block.body,
+				hex::encode(&encoded(&block.body)),
+				state.sum,
+				state.product,
+				hex::encode(&encoded(state)),
+				encoding_fields(header)
+			)
+		})
+		.collect()
+}
+
+/// The same partial header sealed by an engine. `parameters` is a JSON object describing how the
+/// engine was configured.
+fn sealed_vector<C>(name: &str, parameters: &str, engine: &C, parent_digest: &C::Digest) -> String
+where
+	C: Consensus,
+	C::Digest: JsonDigest,
+{
+	let partial = ConsensusHeader {
+		parent: hash(&Block::genesis().header),
+		height: 1,
+		state_root: hash(&6u64),
+		extrinsics_root: hash(&vec![1u64, 2, 3]),
+		consensus_digest: (),
+	};
+	let header = engine.seal(parent_digest, partial).expect("every engine here can seal");
+	format!(
+		"{{\"engine\":\"{name}\",\"parameters\":{parameters},\"parent_digest\":{},\"parent\":\"{}\",\
+		 \"height\":{},\"state_root\":\"{}\",\"extrinsics_root\":\"{}\",\"consensus_digest\":{},{}}}",
+		parent_digest.json(),
+		HexHash(header.parent),
+		header.height,
+		HexHash(header.state_root),
+		HexHash(header.extrinsics_root),
+		header.consensus_digest.json(),
+		encoding_fields(&header)
+	)
+}
+
+/// Proofs for every leaf of an unbalanced tree, so the promotion of odd nodes is covered too.
+fn merkle_vectors() -> Vec<String> {
+	let leaves: Vec<u64> = (1..=5).collect();
+	let tree = GenericMerkleTree::<Sha256>::new(&leaves);
+	leaves
+		.iter()
+		.enumerate()
+		.map(|(index, leaf)| {
+			let proof = tree.proof(index).expect("every leaf has a proof");
+			let siblings: Vec<String> = proof
+				.siblings
+				.iter()
+				.map(|sibling| format!("\"{}\"", hex::encode(sibling)))
+				.collect();
+			format!(
+				"{{\"leaves\":{leaves:?},\"leaf_index\":{index},\"leaf_encoded\":\"{}\",\
+				 \"root\":\"{}\",\"siblings\":[{}]}}",
+				hex::encode(&encoded(&(LEAF_TAG, leaf))),
+				hex::encode(&tree.root()),
+				siblings.join(",")
+			)
+		})
+		.collect()
+}
+
+/// Every test vector, as a JSON object with one array per kind of vector and one vector per line.
+pub fn generate() -> String {
+	let hash_function = "sha256";
+	let pow = PoW { threshold: THRESHOLD };
+	let poa = SimplePoa { authorities: vec![ConsensusAuthority::Alice] };
+	let sealed = vec![
+		sealed_vector("none", "{}", &(), &()),
+		sealed_vector(
+			"pow",
+			&format!("{{\"threshold\":\"{}\"}}", HexHash(pow.threshold)),
+			&pow,
+			&0,
+		),
+		sealed_vector(
+			"simple_poa",
+			"{\"authorities\":[\"Alice\"]}",
+			&poa,
+			&ConsensusAuthority::Alice,
+		),
+	];
+
+	let section =
+		|name: &str, vectors: Vec<String>| format!("\"{name}\":[\n{}\n]", vectors.join(",\n"));
+	format!(
+		"{{\"hash_function\":\"{hash_function}\",\n{},\n{},\n{}\n}}\n",
+		section("blocks", block_vectors()),
+		section("sealed_headers", sealed),
+		section("merkle_proofs", merkle_vectors())
+	)
+}
+
+#[test]
+fn vectors_encoding_reproduces_the_hash() {
+	let block = Block::genesis().child(vec![4, 5]);
+	let authority_header = ConsensusHeader {
+		parent: 1,
+		height: 2,
+		state_root: 3,
+		extrinsics_root: 4,
+		consensus_digest: ConsensusAuthority::Charlie,
+	};
+	let rehash = |bytes: Vec<u8>| {
+		use core::hash::Hasher;
+		let mut hasher = crate::SipHasher13::new();
+		hasher.write(&bytes);
+		hasher.finish()
+	};
+
+	assert_eq!(rehash(encoded(&block.header)), hash(&block.header));
+	assert_eq!(rehash(encoded(&block.body)), hash(&block.body));
+	assert_eq!(rehash(encoded(&authority_header)), hash(&authority_header));
+	assert_eq!(rehash(encoded(&(7u64, 8u64))), hash(&(7u64, 8u64)));
+}
+
+#[test]
+fn vectors_encoding_follows_the_documented_rules() {
+	assert_eq!(encoded(&0x0102u64), [2, 1, 0, 0, 0, 0, 0, 0]);
+	assert_eq!(encoded(&vec![9u64]), [1, 0, 0, 0, 0, 0, 0, 0, 9, 0, 0, 0, 0, 0, 0, 0]);
+	assert_eq!(encoded(&ConsensusAuthority::Bob), [1, 0, 0, 0, 0, 0, 0, 0]);
+	assert!(encoded(&()).is_empty());
+	assert_eq!(encoded(&Block::genesis().header), [0; 40]);
+	assert_eq!(encoded(&[7u8; 32])[..8], 32u64.to_le_bytes());
+	assert_eq!(encoded(&[7u8; 32])[8..], [7; 32]);
+}
+
+#[test]
+fn vectors_merkle_tree_follows_the_documented_rules() {
+	use crate::crypto::sha256::sha256;
+
+	let leaf = |n: u64| sha256(&[&[0][..], &n.to_le_bytes()].concat());
+	let node = |left: [u8; 32], right: [u8; 32]| {
+		let length = 32u64.to_le_bytes();
+		sha256(&[&[1][..], &length, &left, &length, &right].concat())
+	};
+	let tree = GenericMerkleTree::<Sha256>::new(&[1u64, 2, 3]);
+
+	assert_eq!(tree.root(), node(node(leaf(1), leaf(2)), leaf(3)));
+}
+
+#[test]
+fn vectors_are_valid_and_reproducible() {
+	let json = generate();
+	let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+	assert_eq!(json, generate());
+	assert_eq!(parsed["blocks"].as_array().unwrap().len(), 3);
+	assert_eq!(parsed["sealed_headers"].as_array().unwrap().len(), 3);
+	assert_eq!(parsed["merkle_proofs"].as_array().unwrap().len(), 5);
+	assert_eq!(parsed["blocks"][1]["body"], serde_json::json!([1, 2, 3]));
+	assert_eq!(parsed["blocks"][1]["parent"], parsed["blocks"][0]["hash"]);
+	assert_eq!(parsed["blocks"][2]["state"], serde_json::json!({"sum": 6, "product": 6}));
+	assert_eq!(parsed["sealed_headers"][2]["consensus_digest"], "Alice");
+}
@@ -0,0 +1,256 @@
+//! Maintenance tasks for this repository, run with `cargo xtask <task>`.
+//!
+//! `new-exercise` adds the next part to a chapter, so new lessons start with the same structure as
+//! the existing ones:
+//! - a module file with a prompt for the lesson text, a `todo!()` stub, and a test named with the
+//!   chapter's prefix and the part number, such as `bc_7_`,
+//! - the `mod` declaration in the chapter's `mod.rs`,
+//! - an exercise group in the grader, so the new tests are graded.
+//!
+//! Usage: `cargo xtask new-exercise CHAPTER NAME [TITLE]`
+//!
+//! `CHAPTER` is the chapter number, `NAME` the module name without the part prefix, such as
+//! `fee_market`, and `TITLE` the name the grader shows. It defaults to `NAME` in title case.
+
+use std::{env, fs, path::Path, process::ExitCode};
+
+/// The chapters that have exercises, with the module directory and test prefix of each.
+const CHAPTERS: [(u32, &str, &str); 3] =
+	[(1, "c1_state_machine", "sm"), (2, "c2_blockchain", "bc"), (3, "c3_consensus", "cs")];
+
+const USAGE: &str = "usage: cargo xtask new-exercise CHAPTER NAME [TITLE]";
+
+/// Everything needed to write one new exercise.
+struct Exercise {
+	chapter: u32,
+	prefix: &'static str,
+	part: u32,
+	/// The module name including the part, such as `p7_fee_market`.
+	module: String,
+	title: String,
+}
+
+/// The part number of a module file name such as `p4_batched_extrinsics.rs`.
+fn part_of(file_name: &str) -> Option<u32> {
+	let rest = file_name.strip_prefix('p')?.strip_suffix(".rs")?;
+	rest.split_once('_')?.0.parse().ok()
+}
+
+/// Whether the name is a valid, conventional module name.
+fn is_snake_case(name: &str) -> bool {
+	name.starts_with(|c: char| c.is_ascii_lowercase())
+		&& name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// `fee_market` becomes `Fee Market`.
+fn title_case(name: &str) -> String {
+	let words: Vec<String> = name
+		.split('_')
+		.filter(|word| !word.is_empty())
+		.map(|word| word[..1].to_uppercase() + &word[1..])
+		.collect();
+	words.join(" ")
+}
+
+/// The new module file.
+fn module_template(exercise: &Exercise) -> String {
+	let Exercise { prefix, part, title, .. } = exercise;
+	format!(
+		"//! TODO: Introduce {title}. Explain the idea this part teaches, how it builds on the \
+		 previous\n//! parts, and what the student is asked to implement.\n\
+		 \n\
+		 /// TODO: Replace this with the types and functions of the exercise.\n\
+		 pub fn exercise() -> u64 {{\n\
+		 \ttodo!(\"Exercise 1\")\n\
+		 }}\n\
+		 \n\
+		 #[test]\n\
+		 fn {prefix}_{part}_exercise() {{\n\
+		 \tassert_eq!(exercise(), 0);\n\
+		 }}\n"
+	)
+}
+
+/// Whether the line declares a numbered part, such as `pub mod p4_batched_extrinsics;`.
+fn declares_part(line: &str) -> bool {
+	let line = line.trim_start();
+	let line = line.strip_prefix("pub(crate) ").or(line.strip_prefix("pub ")).unwrap_or(line);
+	line.strip_prefix("mod p")
+		.is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+/// Declare the module right after the last numbered part of the chapter's `mod.rs`.
+fn register_module(mod_rs: &str, module: &str) -> Result<String, String> {
+	let mut lines: Vec<&str> = mod_rs.lines().collect();
+	let last = lines
+		.iter()
+		.rposition(|line| declares_part(line))
+		.ok_or("the chapter's mod.rs declares no numbered parts")?;
+	let declaration = format!("mod {module};");
+	lines.insert(last + 1, &declaration);
+	Ok(lines.join("\n") + "\n")
+}
+
+/// Add an exercise group to the grader's `GROUPS`, after the chapter's last numbered part.
+fn register_grader_group(grader: &str, exercise: &Exercise) -> Result<String, String> {
+	let Exercise { chapter, prefix, part, title, .. } = exercise;
+	let mut lines: Vec<String> = grader.lines().map(String::from).collect();
+	let start = lines
+		.iter()
+		.position(|line| line.starts_with("const GROUPS: [(&str, &str); "))
+		.ok_or("the grader has no GROUPS table")?;
+	let is_part_group = |line: &String| {
+		line.split_once(&format!("\"{prefix}_"))
+			.is_some_and(|(_, rest)| rest.starts_with(|c: char| c.is_ascii_digit()))
+	};
+	let end = start
+		+ lines[start..]
+			.iter()
+			.position(|line| line == "];")
+			.ok_or("the GROUPS table is not closed")?;
+	let last = start
+		+ lines[start..end]
+			.iter()
+			.rposition(is_part_group)
+			.ok_or(format!("the grader has no {prefix}_ groups to follow"))?;
+
+	let count: usize = lines[start]
+		.strip_prefix("const GROUPS: [(&str, &str); ")
+		.and_then(|rest| rest.strip_suffix("] = ["))
+		.and_then(|count| count.parse().ok())
+		.ok_or("the GROUPS table has an unexpected header")?;
+	lines[start] = format!("const GROUPS: [(&str, &str); {}] = [", count + 1);
+	lines.insert(
+		last + 1,
+		format!("\t(\"Chapter {chapter} Part {part}: {title}\", \"{prefix}_{part}_\"),"),
+	);
+	Ok(lines.join("\n") + "\n")
+}
+
+fn new_exercise(root: &Path, args: &[String]) -> Result<(), String> {
+	let (chapter, name, title) = match args {
+		[chapter, name] => (chapter, name, title_case(name)),
+		[chapter, name, title] => (chapter, name, title.clone()),
+		_ => return Err(USAGE.into()),
+	};
+	let (chapter, directory, prefix) = CHAPTERS
+		.iter()
+		.find(|(number, ..)| chapter.parse() == Ok(*number))
+		.copied()
+		.ok_or(format!("chapter {chapter} has no exercises; choose 1, 2, or 3"))?;
+	if !is_snake_case(name) {
+		return Err(format!("`{name}` is not a snake_case module name"));
+	}
+
+	let chapter_dir = root.join("src").join(directory);
+	let entries = fs::read_dir(&chapter_dir).map_err(|e| format!("reading {directory}: {e}"))?;
+	let part = entries
+		.filter_map(|entry| part_of(&entry.ok()?.file_name().to_string_lossy()))
+		.max()
+		.unwrap_or(0)
+		+ 1;
+	let exercise = Exercise { chapter, prefix, part, module: format!("p{part}_{name}"), title };
+
+	let mod_rs_path = chapter_dir.join("mod.rs");
+	let grader_path = root.join("src/bin/grader.rs");
+	let read =
+		|path: &Path| fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()));
+	// Work out every edit before writing anything, so a failure leaves the tree untouched.
+	let mod_rs = register_module(&read(&mod_rs_path)?, &exercise.module)?;
+	let grader = register_grader_group(&read(&grader_path)?, &exercise)?;
+	let module_path = chapter_dir.join(format!("{}.rs", exercise.module));
+
+	let write = |path: &Path, contents: &str| {
+		fs::write(path, contents).map_err(|e| format!("{}: {e}", path.display()))
+	};
+	write(&module_path, &module_template(&exercise))?;
+	write(&mod_rs_path, &mod_rs)?;
+	write(&grader_path, &grader)?;
+
+	println!("Created {}", module_path.display());
+	println!("Registered it in {} and in the grader.", mod_rs_path.display());
+	println!(
+		"Remember to describe Chapter {chapter} Part {part} in the README's table of contents."
+	);
+	Ok(())
+}
+
+fn main() -> ExitCode {
+	let args: Vec<String> = env::args().skip(1).collect();
+	let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+		.parent()
+		.expect("xtask is in the repository");
+	let result = match args.first().map(String::as_str) {
+		Some("new-exercise") => new_exercise(root, &args[1..]),
+		_ => Err(USAGE.into()),
+	};
+	match result {
+		Ok(()) => ExitCode::SUCCESS,
+		Err(message) => {
+			eprintln!("{message}");
+			ExitCode::FAILURE
+		},
+	}
+}
+
+#[cfg(test)]
+fn example() -> Exercise {
+	Exercise {
+		chapter: 2,
+		prefix: "bc",
+		part: 7,
+		module: "p7_fee_market".into(),
+		title: "Fee Market".into(),
+	}
+}
+
+#[test]
+fn part_numbers_and_names() {
+	assert_eq!(part_of("p4_batched_extrinsics.rs"), Some(4));
+	assert_eq!(part_of("p12_x.rs"), Some(12));
+	assert_eq!(part_of("mod.rs"), None);
+	assert_eq!(part_of("profiler.rs"), None);
+	assert!(is_snake_case("fee_market2"));
+	assert!(!is_snake_case("FeeMarket"));
+	assert!(!is_snake_case("2fees"));
+	assert_eq!(title_case("fee_market"), "Fee Market");
+}
+
+#[test]
+fn registers_after_the_last_part() {
+	let mod_rs = "mod analysis;\nmod p1_pow;\npub(crate) mod p3_poa; // note\nmod viz;\n";
+
+	assert_eq!(
+		register_module(mod_rs, "p4_new").unwrap(),
+		"mod analysis;\nmod p1_pow;\npub(crate) mod p3_poa; // note\nmod p4_new;\nmod viz;\n"
+	);
+	assert!(register_module("mod viz;\n", "p1_new").is_err());
+}
+
+#[test]
+fn adds_a_grader_group() {
+	let grader = "const GROUPS: [(&str, &str); 3] = [\n\
+		\t(\"Chapter 2 Part 6: Rich State\", \"bc_6_\"),\n\
+		\t(\"Chapter 2: Export\", \"bc_export_\"),\n\
+		\t(\"Chapter 3 Part 1: PoW\", \"cs_1_\"),\n\
+		];\n";
+
+	assert_eq!(
+		register_grader_group(grader, &example()).unwrap(),
+		"const GROUPS: [(&str, &str); 4] = [\n\
+		\t(\"Chapter 2 Part 6: Rich State\", \"bc_6_\"),\n\
+		\t(\"Chapter 2 Part 7: Fee Market\", \"bc_7_\"),\n\
+		\t(\"Chapter 2: Export\", \"bc_export_\"),\n\
+		\t(\"Chapter 3 Part 1: PoW\", \"cs_1_\"),\n\
+		];\n"
+	);
+}
+
+#[test]
+fn template_names_the_test_after_the_part() {
+	let module = module_template(&example());
+
+	assert!(module.starts_with("//! TODO: Introduce Fee Market."));
+	assert!(module.contains("todo!(\"Exercise 1\")"));
+	assert!(module.contains("#[test]\nfn bc_7_exercise() {"));
+}